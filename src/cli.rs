@@ -1,4 +1,4 @@
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 
 /// git-autosnap command-line interface
 #[derive(Parser, Debug, Clone)]
@@ -12,6 +12,59 @@ pub struct Cli {
     pub command: Commands,
 }
 
+impl Cli {
+    /// Parse the process arguments, accepting git's attached `-M90%`/`-C75%` forms.
+    #[must_use]
+    pub fn parse_args() -> Self {
+        Self::parse_from(normalize_args(std::env::args_os()))
+    }
+}
+
+/// Subcommands taking `-M`/`-C` (see [`RenameArgs`]).
+const RENAME_COMMANDS: [&str; 3] = ["diff", "difftool", "file-log"];
+
+/// Rewrite `-M<score>`/`-C<score>` as `-M=<score>`: clap would otherwise read the
+/// digits as further short flags, since the value is optional.
+///
+/// Only the options of a subcommand that takes them are rewritten, up to a `--`;
+/// paths and other commands' arguments (`bisect run make -C1`) pass through as is.
+pub fn normalize_args<I>(args: I) -> Vec<std::ffi::OsString>
+where
+    I: IntoIterator<Item = std::ffi::OsString>,
+{
+    let mut args = args.into_iter();
+    let mut out: Vec<_> = args.next().into_iter().collect();
+    // Global flags such as `-v` come before the subcommand.
+    let mut rewrite = false;
+    for arg in args.by_ref() {
+        let name = arg
+            .to_str()
+            .filter(|s| !s.starts_with('-'))
+            .map(str::to_owned);
+        out.push(arg);
+        if let Some(name) = name {
+            rewrite = RENAME_COMMANDS.contains(&name.as_str());
+            break;
+        }
+    }
+    for arg in args {
+        let Some(s) = arg.to_str().filter(|_| rewrite) else {
+            out.push(arg);
+            continue;
+        };
+        if s == "--" {
+            rewrite = false;
+        }
+        let attached = ["-M", "-C"].into_iter().find_map(|flag| {
+            s.strip_prefix(flag)
+                .filter(|score| score.starts_with(|c: char| c.is_ascii_digit() || c == '.'))
+                .map(|score| format!("{flag}={score}"))
+        });
+        out.push(attached.map_or(arg, Into::into));
+    }
+    out
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// Initialize .autosnap bare repository in the current Git repo
@@ -37,11 +90,24 @@ pub enum Commands {
         message: Option<String>,
     },
 
-    /// Compact old snapshot history into a single baseline commit
+    /// Thin snapshot history by the retention policy and fold the oldest into a baseline
     Compact {
-        /// Compact snapshots older than N days (defaults to autosnap.compact.days)
+        /// Keep every snapshot from the last N days and fold older ones (overrides
+        /// autosnap.compact.retention; defaults to autosnap.compact.days)
         #[arg(long, value_name = "DAYS")]
         days: Option<u32>,
+
+        /// How to remove unreachable objects (defaults to autosnap.gc.backend, else native)
+        #[arg(long, value_enum, value_name = "BACKEND")]
+        gc: Option<GcArg>,
+
+        /// List which snapshots would be kept, folded or thinned, without rewriting
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Undo the last compact from its backup, keeping snapshots taken since
+        #[arg(long, conflicts_with_all = ["days", "dry_run", "gc"])]
+        rollback: bool,
     },
 
     /// Stop watcher (if running) and remove .autosnap directory
@@ -56,6 +122,10 @@ pub enum Commands {
         /// Interactive mode: select commit from list using skim
         #[arg(short, long)]
         interactive: bool,
+
+        /// Do not reapply file modification times recorded with the snapshot
+        #[arg(long)]
+        no_mtimes: bool,
     },
 
     /// Restore files from a snapshot to the working tree
@@ -72,14 +142,30 @@ pub enum Commands {
         #[arg(long)]
         force: bool,
 
-        /// Preview changes without actually restoring
+        /// Preview changes without actually restoring (exits 1 if unsaved edits would be lost)
         #[arg(long)]
         dry_run: bool,
 
-        /// Full restore: remove files not present in snapshot
+        /// Print the dry-run plan as JSON
+        #[arg(long, requires = "dry_run")]
+        json: bool,
+
+        /// Full restore: remove files not present in snapshot (ignored files are kept)
         #[arg(long)]
         full: bool,
 
+        /// Do not reapply file modification times recorded with the snapshot
+        #[arg(long)]
+        no_mtimes: bool,
+
+        /// How to update the main repository index after restoring
+        #[arg(long, value_enum, value_name = "MODE", default_value_t = IndexArg::Keep)]
+        index: IndexArg,
+
+        /// Reset restored paths in the index to the snapshot's staged state (same as --index staged)
+        #[arg(long, conflicts_with = "index")]
+        staged: bool,
+
         /// Specific paths to restore (if empty, restores all)
         #[arg(value_name = "PATH")]
         paths: Vec<String>,
@@ -99,23 +185,172 @@ pub enum Commands {
         #[arg(short, long)]
         interactive: bool,
 
-        /// Show only stats (files changed, insertions, deletions)
-        #[arg(long, conflicts_with = "name_only", conflicts_with = "name_status")]
+        /// Compare a main repository revision (e.g. HEAD, origin/main) with COMMIT1 or
+        /// the working tree
+        #[arg(long, value_name = "REV", conflicts_with = "commit2")]
+        against_main: Option<String>,
+
+        /// Never page the output (by default it goes through core.pager on a terminal)
+        #[arg(long)]
+        no_pager: bool,
+
+        /// Print nothing; implies --exit-code
+        #[arg(long)]
+        quiet: bool,
+
+        /// Exit with 1 if there were differences and 0 otherwise, like `git diff`
+        #[arg(long)]
+        exit_code: bool,
+
+        /// Patch that `git apply` accepts as is: binary files included, full object ids
+        #[arg(short, long, group = "format")]
+        patch: bool,
+
+        /// Write the diff as a `git format-patch` mail (mbox) for `git am`
+        #[arg(long, group = "format")]
+        format_patch: bool,
+
+        /// Show per-file insertions/deletions with a histogram
+        #[arg(long, group = "format")]
         stat: bool,
 
+        /// Total width of --stat output (defaults to the terminal width)
+        // Members of "format" that conflict with --stat waive `requires`, so name them.
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "stat",
+            conflicts_with_all = ["patch", "format_patch", "numstat", "shortstat", "dirstat", "name_only", "name_status"]
+        )]
+        stat_width: Option<usize>,
+
+        /// Machine-readable stat: added, deleted and path separated by tabs
+        #[arg(long, group = "format")]
+        numstat: bool,
+
+        /// Show only the summary line of --stat
+        #[arg(long, group = "format")]
+        shortstat: bool,
+
+        /// Show the share of changes per directory (git's changes,lines,files,cumulative,<limit>)
+        #[arg(
+            long,
+            group = "format",
+            value_name = "PARAMS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        dirstat: Option<String>,
+
         /// Show only names of changed files
-        #[arg(long, conflicts_with = "stat", conflicts_with = "name_status")]
+        #[arg(long, group = "format")]
         name_only: bool,
 
         /// Show names and status of changed files
-        #[arg(long, conflicts_with = "stat", conflicts_with = "name_only")]
+        #[arg(long, group = "format")]
         name_status: bool,
 
+        #[command(flatten)]
+        renames: RenameArgs,
+
+        #[command(flatten)]
+        tuning: DiffTuningArgs,
+
         /// Specific paths to diff (if empty, diffs all)
         #[arg(value_name = "PATH")]
         paths: Vec<String>,
     },
 
+    /// Open the changes between two snapshots in an external diff tool
+    Difftool {
+        /// Older snapshot
+        #[arg(value_name = "COMMIT1")]
+        commit1: String,
+
+        /// Newer snapshot
+        #[arg(value_name = "COMMIT2")]
+        commit2: String,
+
+        /// Compare two directories of changed files in one tool session
+        #[arg(short, long)]
+        dir_diff: bool,
+
+        /// Tool to run instead of autosnap.difftool / diff.tool
+        #[arg(short, long, value_name = "TOOL")]
+        tool: Option<String>,
+
+        #[command(flatten)]
+        renames: RenameArgs,
+
+        /// Limit to these paths
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+
+    /// Browse snapshots in a full-screen timeline with files and diffs
+    Browse,
+
+    /// Print files as they are in a snapshot
+    Cat {
+        /// Commit SHA or ref to read from (defaults to HEAD/latest)
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+
+        /// Interactive mode: pick the commit (unless given) and the files using skim
+        #[arg(short, long)]
+        interactive: bool,
+
+        /// Files to print, relative to the repository root
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+
+    /// Show the snapshots that changed a file, following renames
+    FileLog {
+        /// File to trace through the snapshot history
+        #[arg(value_name = "PATH")]
+        path: String,
+
+        /// Show at most N snapshots
+        #[arg(short = 'n', long = "max-count", value_name = "N")]
+        max_count: Option<usize>,
+
+        #[command(flatten)]
+        renames: RenameArgs,
+    },
+
+    /// List paths changed in the working tree since a snapshot or a point in time;
+    /// exits with 1 when anything changed
+    ChangedSince {
+        /// Snapshot SHA or ref, or a time: a duration ago (30m, 2h, 1d, 1w) or a local
+        /// date (2025-06-01, 2025-06-01 14:30)
+        #[arg(value_name = "SNAPSHOT|TIME")]
+        since: String,
+
+        /// Only consider these paths
+        #[arg(value_name = "PATH")]
+        paths: Vec<String>,
+    },
+
+    /// Summarise what was touched in recent snapshots, grouped by branch
+    Report {
+        /// Start of the window: a duration ago (8h, 1d, 1w) or a local date
+        /// (2025-06-01, 2025-06-01 14:30)
+        #[arg(long, value_name = "TIME", default_value = "1d")]
+        since: String,
+
+        /// Output format
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = ReportFormatArg::Text)]
+        format: ReportFormatArg,
+    },
+
+    /// Binary-search the snapshot chain for the first bad snapshot
+    Bisect {
+        #[command(subcommand)]
+        action: BisectAction,
+    },
+
     /// View watcher logs
     Logs {
         /// Follow log output (like tail -f)
@@ -127,3 +362,192 @@ pub enum Commands {
         lines: usize,
     },
 }
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BisectAction {
+    /// Start bisecting between a bad and a good snapshot
+    Start {
+        /// Snapshot known to be bad
+        #[arg(value_name = "BAD")]
+        bad: String,
+
+        /// Snapshot known to be good (must be older than BAD)
+        #[arg(value_name = "GOOD")]
+        good: String,
+    },
+
+    /// Mark a snapshot as good (defaults to the current candidate)
+    Good {
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+    },
+
+    /// Mark a snapshot as bad (defaults to the current candidate)
+    Bad {
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+    },
+
+    /// Skip a snapshot that cannot be tested (defaults to the current candidate)
+    Skip {
+        #[arg(value_name = "COMMIT")]
+        commit: Option<String>,
+    },
+
+    /// Run a command in each candidate: exit 0 = good, 125 = skip, 1-127 = bad
+    Run {
+        /// Command and arguments to run inside the extracted snapshot
+        #[arg(
+            value_name = "CMD",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        cmd: Vec<String>,
+    },
+
+    /// Finish bisecting and remove bisect state
+    Reset,
+}
+
+/// What `restore` does to the main repository index.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexArg {
+    /// Leave the index untouched
+    Keep,
+    /// Refresh index entries for restored paths from the working tree
+    Update,
+    /// Reset restored paths to the snapshot's staged state
+    Staged,
+}
+
+/// Garbage collection backends for `compact`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcArg {
+    /// Repack and prune in-process with libgit2
+    Native,
+    /// Run `git reflog expire` and `git gc --prune`
+    Git,
+}
+
+/// Output formats for `report`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormatArg {
+    /// Aligned plain text
+    Text,
+    /// Markdown tables, e.g. for a standup note
+    Markdown,
+    /// Machine-readable JSON
+    Json,
+}
+
+/// Line comparison and filtering flags for `diff`.
+#[derive(Args, Debug, Clone)]
+pub struct DiffTuningArgs {
+    /// Ignore whitespace when comparing lines
+    #[arg(short = 'w', long)]
+    pub ignore_all_space: bool,
+
+    /// Ignore changes in the amount of whitespace
+    #[arg(short = 'b', long)]
+    pub ignore_space_change: bool,
+
+    /// Ignore changes whose lines are all blank
+    #[arg(long)]
+    pub ignore_blank_lines: bool,
+
+    /// Show N lines of context around each change instead of 3
+    #[arg(short = 'U', long = "unified", value_name = "N")]
+    pub unified: Option<u32>,
+
+    /// Show changed words instead of lines (plain markers by default)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "plain",
+        conflicts_with_all = ["patch", "format_patch"]
+    )]
+    pub word_diff: Option<WordDiffArg>,
+
+    /// Only show changes of these kinds (A, C, D, M, R, T...); lowercase letters exclude
+    #[arg(long, value_name = "FILTER")]
+    pub diff_filter: Option<String>,
+}
+
+/// `--word-diff` modes.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDiffArg {
+    /// Changed words in colour
+    Color,
+    /// Changed words wrapped in [-removed-] and {+added+}
+    Plain,
+}
+
+/// Rename and copy detection flags shared by diff-like commands.
+#[derive(Args, Debug, Clone)]
+pub struct RenameArgs {
+    /// Detect renames, optionally with a similarity threshold like -M90% (default 50%)
+    #[arg(
+        short = 'M',
+        long = "find-renames",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "50%"
+    )]
+    pub find_renames: Option<String>,
+
+    /// Detect copies as well as renames, optionally with a similarity threshold like -C75%
+    #[arg(
+        short = 'C',
+        long = "find-copies",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "50%"
+    )]
+    pub find_copies: Option<String>,
+
+    /// Turn off rename detection
+    #[arg(long, conflicts_with_all = ["find_renames", "find_copies"])]
+    pub no_renames: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(args: &[&str]) -> Vec<String> {
+        normalize_args(args.iter().map(Into::into))
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn attached_scores_get_an_equals_sign() {
+        assert_eq!(
+            normalized(&["git-autosnap", "-v", "diff", "-M90%", "-C.5", "HEAD"]),
+            ["git-autosnap", "-v", "diff", "-M=90%", "-C=.5", "HEAD"]
+        );
+        assert_eq!(
+            normalized(&["git-autosnap", "file-log", "-M", "a.txt"]),
+            ["git-autosnap", "file-log", "-M", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn paths_and_other_commands_are_left_alone() {
+        assert_eq!(
+            normalized(&["git-autosnap", "bisect", "run", "make", "-C1"]),
+            ["git-autosnap", "bisect", "run", "make", "-C1"]
+        );
+        assert_eq!(
+            normalized(&["git-autosnap", "diff", "-M5", "--", "-M5"]),
+            ["git-autosnap", "diff", "-M=5", "--", "-M5"]
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+
+use super::{Command, note_specs};
+use crate::{app::context::AppContext, cli::BisectAction, core::git::BisectVerdict};
+
+pub struct BisectCommand<'a> {
+    pub action: &'a BisectAction,
+}
+
+impl Command for BisectCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        let root = &ctx.repo_root;
+        match self.action {
+            BisectAction::Start { bad, good } => note_specs(ctx, [bad.as_str(), good.as_str()]),
+            BisectAction::Good { commit }
+            | BisectAction::Bad { commit }
+            | BisectAction::Skip { commit } => note_specs(ctx, commit.as_deref()),
+            BisectAction::Run { .. } | BisectAction::Reset => {}
+        }
+        match self.action {
+            BisectAction::Start { bad, good } => crate::core::git::bisect_start(root, bad, good),
+            BisectAction::Good { commit } => {
+                crate::core::git::bisect_mark(root, BisectVerdict::Good, commit.as_deref())
+            }
+            BisectAction::Bad { commit } => {
+                crate::core::git::bisect_mark(root, BisectVerdict::Bad, commit.as_deref())
+            }
+            BisectAction::Skip { commit } => {
+                crate::core::git::bisect_mark(root, BisectVerdict::Skip, commit.as_deref())
+            }
+            BisectAction::Run { cmd } => crate::core::git::bisect_run(root, cmd),
+            BisectAction::Reset => crate::core::git::bisect_reset(root),
+        }
+    }
+}
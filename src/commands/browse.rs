@@ -0,0 +1,12 @@
+use anyhow::Result;
+
+use super::Command;
+use crate::app::context::AppContext;
+
+pub struct BrowseCommand;
+
+impl Command for BrowseCommand {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        crate::core::git::browse(&ctx.repo_root)
+    }
+}
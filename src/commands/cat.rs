@@ -0,0 +1,17 @@
+use anyhow::Result;
+
+use super::{Command, note_specs};
+use crate::app::context::AppContext;
+
+pub struct CatCommand<'a> {
+    pub commit: Option<&'a str>,
+    pub interactive: bool,
+    pub paths: &'a [String],
+}
+
+impl Command for CatCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        note_specs(ctx, self.commit);
+        crate::core::git::cat(&ctx.repo_root, self.commit, self.interactive, self.paths)
+    }
+}
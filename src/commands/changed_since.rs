@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+use super::Command;
+use crate::app::context::AppContext;
+
+pub struct ChangedSinceCommand<'a> {
+    pub since: &'a str,
+    pub paths: &'a [String],
+}
+
+impl Command for ChangedSinceCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        if let Some(note) = crate::core::git::revision::spec_note(&ctx.repo_root, self.since, true)
+        {
+            eprintln!("{note}");
+        }
+        let changed = crate::core::git::changed_since(&ctx.repo_root, self.since, self.paths)?;
+        // Like `git diff --exit-code`, so scripts can test for drift.
+        if changed {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
+}
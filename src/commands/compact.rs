@@ -1,26 +1,141 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use console::Term;
 
 use super::Command;
-use crate::app::context::AppContext;
+use crate::{
+    app::context::AppContext,
+    core::git::{
+        CompactOptions, GcBackend, GcProgress, RetentionPolicy,
+        backup::BACKUP_REF_PREFIX,
+        report::{commit_time, short_time},
+    },
+};
 
 pub struct CompactCommand {
     pub days: Option<u32>,
+    pub dry_run: bool,
+    pub rollback: bool,
+    /// Overrides `autosnap.gc.backend`.
+    pub gc: Option<GcBackend>,
 }
 
 impl Command for CompactCommand {
     fn run(&self, ctx: &AppContext) -> Result<()> {
-        let days = self.days.unwrap_or(ctx.cfg.compact_days);
-        let result = crate::core::git::compact(&ctx.repo_root, days)?;
+        if self.rollback {
+            let result = crate::core::git::rollback(&ctx.repo_root)?;
+            println!(
+                "rolled back the compact of {}: {} snapshots ({} taken since, replayed)",
+                backup_time(result.backup),
+                result.snapshots,
+                result.replayed
+            );
+            return Ok(());
+        }
+        // An explicit --days wins over the configured tiers.
+        let retention = match (self.days, &ctx.cfg.compact_retention) {
+            (Some(days), _) => RetentionPolicy::days(days),
+            (None, Some(spec)) => spec
+                .parse()
+                .with_context(|| format!("invalid autosnap.compact.retention `{spec}`"))?,
+            (None, None) => RetentionPolicy::days(ctx.cfg.compact_days),
+        };
+        let term = Term::stderr();
+        let show_progress = |step: GcProgress| {
+            let line = match step {
+                GcProgress::Counting(found) => format!("counting objects: {found}"),
+                GcProgress::Packing { done, total } => format!("packing objects: {done}/{total}"),
+                GcProgress::Compressing { done, total } => {
+                    format!("compressing objects: {done}/{total}")
+                }
+                GcProgress::Pruning { done, total } => format!("pruning: {done}/{total}"),
+            };
+            let _ = term.clear_line().and_then(|()| term.write_str(&line));
+        };
+        let opts = CompactOptions {
+            retention: &retention,
+            max_store_size: ctx.cfg.max_store_size,
+            gc: self.gc.unwrap_or(ctx.cfg.gc_backend),
+            prune_grace: ctx.cfg.gc_prune_grace,
+            progress: term.is_term().then_some(&show_progress),
+            backup_grace: Some(ctx.cfg.compact_backup_grace),
+        };
+        if self.dry_run {
+            println!("retention {retention}");
+            crate::core::git::compact_preview(&ctx.repo_root, &opts)?.print();
+            return Ok(());
+        }
+        let result = crate::core::git::compact(&ctx.repo_root, &opts);
+        if term.is_term() {
+            let _ = term.clear_line();
+        }
+        let result = result?;
 
         if result.rewritten {
             println!(
-                "compacted snapshots: {} -> {} commits",
+                "compacted snapshots: {} -> {} commits (retention {retention})",
                 result.before_commits, result.after_commits
             );
         } else {
             println!("no rewrite needed ({} commits)", result.before_commits);
         }
+        println!(
+            "store size: {} ({} reclaimed)",
+            format_bytes(result.store_bytes),
+            format_bytes(result.bytes_reclaimed)
+        );
+        if let Some(created) = result.backup {
+            println!(
+                "previous history kept in {BACKUP_REF_PREFIX}{created}/; undo with `git autosnap compact --rollback`"
+            );
+        }
+        if result.backups_expired > 0 {
+            println!("expired {} old compact backups", result.backups_expired);
+        }
+        if result.backups_dropped > 0 {
+            eprintln!(
+                "warning: dropped {} compact backups to fit autosnap.max-store-size",
+                result.backups_dropped
+            );
+        }
+        if result.objects_pruned > 0 {
+            println!("pruned {} unreachable objects", result.objects_pruned);
+        }
+        if result.over_budget
+            && let Some(budget) = ctx.cfg.max_store_size
+        {
+            if result.held_by_backup {
+                eprintln!(
+                    "warning: store still exceeds autosnap.max-store-size ({}); the backup of this compact holds the previous history until it expires (autosnap.compact.backup-grace)",
+                    format_bytes(budget)
+                );
+            } else {
+                eprintln!(
+                    "warning: store still exceeds autosnap.max-store-size ({}); only pinned and the newest snapshots are left",
+                    format_bytes(budget)
+                );
+            }
+        }
 
         Ok(())
     }
 }
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut unit = 0;
+    let mut scaled = bytes;
+    while scaled >= 1024 * 1024 && unit + 1 < UNITS.len() {
+        scaled /= 1024;
+        unit += 1;
+    }
+    // One decimal, from integer tenths.
+    let tenths = scaled * 10 / 1024;
+    format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+}
+
+fn backup_time(created: i64) -> String {
+    short_time(commit_time(git2::Time::new(created, 0)))
+}
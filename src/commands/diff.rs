@@ -1,25 +1,47 @@
 use anyhow::Result;
 
-use super::Command;
-use crate::{app::context::AppContext, core::git::DiffFormat};
+use super::{Command, note_specs};
+use crate::{
+    app::context::AppContext,
+    core::git::{DiffFormat, DiffRange, DiffSettings},
+};
 
 pub struct DiffCommand<'a> {
-    pub commit1: Option<&'a str>,
-    pub commit2: Option<&'a str>,
+    pub range: DiffRange<'a>,
     pub interactive: bool,
+    /// Send output through the user's pager when stdout is a terminal.
+    pub paginate: bool,
+    /// Exit with 1 when there were differences.
+    pub exit_code: bool,
     pub format: DiffFormat,
+    pub settings: DiffSettings,
     pub paths: &'a [String],
 }
 
 impl Command for DiffCommand<'_> {
     fn run(&self, ctx: &AppContext) -> Result<()> {
-        crate::core::git::diff(
+        note_specs(
+            ctx,
+            self.range.commit1.into_iter().chain(self.range.commit2),
+        );
+        let pager = if self.paginate {
+            crate::core::git::start_pager(&ctx.repo_root)?
+        } else {
+            None
+        };
+        let changed = crate::core::git::diff(
             &ctx.repo_root,
-            self.commit1,
-            self.commit2,
+            self.range,
             self.interactive,
             self.format,
+            &self.settings,
             self.paths,
-        )
+        )?;
+        // Exiting skips destructors, so wait for the pager before that.
+        drop(pager);
+        if self.exit_code && changed {
+            std::process::exit(1);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,15 @@
+use anyhow::Result;
+
+use super::{Command, note_specs};
+use crate::{app::context::AppContext, core::git::DifftoolOptions};
+
+pub struct DifftoolCommand<'a> {
+    pub options: DifftoolOptions<'a>,
+}
+
+impl Command for DifftoolCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        note_specs(ctx, [self.options.commit1, self.options.commit2]);
+        crate::core::git::difftool(&ctx.repo_root, self.options)
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+
+use super::Command;
+use crate::{app::context::AppContext, core::git::RenameDetection};
+
+pub struct FileLogCommand<'a> {
+    pub path: &'a str,
+    pub max_count: Option<usize>,
+    pub renames: RenameDetection,
+}
+
+impl Command for FileLogCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        crate::core::git::file_log(&ctx.repo_root, self.path, self.renames, self.max_count)
+    }
+}
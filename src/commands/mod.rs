@@ -2,14 +2,28 @@ use anyhow::Result;
 
 use crate::{
     app::context::AppContext,
-    cli::{Cli, Commands},
+    cli::{
+        Cli, Commands, DiffTuningArgs, GcArg, IndexArg, RenameArgs, ReportFormatArg, WordDiffArg,
+    },
+    core::git::{
+        DiffFilter, DiffFormat, DiffRange, DiffSettings, DifftoolOptions, DirstatOptions,
+        GcBackend, IndexMode, RenameDetection, ReportFormat, Whitespace, WordDiff,
+        parse_similarity, revision::spec_note,
+    },
 };
 
+pub mod bisect;
+pub mod browse;
+pub mod cat;
+pub mod changed_since;
 pub mod compact;
 pub mod diff;
+pub mod difftool;
+pub mod file_log;
 pub mod init;
 pub mod logs;
 pub mod once;
+pub mod report;
 pub mod restore;
 pub mod shell;
 pub mod start;
@@ -30,6 +44,7 @@ pub trait Command {
 ///
 /// # Errors
 /// Returns an error if the invoked subcommand fails.
+#[allow(clippy::too_many_lines)]
 pub fn dispatch(cli: &Cli) -> Result<()> {
     let ctx = AppContext::from_repo(cli.verbose)?;
 
@@ -54,18 +69,33 @@ pub fn dispatch(cli: &Cli) -> Result<()> {
         }
         Commands::Stop => stop::StopCommand.run(&ctx),
         Commands::Status => status::StatusCommand.run(&ctx),
-        Commands::Compact { days } => {
-            let cmd = compact::CompactCommand { days: *days };
+        Commands::Compact {
+            days,
+            gc,
+            dry_run,
+            rollback,
+        } => {
+            let cmd = compact::CompactCommand {
+                days: *days,
+                dry_run: *dry_run,
+                rollback: *rollback,
+                gc: gc.map(|gc| match gc {
+                    GcArg::Native => GcBackend::Native,
+                    GcArg::Git => GcBackend::Git,
+                }),
+            };
             cmd.run(&ctx)
         }
         Commands::Uninstall => uninstall::UninstallCommand.run(&ctx),
         Commands::Shell {
             commit,
             interactive,
+            no_mtimes,
         } => {
             let cmd = shell::ShellCommand {
                 commit: commit.as_deref(),
                 interactive: *interactive,
+                restore_mtimes: !*no_mtimes,
             };
             cmd.run(&ctx)
         }
@@ -74,7 +104,11 @@ pub fn dispatch(cli: &Cli) -> Result<()> {
             interactive,
             force,
             dry_run,
+            json,
             full,
+            no_mtimes,
+            index,
+            staged,
             paths,
         } => {
             let cmd = restore::RestoreCommand {
@@ -82,7 +116,7 @@ pub fn dispatch(cli: &Cli) -> Result<()> {
                 interactive: *interactive,
                 force: *force,
                 apply: if *dry_run {
-                    restore::RestoreApply::DryRun
+                    restore::RestoreApply::DryRun { json: *json }
                 } else {
                     restore::RestoreApply::Apply
                 },
@@ -91,37 +125,174 @@ pub fn dispatch(cli: &Cli) -> Result<()> {
                 } else {
                     restore::RestoreMode::Overlay
                 },
+                restore_mtimes: !*no_mtimes,
+                index: match (*staged, index) {
+                    (true, _) | (false, IndexArg::Staged) => IndexMode::Staged,
+                    (false, IndexArg::Update) => IndexMode::Update,
+                    (false, IndexArg::Keep) => IndexMode::Keep,
+                },
                 paths,
             };
             cmd.run(&ctx)
         }
+        Commands::Difftool {
+            commit1,
+            commit2,
+            dir_diff,
+            tool,
+            renames,
+            paths,
+        } => difftool::DifftoolCommand {
+            options: DifftoolOptions {
+                commit1,
+                commit2,
+                dir_diff: *dir_diff,
+                tool: tool.as_deref(),
+                renames: rename_detection(renames)?,
+                paths,
+            },
+        }
+        .run(&ctx),
+        Commands::FileLog {
+            path,
+            max_count,
+            renames,
+        } => file_log::FileLogCommand {
+            path,
+            max_count: *max_count,
+            renames: rename_detection(renames)?,
+        }
+        .run(&ctx),
+        Commands::Browse => browse::BrowseCommand.run(&ctx),
+        Commands::Cat {
+            commit,
+            interactive,
+            paths,
+        } => cat::CatCommand {
+            commit: commit.as_deref(),
+            interactive: *interactive,
+            paths,
+        }
+        .run(&ctx),
+        Commands::ChangedSince { since, paths } => {
+            changed_since::ChangedSinceCommand { since, paths }.run(&ctx)
+        }
+        Commands::Report { since, format } => report::ReportCommand {
+            since,
+            format: match format {
+                ReportFormatArg::Text => ReportFormat::Text,
+                ReportFormatArg::Markdown => ReportFormat::Markdown,
+                ReportFormatArg::Json => ReportFormat::Json,
+            },
+        }
+        .run(&ctx),
+        Commands::Bisect { action } => bisect::BisectCommand { action }.run(&ctx),
         Commands::Diff {
             commit1,
             commit2,
             interactive,
+            against_main,
+            no_pager,
+            quiet,
+            exit_code,
+            patch,
+            format_patch,
             stat,
+            stat_width,
+            numstat,
+            shortstat,
+            dirstat,
             name_only,
             name_status,
+            renames,
+            tuning,
             paths,
         } => {
-            let format = if *stat {
-                crate::core::git::DiffFormat::Stat
+            let format = if *quiet {
+                DiffFormat::Quiet
+            } else if *patch {
+                DiffFormat::Patch
+            } else if *format_patch {
+                DiffFormat::FormatPatch
+            } else if *stat {
+                DiffFormat::Stat { width: *stat_width }
+            } else if *numstat {
+                DiffFormat::NumStat
+            } else if *shortstat {
+                DiffFormat::ShortStat
+            } else if let Some(params) = dirstat {
+                DiffFormat::DirStat(DirstatOptions::parse(params)?)
             } else if *name_only {
-                crate::core::git::DiffFormat::NameOnly
+                DiffFormat::NameOnly
             } else if *name_status {
-                crate::core::git::DiffFormat::NameStatus
+                DiffFormat::NameStatus
             } else {
-                crate::core::git::DiffFormat::Unified
+                DiffFormat::Unified
             };
 
             let cmd = diff::DiffCommand {
-                commit1: commit1.as_deref(),
-                commit2: commit2.as_deref(),
+                range: DiffRange {
+                    commit1: commit1.as_deref(),
+                    commit2: commit2.as_deref(),
+                    against_main: against_main.as_deref(),
+                },
                 interactive: *interactive,
+                paginate: !*no_pager && !*interactive && !*quiet,
+                exit_code: *exit_code || *quiet,
                 format,
+                settings: diff_settings(renames, tuning)?,
                 paths,
             };
             cmd.run(&ctx)
         } // All commands are handled explicitly above
     }
 }
+
+// Tell the user when a commit spec resolved to something other than what it names:
+// a snapshot `compact` has since rewritten.
+fn note_specs<'a>(ctx: &AppContext, specs: impl IntoIterator<Item = &'a str>) {
+    for spec in specs {
+        if let Some(note) = spec_note(&ctx.repo_root, spec, false) {
+            eprintln!("{note}");
+        }
+    }
+}
+
+fn rename_detection(args: &RenameArgs) -> Result<RenameDetection> {
+    if args.no_renames {
+        return Ok(RenameDetection::disabled());
+    }
+    let mut detection = RenameDetection::default();
+    if let Some(score) = &args.find_renames {
+        detection.renames = Some(parse_similarity(score)?);
+    }
+    if let Some(score) = &args.find_copies {
+        detection.copies = Some(parse_similarity(score)?);
+    }
+    Ok(detection)
+}
+
+fn diff_settings(renames: &RenameArgs, tuning: &DiffTuningArgs) -> Result<DiffSettings> {
+    let whitespace = if tuning.ignore_all_space {
+        Whitespace::IgnoreAll
+    } else if tuning.ignore_space_change {
+        Whitespace::IgnoreChange
+    } else {
+        Whitespace::Exact
+    };
+    Ok(DiffSettings {
+        renames: rename_detection(renames)?,
+        whitespace,
+        ignore_blank_lines: tuning.ignore_blank_lines,
+        context_lines: tuning.unified,
+        word_diff: tuning.word_diff.map(|mode| match mode {
+            WordDiffArg::Color => WordDiff::Color,
+            WordDiffArg::Plain => WordDiff::Plain,
+        }),
+        filter: tuning
+            .diff_filter
+            .as_deref()
+            .map(DiffFilter::parse)
+            .transpose()?,
+    })
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
 
 use super::Command;
-use crate::app::context::AppContext;
+use crate::{
+    app::context::AppContext,
+    core::git::{SnapshotTrigger, snapshot_once},
+};
 
 pub struct OnceCommand<'a> {
     pub message: Option<&'a str>,
@@ -9,7 +12,7 @@ pub struct OnceCommand<'a> {
 
 impl Command for OnceCommand<'_> {
     fn run(&self, ctx: &AppContext) -> Result<()> {
-        if let Some(hash) = crate::core::git::snapshot_once(&ctx.repo_root, self.message)? {
+        if let Some(hash) = snapshot_once(&ctx.repo_root, self.message, SnapshotTrigger::Manual)? {
             println!("{hash}");
         }
         Ok(())
@@ -0,0 +1,20 @@
+use anyhow::Result;
+
+use super::Command;
+use crate::{app::context::AppContext, core::git::ReportFormat};
+
+pub struct ReportCommand<'a> {
+    pub since: &'a str,
+    pub format: ReportFormat,
+}
+
+impl Command for ReportCommand<'_> {
+    fn run(&self, ctx: &AppContext) -> Result<()> {
+        let report = crate::core::git::activity_report(&ctx.repo_root, self.since)?;
+        print!("{}", report.render(self.format)?);
+        if self.format == ReportFormat::Json {
+            println!();
+        }
+        Ok(())
+    }
+}
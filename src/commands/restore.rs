@@ -1,7 +1,10 @@
 use anyhow::Result;
 
-use super::Command;
-use crate::app::context::AppContext;
+use super::{Command, note_specs};
+use crate::{
+    app::context::AppContext,
+    core::git::{IndexMode, RestoreOptions},
+};
 
 pub struct RestoreCommand<'a> {
     pub commit: Option<&'a str>,
@@ -9,13 +12,15 @@ pub struct RestoreCommand<'a> {
     pub force: bool,
     pub apply: RestoreApply,
     pub mode: RestoreMode,
+    pub restore_mtimes: bool,
+    pub index: IndexMode,
     pub paths: &'a [String],
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum RestoreApply {
     Apply,
-    DryRun,
+    DryRun { json: bool },
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -26,16 +31,30 @@ pub enum RestoreMode {
 
 impl Command for RestoreCommand<'_> {
     fn run(&self, ctx: &AppContext) -> Result<()> {
-        let dry_run = matches!(self.apply, RestoreApply::DryRun);
+        note_specs(ctx, self.commit);
+        let (dry_run, json) = match self.apply {
+            RestoreApply::Apply => (false, false),
+            RestoreApply::DryRun { json } => (true, json),
+        };
         let full = matches!(self.mode, RestoreMode::Full);
-        crate::core::git::restore(
+        let plan = crate::core::git::restore(
             &ctx.repo_root,
-            self.commit,
-            self.interactive,
-            self.force,
-            dry_run,
-            full,
-            self.paths,
-        )
+            &RestoreOptions {
+                commit: self.commit,
+                interactive: self.interactive,
+                force: self.force,
+                dry_run,
+                json,
+                full,
+                restore_mtimes: self.restore_mtimes,
+                index: self.index,
+                paths: self.paths,
+            },
+        )?;
+        // Like `git diff --exit-code`: a dry run that would lose unsaved edits fails.
+        if dry_run && plan.touches_unsaved() {
+            std::process::exit(1);
+        }
+        Ok(())
     }
 }
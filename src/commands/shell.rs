@@ -1,15 +1,22 @@
 use anyhow::Result;
 
-use super::Command;
+use super::{Command, note_specs};
 use crate::app::context::AppContext;
 
 pub struct ShellCommand<'a> {
     pub commit: Option<&'a str>,
     pub interactive: bool,
+    pub restore_mtimes: bool,
 }
 
 impl Command for ShellCommand<'_> {
     fn run(&self, ctx: &AppContext) -> Result<()> {
-        crate::core::git::snapshot_shell(&ctx.repo_root, self.commit, self.interactive)
+        note_specs(ctx, self.commit);
+        crate::core::git::snapshot_shell(
+            &ctx.repo_root,
+            self.commit,
+            self.interactive,
+            self.restore_mtimes,
+        )
     }
 }
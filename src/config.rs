@@ -3,13 +3,31 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use git2::Repository;
 
+use crate::core::git::{GcBackend, revision::parse_ago};
+
 /// Autosnap configuration values sourced from git config.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct AutosnapConfig {
     /// Debounce window in milliseconds for the file watcher.
     pub debounce_ms: u64,
     /// Retention in days used by `compact`.
     pub compact_days: u32,
+    /// Tiered retention for `compact` (`AGE:EVERY,...`); overrides `compact_days`.
+    pub compact_retention: Option<String>,
+    /// Seconds `compact` keeps the backup of a rewritten chain; `0` keeps none.
+    pub compact_backup_grace: i64,
+    /// Upper bound in bytes on the snapshot object store; `compact` and the watcher
+    /// fold the oldest snapshots to stay under it.
+    pub max_store_size: Option<u64>,
+    /// How `compact` removes unreachable objects: `native` (libgit2) or `git`.
+    pub gc_backend: GcBackend,
+    /// Seconds unreachable loose objects survive a gc, in case a write is still in
+    /// progress; `0` prunes them at once.
+    pub gc_prune_grace: i64,
+    /// Record per-file mtimes with each snapshot so restores can reapply them.
+    pub record_mtimes: bool,
+    /// Also record full permission bits (only the executable bit is kept in trees).
+    pub record_permissions: bool,
 }
 
 impl Default for AutosnapConfig {
@@ -17,6 +35,13 @@ impl Default for AutosnapConfig {
         Self {
             debounce_ms: 1000,
             compact_days: 60,
+            compact_retention: None,
+            compact_backup_grace: 7 * 86_400,
+            max_store_size: None,
+            gc_backend: GcBackend::Native,
+            gc_prune_grace: 3600,
+            record_mtimes: true,
+            record_permissions: false,
         }
     }
 }
@@ -52,6 +77,49 @@ impl AutosnapConfig {
         {
             out.compact_days = vu;
         }
+        if let Ok(v) = cfg.get_string("autosnap.compact.retention")
+            && !v.trim().is_empty()
+        {
+            out.compact_retention = Some(v);
+        }
+        // A duration like `7d` or `12h`; `0` disables backups.
+        if let Ok(v) = cfg.get_string("autosnap.compact.backup-grace") {
+            out.compact_backup_grace = if v.trim() == "0" {
+                0
+            } else {
+                parse_ago(v.trim())
+                    .with_context(|| format!("invalid autosnap.compact.backup-grace `{v}`"))?
+                    .whole_seconds()
+                    .max(0)
+            };
+        }
+        // Accepts k/m/g suffixes (`500m`, `2g`).
+        if let Ok(v) = cfg.get_i64("autosnap.max-store-size")
+            && let Ok(vu) = u64::try_from(v)
+            && vu > 0
+        {
+            out.max_store_size = Some(vu);
+        }
+        if let Ok(v) = cfg.get_string("autosnap.gc.backend") {
+            out.gc_backend = v.parse().context("invalid autosnap.gc.backend")?;
+        }
+        // A duration like `1h` or `30m`; `0` prunes unreachable objects at once.
+        if let Ok(v) = cfg.get_string("autosnap.gc.prune-grace") {
+            out.gc_prune_grace = if v.trim() == "0" {
+                0
+            } else {
+                parse_ago(v.trim())
+                    .with_context(|| format!("invalid autosnap.gc.prune-grace `{v}`"))?
+                    .whole_seconds()
+                    .max(0)
+            };
+        }
+        if let Ok(v) = cfg.get_bool("autosnap.metadata.mtimes") {
+            out.record_mtimes = v;
+        }
+        if let Ok(v) = cfg.get_bool("autosnap.metadata.permissions") {
+            out.record_permissions = v;
+        }
 
         Ok(out)
     }
@@ -0,0 +1,220 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+/// Ref namespace holding the refs as they were before each `compact` rewrite.
+///
+/// `refs/autosnap/backup/<unix time>/<ref>` mirrors `refs/<ref>` (so the old tip is at
+/// `.../heads/<branch>`); `.../compacted` marks the tip the rewrite produced.
+pub const BACKUP_REF_PREFIX: &str = "refs/autosnap/backup/";
+
+const COMPACTED: &str = "compacted";
+
+fn backup_prefix(created: i64) -> String {
+    format!("{BACKUP_REF_PREFIX}{created}/")
+}
+
+/// Creation times (unix seconds) of the backups, oldest first.
+///
+/// # Errors
+/// Returns an error if the refs cannot be listed.
+pub fn list_backups(repo: &Repository) -> Result<Vec<i64>> {
+    let mut backups = BTreeSet::new();
+    for name in ref_names(repo)? {
+        if let Some(created) = name
+            .strip_prefix(BACKUP_REF_PREFIX)
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(created, _)| created.parse().ok())
+        {
+            backups.insert(created);
+        }
+    }
+    Ok(backups.into_iter().collect())
+}
+
+/// Mirror every ref except other backups into a new backup taken at `now`. Returns
+/// its creation time, which names it.
+///
+/// # Errors
+/// Returns an error if the refs cannot be read or written.
+pub fn create_backup(repo: &Repository, now: i64) -> Result<i64> {
+    let existing = list_backups(repo)?;
+    let mut created = now;
+    while existing.contains(&created) {
+        created += 1;
+    }
+    let prefix = backup_prefix(created);
+    for (name, target) in live_refs(repo)? {
+        let mirror = format!("{prefix}{}", &name["refs/".len()..]);
+        repo.reference(&mirror, target, false, "autosnap: back up before compact")
+            .with_context(|| format!("failed to back up {name}"))?;
+    }
+    Ok(created)
+}
+
+/// Record `tip` as the chain the backed-up compact left behind.
+///
+/// # Errors
+/// Returns an error if the ref cannot be written.
+pub fn mark_compacted(repo: &Repository, created: i64, tip: Oid) -> Result<()> {
+    repo.reference(
+        &format!("{}{COMPACTED}", backup_prefix(created)),
+        tip,
+        true,
+        "autosnap: compacted tip",
+    )
+    .with_context(|| format!("failed to mark backup {created}"))?;
+    Ok(())
+}
+
+/// The tip the backed-up compact left behind; `None` if it did not finish.
+#[must_use]
+pub fn compacted_tip(repo: &Repository, created: i64) -> Option<Oid> {
+    repo.refname_to_id(&format!("{}{COMPACTED}", backup_prefix(created)))
+        .ok()
+}
+
+/// Point every ref back where backup `created` recorded it, deleting refs made since.
+///
+/// That includes the notes refs: the restored snapshots get back the metadata compact
+/// pruned, and the rewrite map returns to its state before the compact, whose entries
+/// all name snapshots now live again. Notes added since are lost with the snapshots
+/// they annotate; `rollback` writes them again for the snapshots it replays.
+///
+/// # Errors
+/// Returns an error if the refs cannot be read or written.
+pub fn restore_backup(repo: &Repository, created: i64) -> Result<()> {
+    let prefix = backup_prefix(created);
+    let mut saved = HashMap::new();
+    for name in ref_names(repo)? {
+        if let Some(rest) = name.strip_prefix(&prefix)
+            && rest != COMPACTED
+        {
+            saved.insert(format!("refs/{rest}"), repo.refname_to_id(&name)?);
+        }
+    }
+    for (name, _) in live_refs(repo)? {
+        if !saved.contains_key(&name) {
+            repo.find_reference(&name)
+                .and_then(|mut reference| reference.delete())
+                .with_context(|| format!("failed to delete {name}"))?;
+        }
+    }
+    for (name, target) in saved {
+        repo.reference(&name, target, true, "autosnap: roll back compact")
+            .with_context(|| format!("failed to restore {name}"))?;
+    }
+    Ok(())
+}
+
+/// Delete backup `created`.
+///
+/// # Errors
+/// Returns an error if the refs cannot be deleted.
+pub fn drop_backup(repo: &Repository, created: i64) -> Result<()> {
+    let prefix = backup_prefix(created);
+    for name in ref_names(repo)? {
+        if name.starts_with(&prefix) {
+            repo.find_reference(&name)
+                .and_then(|mut reference| reference.delete())
+                .with_context(|| format!("failed to delete {name}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Delete the backups taken more than `grace` seconds before `now`. Returns how many.
+///
+/// # Errors
+/// Returns an error if the refs cannot be listed or deleted.
+pub fn expire_backups(repo: &Repository, now: i64, grace: i64) -> Result<usize> {
+    let expired: Vec<i64> = list_backups(repo)?
+        .into_iter()
+        .filter(|&created| now - created >= grace)
+        .collect();
+    for &created in &expired {
+        drop_backup(repo, created)?;
+    }
+    Ok(expired.len())
+}
+
+fn ref_names(repo: &Repository) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for reference in repo.references().context("failed to list refs")? {
+        let reference = reference.context("failed to read ref")?;
+        if let Some(name) = reference.name() {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+// Direct refs outside the backups, with their targets.
+fn live_refs(repo: &Repository) -> Result<Vec<(String, Oid)>> {
+    let mut refs = Vec::new();
+    for reference in repo.references().context("failed to list refs")? {
+        let reference = reference.context("failed to read ref")?;
+        if let (Some(name), Some(target)) = (reference.name(), reference.target())
+            && name.starts_with("refs/")
+            && !name.starts_with(BACKUP_REF_PREFIX)
+        {
+            refs.push((name.to_string(), target));
+        }
+    }
+    Ok(refs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_refs_and_expires_backups() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("t", "t@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let old = repo
+            .commit(Some("HEAD"), &sig, &sig, "old", &tree, &[])
+            .unwrap();
+        repo.reference("refs/autosnap/pins/a", old, false, "test")
+            .unwrap();
+        let notes_ref = "refs/notes/autosnap-metadata";
+        repo.note(&sig, &sig, Some(notes_ref), old, "old", false)
+            .unwrap();
+
+        let created = create_backup(&repo, 1_000).unwrap();
+        assert_eq!(create_backup(&repo, 1_000).unwrap(), 1_001);
+        let parent = repo.find_commit(old).unwrap();
+        let new = repo
+            .commit(Some("HEAD"), &sig, &sig, "new", &tree, &[&parent])
+            .unwrap();
+        repo.find_reference("refs/autosnap/pins/a")
+            .unwrap()
+            .delete()
+            .unwrap();
+        repo.reference("refs/autosnap/pins/b", new, false, "test")
+            .unwrap();
+        repo.note_delete(old, Some(notes_ref), &sig, &sig).unwrap();
+        repo.note(&sig, &sig, Some(notes_ref), new, "new", false)
+            .unwrap();
+        mark_compacted(&repo, created, new).unwrap();
+        assert_eq!(compacted_tip(&repo, created), Some(new));
+
+        restore_backup(&repo, created).unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(old));
+        assert!(repo.find_reference("refs/autosnap/pins/a").is_ok());
+        assert!(repo.find_reference("refs/autosnap/pins/b").is_err());
+        // Notes come back as they were, without the one added since.
+        let note = repo.find_note(Some(notes_ref), old).unwrap();
+        assert_eq!(note.message(), Some("old"));
+        assert!(repo.find_note(Some(notes_ref), new).is_err());
+
+        assert_eq!(list_backups(&repo).unwrap(), [1_000, 1_001]);
+        assert_eq!(expire_backups(&repo, 1_500, 500).unwrap(), 1);
+        assert_eq!(list_backups(&repo).unwrap(), [1_001]);
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{Oid, Repository};
+
+use super::{
+    diff::{
+        Gitattributes, RenameDetection, all_deltas, collect_file_stats, detect_renames,
+        print_diff_name_status, summary_line,
+    },
+    extract::extract_tree_to_path,
+    repo::{autosnap_dir, state_dir},
+    revision::{resolve_commit, short_summary, snapshot_chain},
+    shell::report_extract_issues,
+};
+
+const BISECT_LOG: &str = "BISECT_LOG";
+const BISECT_WORKTREE: &str = "bisect";
+
+/// Verdict recorded for a snapshot during bisection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BisectVerdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl BisectVerdict {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Good => "good",
+            Self::Bad => "bad",
+            Self::Skip => "skip",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "good" => Some(Self::Good),
+            "bad" => Some(Self::Bad),
+            "skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Persisted bisection state: every verdict recorded so far, in order.
+#[derive(Debug, Default)]
+struct BisectState {
+    marks: Vec<(BisectVerdict, Oid)>,
+}
+
+impl BisectState {
+    fn load(state: &Path) -> Result<Option<Self>> {
+        let path = state.join(BISECT_LOG);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        let mut marks = Vec::new();
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let (verdict, oid) = line
+                .split_once(' ')
+                .with_context(|| format!("malformed bisect log line: {line}"))?;
+            let verdict = BisectVerdict::parse(verdict)
+                .with_context(|| format!("unknown bisect verdict: {verdict}"))?;
+            let oid = Oid::from_str(oid.trim())
+                .with_context(|| format!("invalid oid in bisect log: {oid}"))?;
+            marks.push((verdict, oid));
+        }
+        Ok(Some(Self { marks }))
+    }
+
+    fn save(&self, state: &Path) -> Result<()> {
+        fs::create_dir_all(state)
+            .with_context(|| format!("failed to create {}", state.display()))?;
+        let path = state.join(BISECT_LOG);
+        let mut file = fs::File::create(&path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        for (verdict, oid) in &self.marks {
+            writeln!(file, "{} {oid}", verdict.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the bisection currently stands on the snapshot chain.
+#[derive(Debug)]
+enum BisectStep {
+    /// Test this snapshot next; `remaining` candidates are left after it.
+    Test { oid: Oid, remaining: usize },
+    /// The first bad snapshot has been identified.
+    Found { bad: Oid, previous: Oid },
+    /// Only skipped snapshots remain between the last good and first bad one.
+    Ambiguous { candidates: Vec<Oid> },
+}
+
+/// Begin a bisection between a known bad and a known good snapshot.
+///
+/// # Errors
+/// Returns an error if the commits cannot be resolved or the range is invalid.
+pub fn bisect_start(repo_root: &Path, bad: &str, good: &str) -> Result<()> {
+    let autosnap = open_autosnap_dir(repo_root)?;
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+
+    let bad = resolve_commit(&repo, bad)?.id();
+    let good = resolve_commit(&repo, good)?.id();
+
+    let state = BisectState {
+        marks: vec![(BisectVerdict::Bad, bad), (BisectVerdict::Good, good)],
+    };
+    // Validate ordering before persisting anything.
+    let chain = snapshot_chain(&repo)?;
+    next_step(&chain, &state)?;
+    state.save(&state_dir(repo_root)?)?;
+
+    advance(repo_root, &repo, &state)
+}
+
+/// Record a verdict for a snapshot (the current candidate when `commit` is `None`).
+///
+/// # Errors
+/// Returns an error if no bisection is in progress or the commit cannot be resolved.
+pub fn bisect_mark(repo_root: &Path, verdict: BisectVerdict, commit: Option<&str>) -> Result<()> {
+    let autosnap = open_autosnap_dir(repo_root)?;
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let bisect_dir = state_dir(repo_root)?;
+    let mut state = load_state(&bisect_dir)?;
+    let chain = snapshot_chain(&repo)?;
+
+    let oid = match commit {
+        Some(spec) => resolve_commit(&repo, spec)?.id(),
+        None => match next_step(&chain, &state)? {
+            BisectStep::Test { oid, .. } => oid,
+            _ => bail!("bisection already finished; run `git autosnap bisect reset`"),
+        },
+    };
+
+    state.marks.push((verdict, oid));
+    next_step(&chain, &state)?;
+    state.save(&bisect_dir)?;
+
+    advance(repo_root, &repo, &state)
+}
+
+/// Automatically bisect by running `cmd` inside each extracted candidate.
+///
+/// Exit status 0 marks the snapshot good, 125 skips it, 1-127 marks it bad, and
+/// anything else (including death by signal) aborts the run.
+///
+/// # Errors
+/// Returns an error if no bisection is in progress, the command cannot be spawned,
+/// or it exits with a status that aborts the bisection.
+pub fn bisect_run(repo_root: &Path, cmd: &[String]) -> Result<()> {
+    let Some((program, args)) = cmd.split_first() else {
+        bail!("bisect run requires a command");
+    };
+
+    let autosnap = open_autosnap_dir(repo_root)?;
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let bisect_dir = state_dir(repo_root)?;
+    let mut state = load_state(&bisect_dir)?;
+    let chain = snapshot_chain(&repo)?;
+
+    loop {
+        let oid = match next_step(&chain, &state)? {
+            BisectStep::Test { oid, .. } => oid,
+            step => return report(&repo, &step),
+        };
+
+        let commit = repo.find_commit(oid)?;
+        let (short_id, subject) = short_summary(&commit);
+        println!("running {} on {short_id} {subject}", cmd.join(" "));
+
+        let temp_dir = tempfile::TempDir::new().context("failed to create temporary directory")?;
+        report_extract_issues(&extract_tree_to_path(
+            &repo,
+            &commit.tree()?,
+            temp_dir.path(),
+        )?);
+
+        let status = Command::new(program)
+            .args(args)
+            .current_dir(temp_dir.path())
+            .env("AUTOSNAP_BISECT_COMMIT", oid.to_string())
+            .status()
+            .with_context(|| format!("failed to run bisect command: {program}"))?;
+
+        let verdict = match status.code() {
+            Some(0) => BisectVerdict::Good,
+            Some(125) => BisectVerdict::Skip,
+            Some(code) if (1..128).contains(&code) => BisectVerdict::Bad,
+            _ => bail!("bisect run aborted: command exited with {status}"),
+        };
+        println!("  -> {}", verdict.as_str());
+
+        state.marks.push((verdict, oid));
+        state.save(&bisect_dir)?;
+    }
+}
+
+/// End the bisection, removing its state and extracted candidate.
+///
+/// # Errors
+/// Returns an error if the state or worktree cannot be removed.
+pub fn bisect_reset(repo_root: &Path) -> Result<()> {
+    open_autosnap_dir(repo_root)?;
+    let bisect_dir = state_dir(repo_root)?;
+    let log = bisect_dir.join(BISECT_LOG);
+    if log.exists() {
+        fs::remove_file(&log).with_context(|| format!("failed to remove {}", log.display()))?;
+    }
+    let worktree = bisect_worktree(&bisect_dir);
+    if worktree.exists() {
+        fs::remove_dir_all(&worktree)
+            .with_context(|| format!("failed to remove {}", worktree.display()))?;
+    }
+    println!("bisect state cleared");
+    Ok(())
+}
+
+fn open_autosnap_dir(repo_root: &Path) -> Result<PathBuf> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    Ok(autosnap)
+}
+
+fn load_state(bisect_dir: &Path) -> Result<BisectState> {
+    BisectState::load(bisect_dir)?
+        .context("no bisection in progress; run `git autosnap bisect start <bad> <good>` first")
+}
+
+fn bisect_worktree(bisect_dir: &Path) -> PathBuf {
+    bisect_dir.join(BISECT_WORKTREE)
+}
+
+// Compute the next step from the recorded verdicts.
+fn next_step(chain: &[Oid], state: &BisectState) -> Result<BisectStep> {
+    let position = |oid: &Oid| {
+        chain
+            .iter()
+            .position(|c| c == oid)
+            .with_context(|| format!("{oid} is not on the snapshot chain"))
+    };
+
+    let mut bad_idx: Option<usize> = None;
+    let mut good_idxs = Vec::new();
+    let mut skipped = HashSet::new();
+    for (verdict, oid) in &state.marks {
+        let idx = position(oid)?;
+        match verdict {
+            BisectVerdict::Bad => bad_idx = Some(bad_idx.map_or(idx, |b| b.min(idx))),
+            BisectVerdict::Good => good_idxs.push(idx),
+            BisectVerdict::Skip => {
+                skipped.insert(idx);
+            }
+        }
+    }
+
+    let bad_idx = bad_idx.context("no bad snapshot recorded")?;
+    if good_idxs.iter().any(|g| *g >= bad_idx) {
+        bail!("a good snapshot is not older than the first bad snapshot; check your marks");
+    }
+    let good_idx = good_idxs
+        .into_iter()
+        .max()
+        .context("no good snapshot recorded")?;
+
+    let candidates: Vec<usize> = (good_idx + 1..bad_idx)
+        .filter(|i| !skipped.contains(i))
+        .collect();
+
+    if candidates.is_empty() {
+        if (good_idx + 1..bad_idx).any(|i| skipped.contains(&i)) {
+            return Ok(BisectStep::Ambiguous {
+                candidates: (good_idx + 1..=bad_idx).map(|i| chain[i]).collect(),
+            });
+        }
+        return Ok(BisectStep::Found {
+            bad: chain[bad_idx],
+            previous: chain[bad_idx - 1],
+        });
+    }
+
+    // Choose the untested candidate nearest the midpoint of the open range.
+    let mid = good_idx.midpoint(bad_idx);
+    let pick = candidates
+        .iter()
+        .copied()
+        .min_by_key(|i| i.abs_diff(mid))
+        .context("no bisect candidate available")?;
+
+    Ok(BisectStep::Test {
+        oid: chain[pick],
+        remaining: candidates.len() - 1,
+    })
+}
+
+// Extract the next candidate for manual testing, or report the result.
+fn advance(repo_root: &Path, repo: &Repository, state: &BisectState) -> Result<()> {
+    let chain = snapshot_chain(repo)?;
+    let step = next_step(&chain, state)?;
+    let BisectStep::Test { oid, remaining } = step else {
+        return report(repo, &step);
+    };
+
+    let worktree = bisect_worktree(&state_dir(repo_root)?);
+    if worktree.exists() {
+        fs::remove_dir_all(&worktree)
+            .with_context(|| format!("failed to clear {}", worktree.display()))?;
+    }
+    fs::create_dir_all(&worktree)
+        .with_context(|| format!("failed to create {}", worktree.display()))?;
+
+    let commit = repo.find_commit(oid)?;
+    report_extract_issues(&extract_tree_to_path(repo, &commit.tree()?, &worktree)?);
+
+    let (short_id, subject) = short_summary(&commit);
+    let steps = usize::BITS - remaining.leading_zeros();
+    println!("Bisecting: {remaining} snapshots left to test after this (roughly {steps} steps)");
+    println!("  Commit: {short_id} {subject}");
+    println!("  Location: {}", worktree.display());
+    println!("Mark it with `git autosnap bisect good|bad|skip`");
+    Ok(())
+}
+
+fn report(repo: &Repository, step: &BisectStep) -> Result<()> {
+    match step {
+        BisectStep::Found { bad, previous } => {
+            let commit = repo.find_commit(*bad)?;
+            let (short_id, subject) = short_summary(&commit);
+            println!("{short_id} is the first bad snapshot");
+            println!("  {subject}");
+            println!();
+
+            let old_tree = repo.find_commit(*previous)?.tree()?;
+            let mut diff = repo
+                .diff_tree_to_tree(Some(&old_tree), Some(&commit.tree()?), None)
+                .context("failed to diff first bad snapshot against its parent")?;
+            detect_renames(&mut diff, RenameDetection::default())?;
+            let all = all_deltas(&diff);
+            println!(
+                "{}",
+                summary_line(&collect_file_stats(
+                    repo,
+                    &diff,
+                    &all,
+                    &Gitattributes::default()
+                )?)
+            );
+            print_diff_name_status(&diff, &all)?;
+        }
+        BisectStep::Ambiguous { candidates } => {
+            println!("There are only skipped snapshots left to test.");
+            println!("The first bad snapshot could be any of:");
+            for oid in candidates {
+                let commit = repo.find_commit(*oid)?;
+                let (short_id, subject) = short_summary(&commit);
+                println!("  {short_id} {subject}");
+            }
+        }
+        BisectStep::Test { .. } => {}
+    }
+    Ok(())
+}
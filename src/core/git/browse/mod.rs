@@ -0,0 +1,473 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, Write as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{Oid, Repository};
+use ratatui::{
+    DefaultTerminal,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    widgets::ListState,
+};
+use time::OffsetDateTime;
+
+mod model;
+mod ui;
+
+use model::{
+    DiffBase, DiffView, FileChange, Snapshot, changed_files, file_diff, load_timeline, snapshot_at,
+};
+
+use super::{
+    diff::build_working_tree_from_status,
+    pin::{pinned_snapshots, set_pinned},
+    repo::autosnap_dir,
+    restore::{IndexMode, RestoreOptions, restore, restore_file, restore_hunk},
+    revision::parse_time,
+    shell::snapshot_shell,
+};
+
+/// Browse snapshots in a full-screen terminal UI.
+///
+/// The timeline lists every snapshot, newest first; the middle pane the files the
+/// selected one changed (or, with `w`, would change when restored); the right pane
+/// the selected file's diff. Keys restore a file or hunk, pin snapshots, open one in
+/// a shell, check one out, and jump to a point in time.
+///
+/// # Errors
+/// Returns an error if the store cannot be read or the terminal cannot be set up.
+pub fn browse(repo_root: &Path) -> Result<()> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let mut app = App::new(repo, repo_root.to_path_buf())?;
+
+    let mut terminal = ratatui::try_init().context("failed to set up the terminal")?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// The pane keys act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Timeline,
+    Files,
+    Diff,
+}
+
+/// A change that waits for `y` before it is made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confirm {
+    RestoreFile,
+    RestoreHunk(usize),
+    Checkout,
+}
+
+/// What the bottom line is reading.
+enum Prompt {
+    Confirm(Confirm, String),
+    Time(String),
+}
+
+/// What the event loop does after a key.
+enum Next {
+    Continue,
+    Quit,
+    Shell(Oid),
+    Checkout(Oid),
+}
+
+struct App {
+    repo: Repository,
+    repo_root: PathBuf,
+    snapshots: Vec<Snapshot>,
+    pins: HashSet<Oid>,
+    timeline: ListState,
+    files: Vec<FileChange>,
+    file_list: ListState,
+    diff: DiffView,
+    /// First diff line on screen.
+    scroll: usize,
+    /// Diff lines on screen, as of the last draw.
+    diff_height: usize,
+    focus: Pane,
+    base: DiffBase,
+    /// Tree of the working tree, built when first compared against.
+    work_tree: Option<Oid>,
+    prompt: Option<Prompt>,
+    status: String,
+}
+
+impl App {
+    fn new(repo: Repository, repo_root: PathBuf) -> Result<Self> {
+        let snapshots = load_timeline(&repo)?;
+        if snapshots.is_empty() {
+            bail!("No snapshots found");
+        }
+        let pins = pinned_snapshots(&repo)?;
+        let mut app = Self {
+            repo,
+            repo_root,
+            snapshots,
+            pins,
+            timeline: ListState::default().with_selected(Some(0)),
+            files: Vec::new(),
+            file_list: ListState::default(),
+            diff: DiffView::default(),
+            scroll: 0,
+            diff_height: 0,
+            focus: Pane::Timeline,
+            base: DiffBase::Parent,
+            work_tree: None,
+            prompt: None,
+            status: String::new(),
+        };
+        app.load_files();
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal
+                .draw(|frame| ui::draw(frame, self))
+                .context("failed to draw")?;
+            let Event::Key(key) = event::read().context("failed to read terminal input")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle_key(key) {
+                Next::Continue => {}
+                Next::Quit => return Ok(()),
+                Next::Shell(id) => {
+                    self.suspend(terminal, false, |repo_root| {
+                        snapshot_shell(repo_root, Some(&id.to_string()), false, true)
+                    })?;
+                }
+                Next::Checkout(id) => {
+                    self.suspend(terminal, true, |repo_root| {
+                        restore(
+                            repo_root,
+                            &RestoreOptions {
+                                commit: Some(&id.to_string()),
+                                interactive: false,
+                                force: false,
+                                dry_run: false,
+                                json: false,
+                                full: false,
+                                restore_mtimes: false,
+                                index: IndexMode::Keep,
+                                paths: &[],
+                            },
+                        )
+                        .map(drop)
+                    })?;
+                    self.work_tree_changed();
+                }
+            }
+        }
+    }
+
+    /// Leave the full screen to run `action` on the plain terminal, then come back.
+    /// With `pause`, its output stays up until Enter.
+    fn suspend(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        pause: bool,
+        action: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<()> {
+        ratatui::restore();
+        let result = action(&self.repo_root);
+        if let Err(err) = &result {
+            eprintln!("error: {err:#}");
+        }
+        if pause || result.is_err() {
+            print!("\nPress Enter to return to browse...");
+            let _ = io::stdout().flush();
+            let _ = io::stdin().lock().read_line(&mut String::new());
+        }
+        *terminal = ratatui::try_init().context("failed to set up the terminal")?;
+        self.status = match result {
+            Ok(()) => String::new(),
+            Err(err) => format!("{err:#}"),
+        };
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Next {
+        if let Some(prompt) = self.prompt.take() {
+            return self.handle_prompt(prompt, key);
+        }
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Next::Quit,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Next::Quit;
+            }
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => self.cycle_focus(true),
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => self.cycle_focus(false),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(self.page()),
+            KeyCode::PageUp => self.move_by(-self.page()),
+            KeyCode::Home | KeyCode::Char('g') => self.move_by(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX),
+            KeyCode::Char('n') => self.jump_hunk(true),
+            KeyCode::Char('N') => self.jump_hunk(false),
+            KeyCode::Char('w') => self.toggle_base(),
+            KeyCode::Char('p') => self.toggle_pin(),
+            KeyCode::Char('r') => self.ask_restore_file(),
+            KeyCode::Char('R') => self.ask_restore_hunk(),
+            KeyCode::Char('s') => return Next::Shell(self.snapshot().id),
+            KeyCode::Char('c') => {
+                let snapshot = self.snapshot();
+                let question = format!(
+                    "Check out snapshot {} into the working tree? (y/n)",
+                    snapshot.short
+                );
+                self.prompt = Some(Prompt::Confirm(Confirm::Checkout, question));
+            }
+            KeyCode::Char('t') => self.prompt = Some(Prompt::Time(String::new())),
+            _ => {}
+        }
+        Next::Continue
+    }
+
+    fn handle_prompt(&mut self, prompt: Prompt, key: KeyEvent) -> Next {
+        match prompt {
+            Prompt::Confirm(action, question) => match key.code {
+                KeyCode::Char('y' | 'Y') => return self.confirmed(action),
+                KeyCode::Char('n' | 'N') | KeyCode::Esc => {}
+                _ => self.prompt = Some(Prompt::Confirm(action, question)),
+            },
+            Prompt::Time(mut input) => match key.code {
+                KeyCode::Enter => self.jump_to_time(&input),
+                KeyCode::Esc => {}
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.prompt = Some(Prompt::Time(input));
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.prompt = Some(Prompt::Time(input));
+                }
+                _ => self.prompt = Some(Prompt::Time(input)),
+            },
+        }
+        Next::Continue
+    }
+
+    fn confirmed(&mut self, action: Confirm) -> Next {
+        let id = self.snapshot().id;
+        let hunk = match action {
+            Confirm::Checkout => return Next::Checkout(id),
+            Confirm::RestoreFile => None,
+            Confirm::RestoreHunk(hunk) => Some(hunk),
+        };
+        let Some(path) = self.selected_file().map(|file| file.path.clone()) else {
+            return Next::Continue;
+        };
+        let result = match hunk {
+            None => restore_file(&self.repo, &self.repo_root, id, &path)
+                .map(|()| format!("Restored {path}")),
+            Some(hunk) => restore_hunk(&self.repo, &self.repo_root, id, &path, hunk)
+                .map(|()| format!("Restored hunk {} of {path}", hunk + 1)),
+        };
+        self.status = result.unwrap_or_else(|err| format!("{err:#}"));
+        self.work_tree_changed();
+        Next::Continue
+    }
+
+    fn snapshot(&self) -> &Snapshot {
+        &self.snapshots[self.timeline.selected().unwrap_or(0)]
+    }
+
+    fn selected_file(&self) -> Option<&FileChange> {
+        self.files.get(self.file_list.selected()?)
+    }
+
+    fn page(&self) -> isize {
+        isize::try_from(self.diff_height.max(2) - 1).unwrap_or(1)
+    }
+
+    const fn cycle_focus(&mut self, forward: bool) {
+        self.focus = match (self.focus, forward) {
+            (Pane::Timeline, true) | (Pane::Diff, false) => Pane::Files,
+            (Pane::Files, true) | (Pane::Timeline, false) => Pane::Diff,
+            (Pane::Diff, true) | (Pane::Files, false) => Pane::Timeline,
+        };
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        match self.focus {
+            Pane::Timeline => {
+                let current = self.timeline.selected().unwrap_or(0);
+                let next = step(current, delta, self.snapshots.len());
+                if next != current {
+                    self.timeline.select(Some(next));
+                    self.load_files();
+                }
+            }
+            Pane::Files => {
+                let Some(current) = self.file_list.selected() else {
+                    return;
+                };
+                let next = step(current, delta, self.files.len());
+                if next != current {
+                    self.file_list.select(Some(next));
+                    self.load_diff();
+                }
+            }
+            Pane::Diff => self.scroll = step(self.scroll, delta, self.diff.lines.len()),
+        }
+    }
+
+    fn jump_hunk(&mut self, forward: bool) {
+        let target = if forward {
+            self.diff.hunks.iter().find(|&&start| start > self.scroll)
+        } else {
+            self.diff
+                .hunks
+                .iter()
+                .rev()
+                .find(|&&start| start < self.scroll)
+        };
+        if let Some(&start) = target {
+            self.scroll = start;
+        }
+    }
+
+    fn toggle_base(&mut self) {
+        self.base = match self.base {
+            DiffBase::Parent => DiffBase::WorkTree,
+            DiffBase::WorkTree => DiffBase::Parent,
+        };
+        self.load_files();
+    }
+
+    fn toggle_pin(&mut self) {
+        let id = self.snapshot().id;
+        let pin = !self.pins.contains(&id);
+        match set_pinned(&self.repo, id, pin) {
+            Ok(_) if pin => {
+                self.pins.insert(id);
+                self.status = format!("Pinned {}", self.snapshot().short);
+            }
+            Ok(_) => {
+                self.pins.remove(&id);
+                self.status = format!("Unpinned {}", self.snapshot().short);
+            }
+            Err(err) => self.status = format!("{err:#}"),
+        }
+    }
+
+    fn ask_restore_file(&mut self) {
+        let Some(file) = self.selected_file() else {
+            self.status = "No file selected".to_string();
+            return;
+        };
+        let question = format!(
+            "Restore {} from snapshot {}? (y/n)",
+            file.path,
+            self.snapshot().short
+        );
+        self.prompt = Some(Prompt::Confirm(Confirm::RestoreFile, question));
+    }
+
+    fn ask_restore_hunk(&mut self) {
+        if self.base != DiffBase::WorkTree {
+            self.status = "Hunks restore against the working tree; press w first".to_string();
+            return;
+        }
+        let (Some(file), Some(hunk)) = (self.selected_file(), self.diff.hunk_at(self.scroll))
+        else {
+            self.status = "No hunk to restore".to_string();
+            return;
+        };
+        let question = format!(
+            "Restore hunk {}/{} of {}? (y/n)",
+            hunk + 1,
+            self.diff.hunks.len(),
+            file.path
+        );
+        self.prompt = Some(Prompt::Confirm(Confirm::RestoreHunk(hunk), question));
+    }
+
+    fn jump_to_time(&mut self, input: &str) {
+        let Some(time) = parse_time(input, OffsetDateTime::now_utc()) else {
+            self.status = format!("cannot parse time: {input}");
+            return;
+        };
+        if let Some(index) = snapshot_at(&self.snapshots, time.unix_timestamp()) {
+            self.timeline.select(Some(index));
+            self.focus = Pane::Timeline;
+            self.load_files();
+        }
+    }
+
+    /// The working tree moved: rebuild what was compared against it.
+    fn work_tree_changed(&mut self) {
+        self.work_tree = None;
+        if self.base == DiffBase::WorkTree {
+            let selected = self.file_list.selected();
+            self.load_files();
+            if let Some(index) = selected.filter(|&index| index < self.files.len()) {
+                self.file_list.select(Some(index));
+                self.load_diff();
+            }
+        }
+    }
+
+    fn load_files(&mut self) {
+        let id = self.snapshot().id;
+        let files = self
+            .base_tree()
+            .and_then(|base| changed_files(&self.repo, id, base));
+        self.files = files.unwrap_or_else(|err| {
+            self.status = format!("{err:#}");
+            Vec::new()
+        });
+        self.file_list.select((!self.files.is_empty()).then_some(0));
+        self.load_diff();
+    }
+
+    fn load_diff(&mut self) {
+        self.scroll = 0;
+        let Some(path) = self.selected_file().map(|file| file.path.clone()) else {
+            self.diff = DiffView::default();
+            return;
+        };
+        let id = self.snapshot().id;
+        self.diff =
+            file_diff(&self.repo, &self.repo_root, id, self.base, &path).unwrap_or_else(|err| {
+                self.status = format!("{err:#}");
+                DiffView::default()
+            });
+    }
+
+    /// The tree files are compared with; `None` for the snapshot's parent.
+    fn base_tree(&mut self) -> Result<Option<Oid>> {
+        if self.base == DiffBase::Parent {
+            return Ok(None);
+        }
+        if self.work_tree.is_none() {
+            let tree = build_working_tree_from_status(&self.repo, &self.repo_root)?;
+            self.work_tree = Some(tree.id());
+        }
+        Ok(self.work_tree)
+    }
+}
+
+/// Move `current` by `delta` within `0..len`.
+fn step(current: usize, delta: isize, len: usize) -> usize {
+    current
+        .saturating_add_signed(delta)
+        .min(len.saturating_sub(1))
+}
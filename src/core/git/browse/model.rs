@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use git2::{DiffLineType, Oid, Patch, Repository, Tree};
+
+use super::super::{
+    diff::{all_deltas, status_letter},
+    report::{branch_of, commit_time, note_of, short_time},
+    restore::working_file_sides,
+    revision::snapshot_chain,
+    snapshot::trigger_of,
+};
+
+/// One snapshot of the timeline.
+pub struct Snapshot {
+    pub id: Oid,
+    pub short: String,
+    /// Commit time in seconds.
+    pub time: i64,
+    pub when: String,
+    pub branch: String,
+    pub trigger: String,
+    pub note: String,
+}
+
+/// Every snapshot reachable from HEAD, newest first.
+pub fn load_timeline(repo: &Repository) -> Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    for id in snapshot_chain(repo)?.into_iter().rev() {
+        let commit = repo
+            .find_commit(id)
+            .with_context(|| format!("failed to load snapshot {id}"))?;
+        let message = commit.message().unwrap_or("<no message>");
+        snapshots.push(Snapshot {
+            id,
+            short: format!("{id:.7}"),
+            time: commit.time().seconds(),
+            when: short_time(commit_time(commit.time())),
+            branch: branch_of(message).to_string(),
+            trigger: trigger_of(message).unwrap_or("-").to_string(),
+            note: note_of(message).to_string(),
+        });
+    }
+    Ok(snapshots)
+}
+
+/// What the files and diff panes compare a snapshot with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffBase {
+    /// The previous snapshot: what this one changed.
+    Parent,
+    /// The working tree: what restoring this snapshot would change.
+    WorkTree,
+}
+
+/// A file that differs between the base and the snapshot.
+pub struct FileChange {
+    pub path: String,
+    pub status: char,
+}
+
+/// Files changed from `base` (the parent's tree when `None`) to snapshot `id`.
+pub fn changed_files(repo: &Repository, id: Oid, base: Option<Oid>) -> Result<Vec<FileChange>> {
+    let commit = repo.find_commit(id)?;
+    let base = match base {
+        Some(tree) => Some(repo.find_tree(tree)?),
+        None => commit.parents().next().map(|p| p.tree()).transpose()?,
+    };
+    let diff = repo.diff_tree_to_tree(base.as_ref(), Some(&commit.tree()?), None)?;
+    Ok(all_deltas(&diff)
+        .into_iter()
+        .filter_map(|idx| {
+            let delta = diff.get_delta(idx)?;
+            let file = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())?;
+            Some(FileChange {
+                path: file.to_string_lossy().into_owned(),
+                status: status_letter(&delta),
+            })
+        })
+        .collect())
+}
+
+/// Kind of a diff pane line, which decides its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Hunk,
+    Context,
+    Added,
+    Removed,
+    Note,
+}
+
+/// The diff pane's content for one file.
+#[derive(Default)]
+pub struct DiffView {
+    pub lines: Vec<(LineKind, String)>,
+    /// Index into `lines` of each hunk header.
+    pub hunks: Vec<usize>,
+}
+
+impl DiffView {
+    fn note(text: &str) -> Self {
+        Self {
+            lines: vec![(LineKind::Note, text.to_string())],
+            hunks: Vec::new(),
+        }
+    }
+
+    /// The hunk shown at line `top`: the last one starting at or above it.
+    pub fn hunk_at(&self, top: usize) -> Option<usize> {
+        match self.hunks.partition_point(|&start| start <= top) {
+            0 if self.hunks.is_empty() => None,
+            0 => Some(0),
+            next => Some(next - 1),
+        }
+    }
+}
+
+/// The diff of `path` from `base` to snapshot `id`.
+///
+/// Against the working tree the hunks are numbered like
+/// [`restore_hunk`](super::super::restore::restore_hunk) numbers them.
+pub fn file_diff(
+    repo: &Repository,
+    repo_root: &Path,
+    id: Oid,
+    base: DiffBase,
+    path: &str,
+) -> Result<DiffView> {
+    let (old, new) = match base {
+        DiffBase::WorkTree => working_file_sides(repo, repo_root, id, path)?,
+        DiffBase::Parent => {
+            let commit = repo.find_commit(id)?;
+            let parent = commit.parents().next().map(|p| p.tree()).transpose()?;
+            (
+                blob_content(repo, parent.as_ref(), path),
+                blob_content(repo, Some(&commit.tree()?), path),
+            )
+        }
+    };
+    diff_view(&old, &new)
+}
+
+fn blob_content(repo: &Repository, tree: Option<&Tree<'_>>, path: &str) -> Vec<u8> {
+    let Some(entry) = tree.and_then(|tree| tree.get_path(Path::new(path)).ok()) else {
+        return Vec::new();
+    };
+    repo.find_blob(entry.id())
+        .map(|blob| blob.content().to_vec())
+        .unwrap_or_default()
+}
+
+fn diff_view(old: &[u8], new: &[u8]) -> Result<DiffView> {
+    if old.contains(&0) || new.contains(&0) {
+        return Ok(DiffView::note("Binary file differs"));
+    }
+    let patch = Patch::from_buffers(old, None, new, None, None).context("failed to diff file")?;
+    if patch.num_hunks() == 0 {
+        return Ok(DiffView::note("(no changes)"));
+    }
+    let mut view = DiffView::default();
+    for hunk in 0..patch.num_hunks() {
+        let (header, line_count) = patch.hunk(hunk)?;
+        view.hunks.push(view.lines.len());
+        view.lines.push((
+            LineKind::Hunk,
+            String::from_utf8_lossy(header.header())
+                .trim_end()
+                .to_string(),
+        ));
+        for idx in 0..line_count {
+            let line = patch.line_in_hunk(hunk, idx)?;
+            let (kind, prefix) = match line.origin_value() {
+                DiffLineType::Addition => (LineKind::Added, '+'),
+                DiffLineType::Deletion => (LineKind::Removed, '-'),
+                DiffLineType::Context => (LineKind::Context, ' '),
+                _ => (LineKind::Note, '\\'),
+            };
+            let text = String::from_utf8_lossy(line.content());
+            let text = if kind == LineKind::Note {
+                " No newline at end of file".into()
+            } else {
+                text.trim_end_matches(['\n', '\r']).replace('\t', "    ")
+            };
+            view.lines.push((kind, format!("{prefix}{text}")));
+        }
+    }
+    Ok(view)
+}
+
+/// Index of the newest snapshot taken at or before `time`, or the oldest one when
+/// all are newer. `snapshots` is newest first.
+pub fn snapshot_at(snapshots: &[Snapshot], time: i64) -> Option<usize> {
+    snapshots
+        .iter()
+        .position(|snapshot| snapshot.time <= time)
+        .or_else(|| snapshots.len().checked_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(time: i64) -> Snapshot {
+        Snapshot {
+            id: Oid::zero(),
+            short: String::new(),
+            time,
+            when: String::new(),
+            branch: String::new(),
+            trigger: String::new(),
+            note: String::new(),
+        }
+    }
+
+    #[test]
+    fn time_jumps_to_the_newest_snapshot_not_after_it() {
+        let timeline = [snapshot(300), snapshot(200), snapshot(100)];
+        assert_eq!(snapshot_at(&timeline, 250), Some(1));
+        assert_eq!(snapshot_at(&timeline, 200), Some(1));
+        assert_eq!(snapshot_at(&timeline, 999), Some(0));
+        assert_eq!(snapshot_at(&timeline, 50), Some(2));
+        assert_eq!(snapshot_at(&[], 50), None);
+    }
+
+    #[test]
+    fn diff_view_tracks_hunks() {
+        let old = b"a\nB\nc\nd\ne\nf\ng\nh\ni\nJ\nk\n";
+        let new = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        let view = diff_view(old, new).unwrap();
+        assert_eq!(view.hunks.len(), 2);
+        assert_eq!(view.lines[view.hunks[0]].0, LineKind::Hunk);
+        assert!(view.lines.contains(&(LineKind::Added, "+b".to_string())));
+        assert_eq!(view.hunk_at(0), Some(0));
+        assert_eq!(view.hunk_at(view.hunks[1]), Some(1));
+        assert_eq!(view.hunk_at(view.lines.len()), Some(1));
+    }
+}
@@ -0,0 +1,144 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, Paragraph},
+};
+
+use super::{App, DiffBase, Pane, Prompt, model::LineKind};
+
+const KEYS: &str = "tab: pane  j/k: move  n/N: hunk  w: vs parent/working tree  r/R: restore file/hunk  p: pin  s: shell  c: checkout  t: jump to time  q: quit";
+
+pub fn draw(frame: &mut Frame<'_>, app: &mut App) {
+    let [main, status, keys] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [timeline, files, diff] = Layout::horizontal([
+        Constraint::Percentage(30),
+        Constraint::Percentage(25),
+        Constraint::Percentage(45),
+    ])
+    .areas(main);
+
+    draw_timeline(frame, app, timeline);
+    draw_files(frame, app, files);
+    draw_diff(frame, app, diff);
+
+    let status_line = match &app.prompt {
+        Some(Prompt::Confirm(_, question)) => Line::from(question.as_str().yellow().bold()),
+        Some(Prompt::Time(input)) => Line::from(vec![
+            "Jump to (30m, 2h, 1d, 2025-06-01 14:30): ".yellow(),
+            Span::raw(input.as_str()),
+        ]),
+        None => Line::from(app.status.as_str()),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+    frame.render_widget(Paragraph::new(KEYS.dark_gray()), keys);
+}
+
+fn pane(title: String, focused: bool) -> Block<'static> {
+    let block = Block::bordered().title(title);
+    if focused {
+        block.border_style(Style::new().fg(Color::Yellow))
+    } else {
+        block
+    }
+}
+
+const fn highlight() -> Style {
+    Style::new().add_modifier(Modifier::REVERSED)
+}
+
+fn draw_timeline(frame: &mut Frame<'_>, app: &mut App, area: Rect) {
+    let items: Vec<ListItem<'_>> = app
+        .snapshots
+        .iter()
+        .map(|snapshot| {
+            let pin = if app.pins.contains(&snapshot.id) {
+                "* ".yellow()
+            } else {
+                Span::raw("  ")
+            };
+            ListItem::new(Line::from(vec![
+                pin,
+                snapshot.short.as_str().yellow(),
+                Span::raw(format!("  {}  ", snapshot.when)),
+                snapshot.branch.as_str().cyan(),
+                Span::raw(format!("  {}  {}", snapshot.trigger, snapshot.note)),
+            ]))
+        })
+        .collect();
+    let title = format!(" Snapshots ({}) ", app.snapshots.len());
+    let list = List::new(items)
+        .block(pane(title, app.focus == Pane::Timeline))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.timeline);
+}
+
+fn draw_files(frame: &mut Frame<'_>, app: &mut App, area: Rect) {
+    let items: Vec<ListItem<'_>> = app
+        .files
+        .iter()
+        .map(|file| {
+            let status = match file.status {
+                'A' => Span::raw("A").green(),
+                'D' => Span::raw("D").red(),
+                letter => Span::raw(letter.to_string()).yellow(),
+            };
+            ListItem::new(Line::from(vec![
+                status,
+                Span::raw(format!(" {}", file.path)),
+            ]))
+        })
+        .collect();
+    let title = match app.base {
+        DiffBase::Parent => format!(" Changed ({}) ", app.files.len()),
+        DiffBase::WorkTree => format!(" Restore would change ({}) ", app.files.len()),
+    };
+    let list = List::new(items)
+        .block(pane(title, app.focus == Pane::Files))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, area, &mut app.file_list);
+}
+
+fn draw_diff(frame: &mut Frame<'_>, app: &mut App, area: Rect) {
+    app.diff_height = usize::from(area.height.saturating_sub(2));
+    let current = app
+        .diff
+        .hunk_at(app.scroll)
+        .map(|hunk| app.diff.hunks[hunk]);
+    let lines: Vec<Line<'_>> = app
+        .diff
+        .lines
+        .iter()
+        .enumerate()
+        .skip(app.scroll)
+        .take(app.diff_height)
+        .map(|(idx, (kind, text))| {
+            let style = match kind {
+                LineKind::Hunk if Some(idx) == current => {
+                    Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                }
+                LineKind::Hunk => Style::new().fg(Color::Cyan),
+                LineKind::Added => Style::new().fg(Color::Green),
+                LineKind::Removed => Style::new().fg(Color::Red),
+                LineKind::Context => Style::new(),
+                LineKind::Note => Style::new().fg(Color::DarkGray),
+            };
+            Line::styled(text.as_str(), style)
+        })
+        .collect();
+    let title = match (app.selected_file(), app.base) {
+        (None, _) => " Diff ".to_string(),
+        (Some(file), DiffBase::Parent) => format!(" {} ", file.path),
+        (Some(file), DiffBase::WorkTree) => format!(" {} (working tree -> snapshot) ", file.path),
+    };
+    frame.render_widget(
+        Paragraph::new(lines).block(pane(title, app.focus == Pane::Diff)),
+        area,
+    );
+}
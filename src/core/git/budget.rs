@@ -0,0 +1,202 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    fs,
+    hash::BuildHasher,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use git2::{FileMode, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+
+/// Bytes the object store of the autosnap repo at `autosnap` takes on disk: its packs
+/// and loose objects.
+///
+/// # Errors
+/// Returns an error if the object directory cannot be read.
+pub fn store_size(autosnap: &Path) -> Result<u64> {
+    dir_size(&autosnap.join("objects"))
+}
+
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut total = 0;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Pick the oldest snapshot to keep so that folding every unpinned snapshot before it
+/// should free about `excess` of the store's `store` bytes.
+///
+/// Each blob's size counts as freed once the last snapshot holding it is folded, scaled
+/// by how much the store compresses. `None` when folding cannot free anything: the
+/// chain is down to its newest snapshot and pinned ones.
+///
+/// # Errors
+/// Returns an error if the snapshots' trees cannot be read.
+pub fn budget_baseline<S: BuildHasher>(
+    repo: &Repository,
+    chain: &[Oid],
+    pinned: &HashSet<Oid, S>,
+    store: u64,
+    excess: u64,
+) -> Result<Option<usize>> {
+    let Some(newest) = chain.len().checked_sub(1) else {
+        return Ok(None);
+    };
+    let (last_held, sizes) = blob_lifetimes(repo, chain)?;
+
+    // Blobs of pinned snapshots stay whatever is folded.
+    let mut kept = HashSet::new();
+    for id in chain.iter().filter(|id| pinned.contains(id)) {
+        collect_blobs(&repo.find_commit(*id)?.tree()?, &mut kept)?;
+    }
+    let total: u64 = sizes.values().sum();
+    let mut freed_by = vec![0u64; chain.len()];
+    for (blob, &last) in &last_held {
+        if last < newest && !kept.contains(blob) {
+            freed_by[last] += sizes[blob];
+        }
+    }
+
+    // Folding everything before `baseline` frees the blobs last held before it.
+    let mut freed = 0u64;
+    let mut target = newest;
+    for baseline in 1..=newest {
+        freed += freed_by[baseline - 1];
+        if u128::from(freed) * u128::from(store) >= u128::from(excess) * u128::from(total.max(1)) {
+            target = baseline;
+            break;
+        }
+    }
+    let folds_something = chain[..target].iter().any(|id| !pinned.contains(id));
+    Ok(folds_something.then_some(target))
+}
+
+/// For every blob in `chain`'s trees, the index of the last snapshot holding it, and
+/// its size.
+fn blob_lifetimes(
+    repo: &Repository,
+    chain: &[Oid],
+) -> Result<(HashMap<Oid, usize>, HashMap<Oid, u64>)> {
+    let odb = repo.odb().context("failed to open object database")?;
+    let mut sizes = HashMap::new();
+    let mut held: HashMap<Oid, usize> = HashMap::new();
+    let mut last_held = HashMap::new();
+    let mut previous: Option<Tree<'_>> = None;
+
+    for (idx, id) in chain.iter().enumerate() {
+        let tree = repo
+            .find_commit(*id)
+            .and_then(|commit| commit.tree())
+            .with_context(|| format!("failed to read snapshot {id}"))?;
+        let diff = repo
+            .diff_tree_to_tree(previous.as_ref(), Some(&tree), None)
+            .with_context(|| format!("failed to diff snapshot {id}"))?;
+        for delta in diff.deltas() {
+            let old = delta.old_file();
+            if !old.id().is_zero()
+                && old.mode() != FileMode::Commit
+                && let Some(count) = held.get_mut(&old.id())
+            {
+                *count -= 1;
+                if *count == 0 {
+                    held.remove(&old.id());
+                    last_held.insert(old.id(), idx - 1);
+                }
+            }
+            let new = delta.new_file();
+            if !new.id().is_zero() && new.mode() != FileMode::Commit {
+                *held.entry(new.id()).or_insert(0) += 1;
+                if let Entry::Vacant(slot) = sizes.entry(new.id()) {
+                    let (size, _) = odb
+                        .read_header(new.id())
+                        .with_context(|| format!("failed to read blob {}", new.id()))?;
+                    slot.insert(u64::try_from(size).unwrap_or(u64::MAX));
+                }
+            }
+        }
+        previous = Some(tree);
+    }
+    let newest = chain.len().saturating_sub(1);
+    for blob in held.into_keys() {
+        last_held.insert(blob, newest);
+    }
+    Ok((last_held, sizes))
+}
+
+fn collect_blobs(tree: &Tree<'_>, blobs: &mut HashSet<Oid>) -> Result<()> {
+    tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            blobs.insert(entry.id());
+        }
+        TreeWalkResult::Ok
+    })
+    .context("failed to walk snapshot tree")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(repo: &Repository, parent: Option<Oid>, files: &[(&str, &str)]) -> Oid {
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            builder.insert(name, blob, 0o100_644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let sig = git2::Signature::now("t", "t@example.com").unwrap();
+        let parents: Vec<_> = parent
+            .map(|id| repo.find_commit(id).unwrap())
+            .into_iter()
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(None, &sig, &sig, "snapshot", &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn folds_just_enough_of_the_oldest_snapshots() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let big = "x".repeat(1000);
+        let a = snapshot(&repo, None, &[("big", &big), ("keep", "k")]);
+        let b = snapshot(&repo, Some(a), &[("big", "small"), ("keep", "k")]);
+        let c = snapshot(&repo, Some(b), &[("big", "smaller"), ("keep", "k")]);
+        let chain = [a, b, c];
+        let none = HashSet::new();
+
+        // Folding `a` frees the 1000-byte blob: enough to drop 900 of 1013 bytes.
+        assert_eq!(
+            budget_baseline(&repo, &chain, &none, 1013, 900).unwrap(),
+            Some(1)
+        );
+        // More than that needs `b` folded too; nothing beyond the newest.
+        assert_eq!(
+            budget_baseline(&repo, &chain, &none, 1013, 1004).unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            budget_baseline(&repo, &chain, &none, 1013, 5000).unwrap(),
+            Some(2)
+        );
+        // A pinned snapshot keeps its blobs and is never folded.
+        let pinned = HashSet::from([a, b]);
+        assert_eq!(
+            budget_baseline(&repo, &chain, &pinned, 1013, 900).unwrap(),
+            None
+        );
+    }
+}
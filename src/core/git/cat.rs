@@ -0,0 +1,93 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{ObjectType, Repository};
+
+use super::{
+    picker::{PreviewBase, select_commit_interactive, select_files_interactive},
+    repo::autosnap_dir,
+    revision::resolve_commit,
+};
+
+/// Print files as they are in a snapshot, one after another like `cat`.
+///
+/// With `interactive`, a missing `commit` is picked from the history and missing
+/// `paths` from the snapshot's files.
+///
+/// # Errors
+/// Returns an error if the snapshot cannot be resolved, a path is not a file in it, or
+/// nothing was selected.
+pub fn cat(
+    repo_root: &Path,
+    commit: Option<&str>,
+    interactive: bool,
+    paths: &[String],
+) -> Result<()> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+
+    let selected = if interactive && commit.is_none() {
+        match select_commit_interactive(&autosnap, PreviewBase::Parent)? {
+            Some(selected) => Some(selected),
+            None => bail!("no snapshot selected"),
+        }
+    } else {
+        None
+    };
+    let commit_ref = commit.or(selected.as_deref()).unwrap_or("HEAD");
+
+    let picked;
+    let paths = if paths.is_empty() {
+        if !interactive {
+            bail!("no paths given; pass PATH... or use -i to pick files");
+        }
+        picked =
+            select_files_interactive(&autosnap, commit_ref, PreviewBase::Parent, "Cat files> ")?;
+        if picked.is_empty() {
+            bail!("no files selected");
+        }
+        &picked[..]
+    } else {
+        paths
+    };
+
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let tree = resolve_commit(&repo, commit_ref)
+        .and_then(|commit| commit.tree().context("failed to read snapshot tree"))
+        .with_context(|| format!("failed to resolve {commit_ref} to a snapshot"))?;
+
+    // Resolve every path first so nothing is printed when one of them is wrong.
+    let blobs = paths
+        .iter()
+        .map(|path| {
+            let path = path.trim_start_matches("./");
+            let entry = tree
+                .get_path(Path::new(path))
+                .with_context(|| format!("{path} is not in snapshot {commit_ref}"))?;
+            if entry.kind() != Some(ObjectType::Blob) {
+                bail!("{path} is not a file in snapshot {commit_ref}");
+            }
+            repo.find_blob(entry.id())
+                .with_context(|| format!("failed to read {path}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut out = io::stdout().lock();
+    for blob in &blobs {
+        match out.write_all(blob.content()) {
+            // A closed pager or `head` is not an error.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+    match out.flush() {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
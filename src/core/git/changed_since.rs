@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use git2::Repository;
+
+use super::{
+    diff::{DiffFormat, DiffRange, DiffSettings, diff},
+    repo::autosnap_dir,
+    revision::resolve_since,
+};
+
+/// Print the paths that changed in the working tree since a snapshot or a point in
+/// time, in `--name-status` form.
+///
+/// Returns whether anything changed.
+///
+/// # Errors
+/// Returns an error if `since` cannot be resolved or diffing fails.
+pub fn changed_since(repo_root: &Path, since: &str, paths: &[String]) -> Result<bool> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let base = resolve_since(&repo, since)?.id().to_string();
+
+    diff(
+        repo_root,
+        DiffRange {
+            commit1: Some(&base),
+            ..DiffRange::default()
+        },
+        false,
+        DiffFormat::NameStatus,
+        &DiffSettings::default(),
+        paths,
+    )
+}
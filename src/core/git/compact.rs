@@ -1,21 +1,99 @@
-use std::{path::Path, process::Command};
+use std::{collections::HashSet, path::Path};
 
 use anyhow::{Context, Result};
 use git2::{Oid, Repository, Signature, Time};
-
-use super::{ops_lock::acquire_ops_lock, repo::autosnap_dir};
+use time::OffsetDateTime;
+
+use super::{
+    backup::{
+        compacted_tip, create_backup, drop_backup, expire_backups, list_backups, mark_compacted,
+        restore_backup,
+    },
+    budget::{budget_baseline, store_size},
+    gc::{GcBackend, GcProgress, collect_garbage},
+    metadata::{SnapshotMetadata, move_metadata, prune_metadata, read_metadata, write_metadata},
+    ops_lock::acquire_ops_lock,
+    pin::{is_pinned, move_pin, pinned_snapshots, set_pinned},
+    repo::autosnap_dir,
+    report::{commit_time, short_time},
+    retention::{RetentionPlan, RetentionPolicy},
+    revision::snapshot_chain,
+    rewrite_map::record_rewrites,
+    snapshot::signature_from_main,
+    staged::{drop_staged_tree, move_staged_tree, record_staged_tree, staged_tree},
+};
 
 const BASELINE_MESSAGE: &str = "AUTOSNAP_COMPACT_BASELINE";
 
 /// Result summary of a compact operation.
 #[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct CompactResult {
     pub before_commits: usize,
     pub after_commits: usize,
     pub rewritten: bool,
     pub baseline_created: bool,
+    /// Bytes the object store takes after compacting.
+    pub store_bytes: u64,
+    /// Bytes the object store shrank by.
+    pub bytes_reclaimed: u64,
+    /// The store is still larger than `max_store_size`: only the newest and pinned
+    /// snapshots are left to drop, or `held_by_backup`.
+    pub over_budget: bool,
+    /// Folding stopped short of `max_store_size` because the backup of this compact
+    /// still holds the previous history.
+    pub held_by_backup: bool,
+    /// Unreachable objects garbage collection removed.
+    pub objects_pruned: usize,
+    /// Creation time of the backup of the chain before this rewrite, if one was kept.
+    pub backup: Option<i64>,
+    /// Backups older than the grace period, deleted.
+    pub backups_expired: usize,
+    /// Backups deleted to fit `max_store_size`.
+    pub backups_dropped: usize,
 }
 
+impl CompactResult {
+    const fn empty() -> Self {
+        Self {
+            before_commits: 0,
+            after_commits: 0,
+            rewritten: false,
+            baseline_created: false,
+            store_bytes: 0,
+            bytes_reclaimed: 0,
+            over_budget: false,
+            held_by_backup: false,
+            objects_pruned: 0,
+            backup: None,
+            backups_expired: 0,
+            backups_dropped: 0,
+        }
+    }
+}
+
+/// Options controlling a compact.
+#[derive(Clone, Copy)]
+pub struct CompactOptions<'a> {
+    /// Which snapshots to keep, by age.
+    pub retention: &'a RetentionPolicy,
+    /// Fold the oldest unpinned snapshots until the store fits in this many bytes.
+    pub max_store_size: Option<u64>,
+    /// How unreachable objects are removed after a rewrite.
+    pub gc: GcBackend,
+    /// Seconds unreachable loose objects are kept before gc removes them.
+    pub prune_grace: i64,
+    /// Called as garbage collection runs.
+    pub progress: Option<&'a dyn Fn(GcProgress)>,
+    /// Back up the chain before rewriting it and keep backups this many seconds
+    /// (`0` keeps none); `None` neither backs up nor expires backups.
+    pub backup_grace: Option<i64>,
+}
+
+/// How often `compact` refolds the chain to get under `max_store_size`; the store
+/// size is only known after each gc.
+const BUDGET_ROUNDS: usize = 3;
+
 #[derive(Debug, Clone)]
 struct CommitReplayData {
     tree_id: Oid,
@@ -50,154 +128,537 @@ impl SignatureData {
     }
 }
 
-/// Compact old snapshot history by collapsing commits older than `days` into one baseline commit.
+/// Compact snapshot history according to `opts.retention`, then to the size budget.
+///
+/// Snapshots the policy drops disappear from the chain; those past its horizon fold
+/// into one baseline commit. Kept snapshots are replayed with their original
+/// messages, signatures, metadata notes, staged trees and pins. With a
+/// `max_store_size`, the oldest unpinned snapshots then fold into the baseline until
+/// the store fits.
 ///
-/// After compacting, this always runs post-maintenance:
-/// - `git reflog expire --expire=now --all`
-/// - `git gc --prune=now`
+/// After each rewrite, reflogs and unreachable objects are dropped with `opts.gc`.
+/// Objects a backup still holds survive until it expires, unless the store is over
+/// budget: backups go before any snapshot is folded for size.
 ///
 /// # Errors
-/// Returns an error if repository rewrite or post-gc commands fail.
-pub fn compact(repo_root: &Path, days: u32) -> Result<CompactResult> {
+/// Returns an error if repository rewrite or garbage collection fails.
+pub fn compact(repo_root: &Path, opts: &CompactOptions<'_>) -> Result<CompactResult> {
     let autosnap = autosnap_dir(repo_root);
     if !autosnap.exists() {
-        return Ok(CompactResult {
-            before_commits: 0,
-            after_commits: 0,
-            rewritten: false,
-            baseline_created: false,
-        });
+        return Ok(CompactResult::empty());
     }
 
     let _ops_lock = acquire_ops_lock(repo_root)?;
 
     let repo = Repository::open(&autosnap)
         .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
-
-    let commits = list_commits_oldest_first(&repo)?;
+    let store_before = store_size(&autosnap)?;
+    let mut objects_pruned = 0;
+    let mut gc = |repo: &Repository| -> Result<()> {
+        let stats = collect_garbage(repo, opts.gc, opts.prune_grace, &mut |step| {
+            if let Some(progress) = opts.progress {
+                progress(step);
+            }
+        })?;
+        objects_pruned += stats.pruned;
+        Ok(())
+    };
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let backups_expired = match opts.backup_grace {
+        Some(grace) => expire_backups(&repo, now, grace)?,
+        None => 0,
+    };
+
+    let (commits, plan) = retention_plan(&repo, opts.retention)?;
     let before_commits = commits.len();
-    let cutoff = cutoff_epoch(days);
-
-    if before_commits == 0 {
-        run_post_gc(&autosnap)?;
-        return Ok(CompactResult {
-            before_commits,
-            after_commits: 0,
-            rewritten: false,
-            baseline_created: false,
-        });
+    let mut baseline_created = creates_baseline(&repo, &plan)?;
+    let mut rewritten = plan.keep.len() != before_commits || baseline_created;
+    let mut backup = None;
+    if rewritten {
+        if opts.backup_grace.is_some_and(|grace| grace > 0) {
+            backup = Some(create_backup(&repo, now)?);
+        }
+        let tip = rewrite_chain(&repo, repo_root, &commits, &plan, now)?;
+        if let Some(created) = backup {
+            mark_compacted(&repo, created, tip)?;
+        }
+    }
+    gc(&repo)?;
+
+    let mut over_budget = false;
+    let mut held_by_backup = false;
+    let mut backups_dropped = 0;
+    if let Some(budget) = opts.max_store_size {
+        let mut rounds = 0;
+        while rounds < BUDGET_ROUNDS {
+            let store = store_size(&autosnap)?;
+            over_budget = store > budget;
+            if !over_budget {
+                break;
+            }
+            // Backups hold on to everything they saw; older ones go first.
+            let backups: Vec<i64> = list_backups(&repo)?
+                .into_iter()
+                .filter(|&created| Some(created) != backup)
+                .collect();
+            if !backups.is_empty() {
+                for &created in &backups {
+                    drop_backup(&repo, created)?;
+                }
+                backups_dropped += backups.len();
+                gc(&repo)?;
+                continue;
+            }
+            // This compact's own backup stays for `--rollback`, and folding further
+            // frees nothing it still holds.
+            if backup.is_some() {
+                held_by_backup = true;
+                break;
+            }
+            rounds += 1;
+            let chain = snapshot_chain(&repo)?;
+            let Some(plan) = budget_plan(&repo, &chain, store, budget)? else {
+                break;
+            };
+            baseline_created |= creates_baseline(&repo, &plan)?;
+            rewritten = true;
+            rewrite_chain(&repo, repo_root, &chain, &plan, now)?;
+            gc(&repo)?;
+        }
+        if over_budget {
+            over_budget = store_size(&autosnap)? > budget;
+        }
     }
 
-    let (old_oids, keep_oids): (Vec<Oid>, Vec<Oid>) = commits.into_iter().partition(|oid| {
-        repo.find_commit(*oid)
-            .map(|c| c.time().seconds() < cutoff)
-            .unwrap_or(false)
-    });
-
-    if old_oids.is_empty() {
-        run_post_gc(&autosnap)?;
-        return Ok(CompactResult {
-            before_commits,
-            after_commits: before_commits,
-            rewritten: false,
-            baseline_created: false,
-        });
+    let after_commits = snapshot_chain(&repo)?.len();
+    let store_bytes = store_size(&autosnap)?;
+
+    Ok(CompactResult {
+        before_commits,
+        after_commits,
+        rewritten,
+        baseline_created,
+        store_bytes,
+        bytes_reclaimed: store_before.saturating_sub(store_bytes),
+        over_budget,
+        held_by_backup,
+        objects_pruned,
+        backup,
+        backups_expired,
+        backups_dropped,
+    })
+}
+
+/// Fold the snapshots above the store budget, without retention thinning, when the
+/// store has grown past `max_store_size`. Returns `None` when it still fits.
+///
+/// # Errors
+/// Returns an error if the store cannot be measured or compacting fails.
+pub fn compact_to_budget(
+    repo_root: &Path,
+    max_store_size: u64,
+    gc: GcBackend,
+    prune_grace: i64,
+) -> Result<Option<CompactResult>> {
+    if store_size(&autosnap_dir(repo_root))? <= max_store_size {
+        return Ok(None);
     }
+    compact(
+        repo_root,
+        &CompactOptions {
+            retention: &RetentionPolicy::keep_all(),
+            max_store_size: Some(max_store_size),
+            gc,
+            prune_grace,
+            progress: None,
+            backup_grace: None,
+        },
+    )
+    .map(Some)
+}
+
+/// Outcome of [`rollback`].
+#[derive(Debug, Clone, Copy)]
+pub struct RollbackResult {
+    /// Creation time of the backup restored.
+    pub backup: i64,
+    /// Snapshots in the restored chain.
+    pub snapshots: usize,
+    /// Snapshots taken since the compact, replayed on top.
+    pub replayed: usize,
+}
+
+// What a snapshot taken after a compact carries across a rollback.
+struct Carried {
+    id: Oid,
+    metadata: Option<SnapshotMetadata>,
+    staged: Option<Oid>,
+    pinned: bool,
+}
+
+/// Undo the newest backed-up compact (`compact --rollback`).
+///
+/// Refs go back to where the backup recorded them, notes refs included (see
+/// [`restore_backup`]), then snapshots taken since the compact are replayed on top
+/// with their metadata, staged trees and pins. The backup is deleted.
+///
+/// # Errors
+/// Returns an error if there is no backup, history was rewritten again since, or the
+/// refs cannot be restored.
+pub fn rollback(repo_root: &Path) -> Result<RollbackResult> {
+    let autosnap = autosnap_dir(repo_root);
+    let _ops_lock = acquire_ops_lock(repo_root)?;
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
 
-    let baseline_source_oid = *old_oids
+    let backup = *list_backups(&repo)?
         .last()
-        .context("missing baseline source in old commit set")?;
-    let baseline_source = repo
-        .find_commit(baseline_source_oid)
-        .context("failed to load baseline source commit")?;
-
-    let baseline_tree = baseline_source
-        .tree()
-        .context("failed to load baseline source tree")?;
-    let baseline_author =
-        SignatureData::from_signature(&baseline_source.author()).to_signature()?;
-    let baseline_committer =
-        SignatureData::from_signature(&baseline_source.committer()).to_signature()?;
-
-    let baseline_oid = repo
-        .commit(
-            None,
-            &baseline_author,
-            &baseline_committer,
-            BASELINE_MESSAGE,
-            &baseline_tree,
-            &[],
-        )
-        .context("failed to create baseline commit")?;
-
-    let mut parent_oid = baseline_oid;
-
-    for keep_oid in keep_oids {
-        let src = repo
-            .find_commit(keep_oid)
-            .with_context(|| format!("failed to load keep commit {keep_oid}"))?;
-
-        let replay = CommitReplayData {
-            tree_id: src.tree_id(),
-            message: src.message().unwrap_or("").to_string(),
-            author: SignatureData::from_signature(&src.author()),
-            committer: SignatureData::from_signature(&src.committer()),
-        };
-
-        let tree = repo
-            .find_tree(replay.tree_id)
-            .with_context(|| format!("failed to find tree {}", replay.tree_id))?;
-        let parent = repo
-            .find_commit(parent_oid)
-            .with_context(|| format!("failed to find parent commit {parent_oid}"))?;
-
-        let author = replay.author.to_signature()?;
-        let committer = replay.committer.to_signature()?;
-
-        parent_oid = repo
-            .commit(
-                None,
-                &author,
-                &committer,
-                &replay.message,
-                &tree,
-                &[&parent],
-            )
-            .with_context(|| format!("failed to replay commit {keep_oid}"))?;
+        .context("no compact backup to roll back to")?;
+    let chain = snapshot_chain(&repo)?;
+    let newer = match compacted_tip(&repo, backup) {
+        Some(tip) => {
+            let pos = chain.iter().position(|&id| id == tip).with_context(|| {
+                format!("snapshot history was rewritten again after backup {backup}")
+            })?;
+            &chain[pos + 1..]
+        }
+        // The compact stopped before finishing; nothing was built on it.
+        None => &[],
+    };
+    let carried = newer
+        .iter()
+        .map(|&id| {
+            Ok(Carried {
+                id,
+                metadata: read_metadata(&repo, id)?,
+                staged: staged_tree(&repo, id).map(|tree| tree.id()),
+                pinned: is_pinned(&repo, id),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    restore_backup(&repo, backup)?;
+    let notes_sig = signature_from_main(repo_root)?;
+    let restored = snapshot_chain(&repo)?;
+    let mut tip = restored.last().copied();
+    let mut rewrites = Vec::new();
+    for snapshot in &carried {
+        let new = replay_commit(&repo, snapshot.id, tip, None)?;
+        if let Some(metadata) = &snapshot.metadata {
+            write_metadata(&repo, &notes_sig, new, metadata)?;
+        }
+        if let Some(staged) = snapshot.staged {
+            record_staged_tree(&repo, new, staged)?;
+        }
+        set_pinned(&repo, new, snapshot.pinned)?;
+        if new != snapshot.id {
+            rewrites.push((snapshot.id, new));
+        }
+        tip = Some(new);
     }
+    if let Some(tip) = tip
+        && !carried.is_empty()
+    {
+        update_head_target(&repo, tip)?;
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        record_rewrites(&repo, &notes_sig, &rewrites, now)?;
+    }
+    drop_backup(&repo, backup)?;
 
-    update_head_target(&repo, parent_oid)?;
-    run_post_gc(&autosnap)?;
+    Ok(RollbackResult {
+        backup,
+        snapshots: restored.len() + carried.len(),
+        replayed: carried.len(),
+    })
+}
 
-    let after_commits = list_commits_oldest_first(&repo)?.len();
+/// What `compact` would do to a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFate {
+    Keep,
+    /// Kept, standing for everything folded before it.
+    Baseline,
+    /// Past the retention horizon or over the store budget: folds into the baseline.
+    Fold,
+    /// Dropped to thin its retention tier.
+    Thin,
+}
 
-    Ok(CompactResult {
-        before_commits,
-        after_commits,
-        rewritten: true,
-        baseline_created: true,
+impl SnapshotFate {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::Baseline => "baseline",
+            Self::Fold => "fold",
+            Self::Thin => "thin",
+        }
+    }
+}
+
+/// One snapshot of a [`CompactPreview`].
+#[derive(Debug, Clone)]
+pub struct PreviewEntry {
+    pub id: Oid,
+    pub time: git2::Time,
+    pub subject: String,
+    pub fate: SnapshotFate,
+}
+
+/// What `compact` would do, without rewriting anything.
+#[derive(Debug, Clone, Default)]
+pub struct CompactPreview {
+    /// Every snapshot, oldest first.
+    pub entries: Vec<PreviewEntry>,
+    pub after_commits: usize,
+}
+
+impl CompactPreview {
+    /// Print the preview for humans, oldest snapshot first.
+    pub fn print(&self) {
+        for entry in &self.entries {
+            println!(
+                "  {:<8}  {:.7}  {}  {}",
+                entry.fate.label(),
+                entry.id,
+                short_time(commit_time(entry.time)),
+                entry.subject
+            );
+        }
+        if !self.entries.is_empty() {
+            println!();
+        }
+        let count = |fate| self.entries.iter().filter(|e| e.fate == fate).count();
+        println!(
+            "{} -> {} commits ({} folded, {} thinned); nothing was rewritten",
+            self.entries.len(),
+            self.after_commits,
+            count(SnapshotFate::Fold),
+            count(SnapshotFate::Thin)
+        );
+    }
+}
+
+/// Plan a compact with `opts` without touching the store (`compact --dry-run`).
+///
+/// Folding for `max_store_size` is estimated from the current store size: the real
+/// run measures again after dropping what retention drops, so it may fold less.
+///
+/// # Errors
+/// Returns an error if the snapshots cannot be read.
+pub fn compact_preview(repo_root: &Path, opts: &CompactOptions<'_>) -> Result<CompactPreview> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        return Ok(CompactPreview::default());
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+
+    let (chain, mut plan) = retention_plan(&repo, opts.retention)?;
+    if let Some(budget) = opts.max_store_size {
+        let store = store_size(&autosnap)?;
+        if store > budget
+            && let Some(folded) = budget_plan(&repo, &plan.keep, store, budget)?
+        {
+            plan = folded;
+        }
+    }
+
+    let baseline_idx = plan
+        .baseline
+        .and_then(|baseline| chain.iter().position(|&id| id == baseline));
+    let entries = chain
+        .iter()
+        .enumerate()
+        .map(|(idx, &id)| {
+            let commit = repo
+                .find_commit(id)
+                .with_context(|| format!("failed to load snapshot {id}"))?;
+            let fate = if Some(id) == plan.baseline {
+                SnapshotFate::Baseline
+            } else if plan.keep.contains(&id) {
+                SnapshotFate::Keep
+            } else if baseline_idx.is_some_and(|baseline| idx < baseline) {
+                SnapshotFate::Fold
+            } else {
+                SnapshotFate::Thin
+            };
+            Ok(PreviewEntry {
+                id,
+                time: commit.time(),
+                subject: commit.summary().unwrap_or("").to_string(),
+                fate,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CompactPreview {
+        entries,
+        after_commits: plan.keep.len(),
     })
 }
 
-fn cutoff_epoch(days: u32) -> i64 {
-    use time::{Duration, OffsetDateTime};
-    let now = OffsetDateTime::now_utc();
-    (now - Duration::days(i64::from(days))).unix_timestamp()
+// The snapshot chain and what `retention` keeps of it now.
+fn retention_plan(
+    repo: &Repository,
+    retention: &RetentionPolicy,
+) -> Result<(Vec<Oid>, RetentionPlan)> {
+    let chain = snapshot_chain(repo)?;
+    let snapshots = chain
+        .iter()
+        .map(|&oid| {
+            let commit = repo
+                .find_commit(oid)
+                .with_context(|| format!("failed to load snapshot {oid}"))?;
+            Ok((oid, commit.time().seconds()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let plan = retention.plan(&snapshots, now, &pinned_snapshots(repo)?);
+    Ok((chain, plan))
+}
+
+// Fold the oldest unpinned snapshots of `chain` so a `store`-byte store fits in
+// `budget`; `None` when nothing can be folded.
+fn budget_plan(
+    repo: &Repository,
+    chain: &[Oid],
+    store: u64,
+    budget: u64,
+) -> Result<Option<RetentionPlan>> {
+    let pinned = pinned_snapshots(repo)?;
+    let excess = store.saturating_sub(budget);
+    let Some(baseline) = budget_baseline(repo, chain, &pinned, store, excess)? else {
+        return Ok(None);
+    };
+    Ok(Some(RetentionPlan {
+        keep: chain
+            .iter()
+            .enumerate()
+            .filter(|&(idx, id)| idx >= baseline || pinned.contains(id))
+            .map(|(_, id)| *id)
+            .collect(),
+        baseline: Some(chain[baseline]),
+    }))
+}
+
+// Whether `plan` turns a snapshot into the baseline.
+fn creates_baseline(repo: &Repository, plan: &RetentionPlan) -> Result<bool> {
+    Ok(match plan.baseline {
+        Some(oid) => repo.find_commit(oid)?.message() != Some(BASELINE_MESSAGE),
+        None => false,
+    })
 }
 
-fn list_commits_oldest_first(repo: &Repository) -> Result<Vec<Oid>> {
-    let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
-    if revwalk.push_head().is_err() {
-        return Ok(Vec::new());
+// Replace `chain` with the snapshots `plan` keeps and point HEAD at the new tip,
+// which is returned.
+//
+// Every replaced snapshot is recorded as rewritten to its replay; dropped ones to the
+// next kept snapshot, which stands for them.
+fn rewrite_chain(
+    repo: &Repository,
+    repo_root: &Path,
+    chain: &[Oid],
+    plan: &RetentionPlan,
+    now: i64,
+) -> Result<Oid> {
+    let notes_sig = signature_from_main(repo_root)?;
+    let replayed = replay_kept(repo, &notes_sig, plan)?;
+    let tip = *replayed.last().context("retention kept no snapshots")?;
+
+    let mut kept = plan.keep.iter().zip(&replayed).rev().peekable();
+    let mut successor = tip;
+    let mut rewrites = Vec::new();
+    for &old in chain.iter().rev() {
+        if let Some(&(&id, &new)) = kept.peek()
+            && id == old
+        {
+            successor = new;
+            kept.next();
+        } else {
+            drop_staged_tree(repo, old)?;
+        }
+        if old != successor {
+            rewrites.push((old, successor));
+        }
     }
+    update_head_target(repo, tip)?;
+    record_rewrites(repo, &notes_sig, &rewrites, now)?;
+    // Metadata of dropped snapshots goes, and so does the history of every note.
+    let live: HashSet<Oid> = replayed.iter().copied().collect();
+    prune_metadata(repo, &notes_sig, |id| live.contains(&id))?;
+    Ok(tip)
+}
 
-    let mut oids = Vec::new();
-    for oid in revwalk {
-        oids.push(oid.context("failed to iterate revwalk")?);
+// Replay the kept snapshots (oldest first) as a new chain, preserving their original
+// messages and signatures; the baseline gets the baseline message. Returns the new ids,
+// in the order of `plan.keep`.
+fn replay_kept(
+    repo: &Repository,
+    notes_sig: &Signature<'_>,
+    plan: &RetentionPlan,
+) -> Result<Vec<Oid>> {
+    let mut replayed = Vec::with_capacity(plan.keep.len());
+    let mut parent_oid: Option<Oid> = None;
+
+    for &keep_oid in &plan.keep {
+        let message = (Some(keep_oid) == plan.baseline).then_some(BASELINE_MESSAGE);
+        let new_oid = replay_commit(repo, keep_oid, parent_oid, message)?;
+        if new_oid != keep_oid {
+            move_metadata(repo, notes_sig, keep_oid, new_oid)?;
+            move_staged_tree(repo, keep_oid, new_oid)?;
+            move_pin(repo, keep_oid, new_oid)?;
+        }
+        parent_oid = Some(new_oid);
+        replayed.push(new_oid);
     }
-    oids.reverse();
 
-    Ok(oids)
+    Ok(replayed)
+}
+
+// Recreate snapshot `src` on top of `parent` with its original tree, signatures and
+// message (or `message`). Returns the new id.
+fn replay_commit(
+    repo: &Repository,
+    src: Oid,
+    parent: Option<Oid>,
+    message: Option<&str>,
+) -> Result<Oid> {
+    let src_commit = repo
+        .find_commit(src)
+        .with_context(|| format!("failed to load keep commit {src}"))?;
+    let replay = CommitReplayData {
+        tree_id: src_commit.tree_id(),
+        message: message.map_or_else(
+            || src_commit.message().unwrap_or("").to_string(),
+            ToString::to_string,
+        ),
+        author: SignatureData::from_signature(&src_commit.author()),
+        committer: SignatureData::from_signature(&src_commit.committer()),
+    };
+
+    let tree = repo
+        .find_tree(replay.tree_id)
+        .with_context(|| format!("failed to find tree {}", replay.tree_id))?;
+    let parent = parent
+        .map(|oid| {
+            repo.find_commit(oid)
+                .with_context(|| format!("failed to find parent commit {oid}"))
+        })
+        .transpose()?;
+
+    let author = replay.author.to_signature()?;
+    let committer = replay.committer.to_signature()?;
+
+    repo.commit(
+        None,
+        &author,
+        &committer,
+        &replay.message,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )
+    .with_context(|| format!("failed to replay commit {src}"))
 }
 
 fn update_head_target(repo: &Repository, target: Oid) -> Result<()> {
@@ -226,35 +687,3 @@ fn update_head_target(repo: &Repository, target: Oid) -> Result<()> {
 
     Ok(())
 }
-
-fn run_post_gc(autosnap: &Path) -> Result<()> {
-    let gitdir = autosnap.to_string_lossy().to_string();
-
-    let status = Command::new("git")
-        .args([
-            format!("--git-dir={gitdir}").as_str(),
-            "reflog",
-            "expire",
-            "--expire=now",
-            "--all",
-        ])
-        .status()
-        .context("failed to run git reflog expire --expire=now --all")?;
-    if !status.success() {
-        return Err(anyhow::anyhow!(
-            "git reflog expire --expire=now --all exited with status {status}"
-        ));
-    }
-
-    let status = Command::new("git")
-        .args([format!("--git-dir={gitdir}").as_str(), "gc", "--prune=now"])
-        .status()
-        .context("failed to run git gc --prune=now")?;
-    if !status.success() {
-        return Err(anyhow::anyhow!(
-            "git gc --prune=now exited with status {status}"
-        ));
-    }
-
-    Ok(())
-}
@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{Delta, Diff, DiffDelta, DiffLineType, Patch, Repository, Tree, TreeWalkMode};
+use globset::{GlobBuilder, GlobMatcher};
+
+use super::options::DiffSettings;
+
+/// State of one attribute in a `.gitattributes` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Set,
+    Unset,
+    /// `!attr`: back to unspecified.
+    Unspecified,
+    Value(String),
+}
+
+/// One `pattern attr...` line, relative to the directory of its `.gitattributes`.
+#[derive(Debug, Clone)]
+struct Rule {
+    base: String,
+    matcher: GlobMatcher,
+    /// Patterns without a slash match the file name at any depth.
+    basename_only: bool,
+    attrs: Vec<(String, State)>,
+}
+
+impl Rule {
+    fn matches(&self, path: &str) -> bool {
+        let Some(rel) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        if self.basename_only {
+            let name = rel.rsplit('/').next().unwrap_or(rel);
+            self.matcher.is_match(name)
+        } else {
+            self.matcher.is_match(rel)
+        }
+    }
+}
+
+/// How a file is shown, from its `diff` attribute and driver config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum DiffMode {
+    #[default]
+    Text,
+    /// `-diff`, `binary` or `diff.<driver>.binary`.
+    Binary,
+    /// `diff.<driver>.textconv` command.
+    Textconv(String),
+}
+
+/// The `.gitattributes` of a snapshot, resolved for diffing.
+///
+/// Rules come from every `.gitattributes` in the snapshot tree (deeper files win) and
+/// the main repository's `info/attributes`; drivers come from its config. The default
+/// value has no rules, so every file diffs as plain text.
+#[derive(Debug, Default)]
+pub struct Gitattributes {
+    /// In increasing precedence; the last matching rule wins per attribute.
+    rules: Vec<Rule>,
+    /// `diff=<driver>` resolved against `diff.<driver>.*`.
+    drivers: HashMap<String, DiffMode>,
+    /// Where textconv commands run.
+    workdir: PathBuf,
+    settings: DiffSettings,
+}
+
+impl Gitattributes {
+    /// Load the attributes that apply to `tree`, the snapshot side of a diff.
+    ///
+    /// # Errors
+    /// Returns an error if an attributes blob cannot be read.
+    pub fn load(
+        repo: &Repository,
+        repo_root: &Path,
+        tree: &Tree<'_>,
+        settings: &DiffSettings,
+    ) -> Result<Self> {
+        let mut files = Vec::new();
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.name_bytes() == b".gitattributes" {
+                files.push((dir.to_string(), entry.id()));
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .context("failed to walk snapshot tree")?;
+        files.sort_by_key(|(dir, _)| dir.matches('/').count());
+
+        let mut parser = Parser::default();
+        for (dir, id) in files {
+            let blob = repo
+                .find_blob(id)
+                .with_context(|| format!("failed to read {dir}.gitattributes"))?;
+            parser.parse(&dir, &String::from_utf8_lossy(blob.content()));
+        }
+
+        let main = Repository::discover(repo_root).ok();
+        if let Some(main) = &main
+            && let Ok(info) = std::fs::read_to_string(main.path().join("info/attributes"))
+        {
+            parser.parse("", &info);
+        }
+
+        let config = main.as_ref().and_then(|m| m.config().ok());
+        let mut drivers = HashMap::new();
+        for rule in &parser.rules {
+            for (name, state) in &rule.attrs {
+                if let (true, State::Value(driver)) = (name == "diff", state) {
+                    drivers.entry(driver.clone()).or_insert_with(|| {
+                        config
+                            .as_ref()
+                            .map_or(DiffMode::Text, |c| driver_mode(c, driver))
+                    });
+                }
+            }
+        }
+
+        Ok(Self {
+            rules: parser.rules,
+            drivers,
+            workdir: repo_root.to_path_buf(),
+            settings: *settings,
+        })
+    }
+
+    fn lookup(&self, path: &str) -> (DiffMode, bool) {
+        let mut diff = None;
+        let mut generated = None;
+        for rule in self.rules.iter().filter(|rule| rule.matches(path)) {
+            for (name, state) in &rule.attrs {
+                match name.as_str() {
+                    "diff" => diff = Some(state),
+                    "linguist-generated" => generated = Some(state),
+                    _ => {}
+                }
+            }
+        }
+        let mode = match diff {
+            Some(State::Unset) => DiffMode::Binary,
+            Some(State::Value(driver)) => self.drivers.get(driver).cloned().unwrap_or_default(),
+            _ => DiffMode::Text,
+        };
+        let generated = match generated {
+            Some(State::Set) => true,
+            Some(State::Value(value)) => value == "true",
+            _ => false,
+        };
+        (mode, generated)
+    }
+
+    /// Whether `path` is shown as binary regardless of its content.
+    pub fn is_binary(&self, path: &str) -> bool {
+        !self.rules.is_empty() && self.lookup(path).0 == DiffMode::Binary
+    }
+
+    /// The patch to show for delta `idx` of `diff`, with `-diff`, textconv and
+    /// `linguist-generated` applied.
+    ///
+    /// # Errors
+    /// Returns an error if the patch cannot be generated or a textconv command fails.
+    pub fn file_patch<'d>(
+        &self,
+        repo: &Repository,
+        diff: &Diff<'d>,
+        idx: usize,
+    ) -> Result<Option<FilePatch<'d>>> {
+        let Some(patch) = Patch::from_diff(diff, idx).context("failed to generate patch")? else {
+            return Ok(None);
+        };
+        if self.rules.is_empty() {
+            return Ok(Some(FilePatch::Plain(patch)));
+        }
+        let delta = patch.delta();
+        let Some(file_name) = delta_path(&delta) else {
+            return Ok(Some(FilePatch::Plain(patch)));
+        };
+        let (mode, generated) = self.lookup(&file_name);
+        if generated {
+            return Ok(Some(FilePatch::Suppressed {
+                header: patch,
+                what: "Generated files",
+            }));
+        }
+        match mode {
+            DiffMode::Text => Ok(Some(FilePatch::Plain(patch))),
+            DiffMode::Binary => Ok(Some(FilePatch::Suppressed {
+                header: patch,
+                what: "Binary files",
+            })),
+            DiffMode::Textconv(command) => {
+                let old = self.convert(repo, &command, &delta, true)?;
+                let new = self.convert(repo, &command, &delta, false)?;
+                Ok(Some(FilePatch::Converted {
+                    header: patch,
+                    old,
+                    new,
+                    settings: self.settings,
+                }))
+            }
+        }
+    }
+
+    // One side through the textconv command; a missing side stays empty, as in git.
+    fn convert(
+        &self,
+        repo: &Repository,
+        command: &str,
+        delta: &DiffDelta<'_>,
+        old: bool,
+    ) -> Result<Vec<u8>> {
+        let file = if old {
+            delta.old_file()
+        } else {
+            delta.new_file()
+        };
+        if file.id().is_zero() {
+            return Ok(Vec::new());
+        }
+        let path = file.path().unwrap_or_else(|| Path::new("file"));
+        let blob = repo
+            .find_blob(file.id())
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        run_textconv(command, path, blob.content(), &self.workdir)
+    }
+}
+
+/// A delta's patch as it is displayed.
+pub enum FilePatch<'d> {
+    Plain(Patch<'d>),
+    /// Hunks come from the textconv output of both sides; the header from the delta.
+    Converted {
+        header: Patch<'d>,
+        old: Vec<u8>,
+        new: Vec<u8>,
+        settings: DiffSettings,
+    },
+    /// Only the header and a `<what> a/x and b/x differ` line.
+    Suppressed {
+        header: Patch<'d>,
+        what: &'static str,
+    },
+}
+
+impl FilePatch<'_> {
+    /// Whether any change is left to show once ignored changes are dropped.
+    ///
+    /// # Errors
+    /// Returns an error if the converted patch cannot be generated.
+    pub fn shows_changes(&self) -> Result<bool> {
+        Ok(match self {
+            Self::Plain(patch) => patch.num_hunks() > 0 || patch.delta().flags().is_binary(),
+            Self::Converted {
+                old, new, settings, ..
+            } => converted_patch(old, new, settings)?.num_hunks() > 0,
+            Self::Suppressed { .. } => true,
+        })
+    }
+
+    /// Feed every displayed line to `emit`, like [`Patch::print`].
+    ///
+    /// # Errors
+    /// Returns an error if the patch cannot be generated or `emit` fails.
+    pub fn print(
+        &mut self,
+        emit: &mut dyn FnMut(DiffLineType, &[u8]) -> io::Result<()>,
+    ) -> Result<()> {
+        match self {
+            Self::Plain(patch) => print_lines(patch, &mut |origin, content| emit(origin, content)),
+            Self::Converted {
+                header,
+                old,
+                new,
+                settings,
+            } => {
+                let mut converted = converted_patch(old, new, settings)?;
+                let delta = header.delta();
+                // Like git, a file whose converted text did not change is not shown.
+                if converted.num_hunks() == 0
+                    && delta.status() == Delta::Modified
+                    && delta.old_file().mode() == delta.new_file().mode()
+                {
+                    return Ok(());
+                }
+                print_lines(header, &mut |origin, content| match origin {
+                    DiffLineType::FileHeader => emit(origin, content),
+                    _ => Ok(()),
+                })?;
+                print_lines(&mut converted, &mut |origin, content| match origin {
+                    DiffLineType::FileHeader => Ok(()),
+                    _ => emit(origin, content),
+                })
+            }
+            Self::Suppressed { header, what } => {
+                let delta = header.delta();
+                let changed = delta.old_file().id() != delta.new_file().id();
+                let differ = format!(
+                    "{what} {} and {} differ\n",
+                    side_label(&delta, true),
+                    side_label(&delta, false)
+                );
+                print_lines(header, &mut |origin, content| match origin {
+                    DiffLineType::FileHeader => {
+                        emit(origin, &strip_file_names(content))?;
+                        if changed {
+                            emit(DiffLineType::Binary, differ.as_bytes())?;
+                        }
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                })
+            }
+        }
+    }
+}
+
+fn print_lines(
+    patch: &mut Patch<'_>,
+    emit: &mut dyn FnMut(DiffLineType, &[u8]) -> io::Result<()>,
+) -> Result<()> {
+    let mut write_err = None;
+    let printed = patch.print(&mut |_delta, _hunk, line| match emit(
+        line.origin_value(),
+        line.content(),
+    ) {
+        Ok(()) => true,
+        Err(err) => {
+            write_err = Some(err);
+            false
+        }
+    });
+    if let Some(err) = write_err {
+        return Err(err).context("failed to write patch");
+    }
+    printed.context("failed to generate patch")
+}
+
+fn converted_patch<'b>(old: &'b [u8], new: &'b [u8], settings: &DiffSettings) -> Result<Patch<'b>> {
+    let mut opts = git2::DiffOptions::new();
+    settings.apply(&mut opts);
+    Patch::from_buffers(old, None, new, None, Some(&mut opts))
+        .context("failed to diff textconv output")
+}
+
+// `a/<path>`, or `/dev/null` for the side that does not exist.
+fn side_label(delta: &DiffDelta<'_>, old: bool) -> String {
+    let (file, missing, prefix) = if old {
+        (delta.old_file(), Delta::Added, "a/")
+    } else {
+        (delta.new_file(), Delta::Deleted, "b/")
+    };
+    match file.path() {
+        Some(path) if delta.status() != missing => format!("{prefix}{}", path.display()),
+        _ => "/dev/null".to_string(),
+    }
+}
+
+// Suppressed files show git's binary header: no `---`/`+++` lines.
+fn strip_file_names(header: &[u8]) -> Vec<u8> {
+    header
+        .split_inclusive(|&b| b == b'\n')
+        .filter(|line| !line.starts_with(b"--- ") && !line.starts_with(b"+++ "))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+fn delta_path(delta: &DiffDelta<'_>) -> Option<String> {
+    delta
+        .new_file()
+        .path()
+        .or_else(|| delta.old_file().path())
+        .and_then(Path::to_str)
+        .map(str::to_string)
+}
+
+fn driver_mode(config: &git2::Config, driver: &str) -> DiffMode {
+    if config
+        .get_bool(&format!("diff.{driver}.binary"))
+        .unwrap_or(false)
+    {
+        return DiffMode::Binary;
+    }
+    config
+        .get_string(&format!("diff.{driver}.textconv"))
+        .ok()
+        .filter(|command| !command.trim().is_empty())
+        .map_or(DiffMode::Text, DiffMode::Textconv)
+}
+
+// Git runs `<command> <tempfile>` through the shell; the temporary file keeps the
+// original name as a suffix so converters can go by extension.
+fn run_textconv(command: &str, path: &Path, content: &[u8], workdir: &Path) -> Result<Vec<u8>> {
+    let name = path
+        .file_name()
+        .map_or_else(|| "file".into(), |n| n.to_string_lossy());
+    let mut file = tempfile::Builder::new()
+        .prefix("git-autosnap-textconv.")
+        .suffix(&format!("_{name}"))
+        .tempfile()
+        .context("failed to create textconv input")?;
+    file.write_all(content)
+        .and_then(|()| file.flush())
+        .context("failed to write textconv input")?;
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{command} \"$@\""))
+        .arg(command)
+        .arg(file.path())
+        .current_dir(workdir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run textconv: {command}"))?;
+    if !output.status.success() {
+        bail!("textconv failed for {}: {command}", path.display());
+    }
+    Ok(output.stdout)
+}
+
+/// Accumulates rules and `[attr]` macros across attribute files.
+struct Parser {
+    rules: Vec<Rule>,
+    macros: HashMap<String, Vec<(String, State)>>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        let binary = ["diff", "merge", "text"]
+            .map(|name| (name.to_string(), State::Unset))
+            .to_vec();
+        Self {
+            rules: Vec::new(),
+            macros: HashMap::from([("binary".to_string(), binary)]),
+        }
+    }
+}
+
+impl Parser {
+    /// Parse one attributes file found in `dir` (`""` or `"sub/dir/"`).
+    fn parse(&mut self, dir: &str, text: &str) {
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            if pattern.starts_with('#') {
+                continue;
+            }
+            let attrs = fields.flat_map(|field| self.expand(field)).collect();
+            // Macros may only be defined at the top level.
+            if let Some(name) = pattern.strip_prefix("[attr]") {
+                if dir.is_empty() {
+                    self.macros.insert(name.to_string(), attrs);
+                }
+                continue;
+            }
+            if let Some(rule) = rule(dir, pattern, attrs) {
+                self.rules.push(rule);
+            }
+        }
+    }
+
+    fn expand(&self, field: &str) -> Vec<(String, State)> {
+        let (name, state) = match field.as_bytes().first() {
+            Some(b'-') => (&field[1..], State::Unset),
+            Some(b'!') => (&field[1..], State::Unspecified),
+            _ => field
+                .split_once('=')
+                .map_or((field, State::Set), |(name, value)| {
+                    (name, State::Value(value.to_string()))
+                }),
+        };
+        let mut attrs = vec![(name.to_string(), state.clone())];
+        if state == State::Set
+            && let Some(expansion) = self.macros.get(name)
+        {
+            attrs.extend(expansion.iter().cloned());
+        }
+        attrs
+    }
+}
+
+// Negated and directory-only patterns never match files, as in git.
+fn rule(dir: &str, pattern: &str, attrs: Vec<(String, State)>) -> Option<Rule> {
+    if pattern.starts_with('!') || pattern.ends_with('/') {
+        return None;
+    }
+    let basename_only = !pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    let matcher = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+        .ok()?
+        .compile_matcher();
+    Some(Rule {
+        base: dir.to_string(),
+        matcher,
+        basename_only,
+        attrs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(files: &[(&str, &str)], drivers: &[(&str, DiffMode)]) -> Gitattributes {
+        let mut parser = Parser::default();
+        for (dir, text) in files {
+            parser.parse(dir, text);
+        }
+        Gitattributes {
+            rules: parser.rules,
+            drivers: drivers
+                .iter()
+                .map(|(name, mode)| ((*name).to_string(), mode.clone()))
+                .collect(),
+            ..Gitattributes::default()
+        }
+    }
+
+    #[test]
+    fn diff_attribute_selects_binary_and_drivers() {
+        let attrs = attributes(
+            &[(
+                "",
+                "*.lock -diff\n*.db binary\n*.ipynb diff=nb\n# *.rs -diff\n",
+            )],
+            &[("nb", DiffMode::Textconv("nbconvert".into()))],
+        );
+        assert_eq!(attrs.lookup("Cargo.lock").0, DiffMode::Binary);
+        assert_eq!(attrs.lookup("data/app.db").0, DiffMode::Binary);
+        assert_eq!(
+            attrs.lookup("notes/a.ipynb").0,
+            DiffMode::Textconv("nbconvert".into())
+        );
+        assert_eq!(attrs.lookup("src/main.rs").0, DiffMode::Text);
+    }
+
+    #[test]
+    fn later_and_deeper_rules_win() {
+        let attrs = attributes(
+            &[
+                (
+                    "",
+                    "*.txt -diff\n/docs/*.txt diff\n[attr]gen linguist-generated\n",
+                ),
+                ("vendor/", "* gen\nkeep.txt !linguist-generated\n"),
+            ],
+            &[],
+        );
+        assert_eq!(attrs.lookup("a.txt").0, DiffMode::Binary);
+        assert_eq!(attrs.lookup("docs/a.txt").0, DiffMode::Text);
+        assert_eq!(attrs.lookup("docs/sub/a.txt").0, DiffMode::Binary);
+        assert!(attrs.lookup("vendor/lib/x.js").1);
+        assert!(!attrs.lookup("vendor/keep.txt").1);
+        assert!(!attrs.lookup("x.js").1);
+    }
+
+    #[test]
+    fn suppressed_headers_drop_file_names() {
+        let header = b"diff --git a/x b/x\nindex 1..2 100644\n--- a/x\n+++ b/x\n";
+        assert_eq!(
+            strip_file_names(header),
+            b"diff --git a/x b/x\nindex 1..2 100644\n"
+        );
+    }
+}
@@ -0,0 +1,433 @@
+use std::io::{self, Write};
+
+use anyhow::{Context, Result, bail};
+use git2::{Commit, Oid, Repository, Signature, Tree};
+
+use super::{
+    index::build_index,
+    picker::{PreviewBase, select_commit_interactive, select_range_interactive},
+    repo::autosnap_dir,
+    revision::{resolve_commit, resolve_main_commit},
+};
+
+mod attributes;
+mod options;
+mod patch;
+mod rename;
+mod stat;
+mod word;
+
+pub(crate) use attributes::Gitattributes;
+pub use options::{DiffFilter, DiffSettings, Whitespace, WordDiff};
+pub(crate) use options::{select_deltas, status_letter};
+pub(crate) use patch::write_patch;
+use patch::{MailHeader, write_format_patch};
+pub use rename::{RenameDetection, parse_similarity};
+pub(crate) use rename::{Similarities, detect_renames, is_rename_or_copy};
+pub use stat::DirstatOptions;
+pub(crate) use stat::{FileStat, collect_file_stats, render_stat, summary_line};
+
+#[derive(Clone, Copy, Debug)]
+pub enum DiffFormat {
+    /// Git's patch format, coloured on a terminal.
+    Unified,
+    /// `git apply`-ready patch: binary deltas included and full object ids.
+    Patch,
+    /// A `git format-patch` mail for the whole range.
+    FormatPatch,
+    /// Per-file histogram; `width` overrides the terminal width.
+    Stat {
+        width: Option<usize>,
+    },
+    NumStat,
+    ShortStat,
+    DirStat(DirstatOptions),
+    NameOnly,
+    NameStatus,
+    /// No output; only whether anything differs.
+    Quiet,
+}
+
+impl DiffFormat {
+    /// Whether the output must round-trip through `git apply`.
+    const fn applicable(self) -> bool {
+        matches!(self, Self::Patch | Self::FormatPatch)
+    }
+
+    /// Whether the output reflects line changes, so files whose changes were all
+    /// ignored drop out (names are listed regardless, as in git).
+    const fn shows_content(self) -> bool {
+        !matches!(self, Self::NameOnly | Self::NameStatus)
+    }
+}
+
+/// Which two trees `diff` compares.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DiffRange<'a> {
+    pub commit1: Option<&'a str>,
+    pub commit2: Option<&'a str>,
+    /// Main repository revision to compare against instead of the first snapshot.
+    pub against_main: Option<&'a str>,
+}
+
+/// Show diffs between snapshots, the working tree and main repository commits.
+///
+/// Returns whether any file differs, after `--diff-filter` and ignored changes, for
+/// `--exit-code`.
+///
+/// # Errors
+/// Returns an error if commits cannot be resolved or diffing fails.
+pub fn diff(
+    repo_root: &std::path::Path,
+    range: DiffRange<'_>,
+    interactive: bool,
+    format: DiffFormat,
+    settings: &DiffSettings,
+    paths: &[String],
+) -> Result<bool> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+
+    // Open the autosnap bare repository
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+
+    let (old, new) = match range.against_main {
+        Some(rev) => resolve_against_main(&repo, repo_root, rev, range, interactive)?,
+        None => resolve_snapshots(&repo, repo_root, range, interactive)?,
+    };
+
+    let mut diff_opts = git2::DiffOptions::new();
+    if format.applicable() {
+        // Like `git diff --binary`, which also implies --full-index.
+        diff_opts.show_binary(true).id_abbrev(40);
+    }
+    settings.apply(&mut diff_opts);
+    for path in paths {
+        diff_opts.pathspec(path);
+    }
+
+    let mut diff =
+        repo.diff_tree_to_tree(Some(&old.tree()?), Some(&new.tree()?), Some(&mut diff_opts))?;
+    detect_renames(&mut diff, settings.renames)?;
+    // Patches meant for `git apply` keep every file's real content.
+    let attributes = if format.applicable() {
+        Gitattributes::default()
+    } else {
+        Gitattributes::load(
+            &repo,
+            repo_root,
+            &attributes_side(&old, &new).tree()?,
+            settings,
+        )?
+    };
+    let indices = select_deltas(&repo, &diff, settings, &attributes, format.shows_content())?;
+
+    let result = if matches!(format, DiffFormat::FormatPatch) {
+        let header = mail_header(&repo, &old, &new)?;
+        let stats = collect_file_stats(&repo, &diff, &indices, &attributes)?;
+        write_format_patch(
+            &repo,
+            &diff,
+            &indices,
+            &stats,
+            &header,
+            &mut io::stdout().lock(),
+        )
+    } else {
+        print_diff(
+            &repo,
+            &diff,
+            &indices,
+            &attributes,
+            format,
+            settings.word_diff,
+        )
+    };
+    ignore_broken_pipe(result)?;
+    Ok(!indices.is_empty())
+}
+
+// Snapshot-to-snapshot or snapshot-to-working-tree sides.
+fn resolve_snapshots<'r>(
+    repo: &'r Repository,
+    repo_root: &std::path::Path,
+    range: DiffRange<'_>,
+    interactive: bool,
+) -> Result<(Side<'r>, Side<'r>)> {
+    let autosnap = autosnap_dir(repo_root);
+
+    // Interactive selection of the range
+    let picked = if interactive {
+        select_range_interactive(&autosnap, repo_root)?
+    } else {
+        None
+    };
+    let commit1 = range
+        .commit1
+        .or_else(|| picked.as_ref().map(|p| p.base.as_str()));
+    let commit2 = range
+        .commit2
+        .or_else(|| picked.as_ref().and_then(|p| p.target.as_deref()));
+
+    // Resolve trees for diffing, keeping the commits for --format-patch headers
+    Ok(match (commit1, commit2) {
+        (None, None) => {
+            // Diff working tree vs HEAD
+            let head = find_commit(repo, "HEAD").context("failed to find HEAD")?;
+            let work_tree = build_working_tree_from_status(repo, repo_root)?;
+            (Side::WorkTree(work_tree), Side::Commit(head))
+        }
+        (Some(commit_ref), None) => {
+            let commit = find_commit(repo, commit_ref)?;
+            let work_tree = build_working_tree_from_status(repo, repo_root)?;
+            (Side::Commit(commit), Side::WorkTree(work_tree))
+        }
+        (Some(commit1_ref), Some(commit2_ref)) => (
+            Side::Commit(find_commit(repo, commit1_ref)?),
+            Side::Commit(find_commit(repo, commit2_ref)?),
+        ),
+        (None, Some(commit_ref)) => {
+            let work_tree = build_working_tree_from_status(repo, repo_root)?;
+            (
+                Side::WorkTree(work_tree),
+                Side::Commit(find_commit(repo, commit_ref)?),
+            )
+        }
+    })
+}
+
+// A main repository commit against a snapshot (COMMIT1) or the working tree.
+fn resolve_against_main<'r>(
+    repo: &'r Repository,
+    repo_root: &std::path::Path,
+    rev: &str,
+    range: DiffRange<'_>,
+    interactive: bool,
+) -> Result<(Side<'r>, Side<'r>)> {
+    if range.commit2.is_some() {
+        bail!("--against-main compares with one snapshot at most");
+    }
+    let selected = if interactive && range.commit1.is_none() {
+        select_commit_interactive(&autosnap_dir(repo_root), PreviewBase::Parent)?
+    } else {
+        None
+    };
+    let new = match range.commit1.or(selected.as_deref()) {
+        Some(commit_ref) => Side::Commit(find_commit(repo, commit_ref)?),
+        None => Side::WorkTree(build_working_tree_from_status(repo, repo_root)?),
+    };
+    // Only attach the main object store once the working tree is captured: with it
+    // attached, libgit2 would skip writing blobs the main repo already has, leaving the
+    // autosnap index pointing at objects its own store lacks.
+    let base = resolve_main_commit(repo, repo_root, rev)?;
+    Ok((Side::Commit(base), new))
+}
+
+/// One end of a diff: a snapshot or the current working tree.
+enum Side<'r> {
+    Commit(Commit<'r>),
+    WorkTree(Tree<'r>),
+}
+
+impl<'r> Side<'r> {
+    fn tree(&self) -> Result<Tree<'r>> {
+        match self {
+            Self::Commit(commit) => commit.tree().context("failed to read snapshot tree"),
+            Self::WorkTree(tree) => Ok(tree.clone()),
+        }
+    }
+}
+
+// Attributes come from the older snapshot, not from a working tree that changed them since.
+const fn attributes_side<'a, 'r>(old: &'a Side<'r>, new: &'a Side<'r>) -> &'a Side<'r> {
+    match old {
+        Side::Commit(_) => old,
+        Side::WorkTree(_) => new,
+    }
+}
+
+fn find_commit<'r>(repo: &'r Repository, spec: &str) -> Result<Commit<'r>> {
+    resolve_commit(repo, spec).with_context(|| format!("failed to find commit: {spec}"))
+}
+
+// The mail describes the new side: its snapshot, or the working tree on top of the
+// old side when there is none.
+fn mail_header<'r>(repo: &'r Repository, old: &Side<'r>, new: &Side<'r>) -> Result<MailHeader<'r>> {
+    if let Side::Commit(commit) = new {
+        let message = commit.message().unwrap_or("");
+        let (subject, body) = message.split_once('\n').unwrap_or((message, ""));
+        return Ok(MailHeader {
+            id: commit.id(),
+            author: commit.author().to_owned(),
+            subject: subject.trim().to_string(),
+            body: body.to_string(),
+        });
+    }
+    let subject = match old {
+        Side::Commit(commit) => format!(
+            "Working tree changes since {}",
+            super::revision::short_summary(commit).0
+        ),
+        Side::WorkTree(_) => "Working tree changes".to_string(),
+    };
+    let author = repo
+        .signature()
+        .or_else(|_| Signature::now("git-autosnap", "git-autosnap@localhost"))
+        .context("failed to create signature")?;
+    Ok(MailHeader {
+        id: Oid::zero(),
+        author,
+        subject,
+        body: String::new(),
+    })
+}
+
+// A closed pipe (`| head`) ends output quietly, like git.
+fn ignore_broken_pipe(result: Result<()>) -> Result<()> {
+    match result {
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+fn print_diff(
+    repo: &Repository,
+    diff: &git2::Diff,
+    indices: &[usize],
+    attributes: &Gitattributes,
+    format: DiffFormat,
+    word_diff: Option<WordDiff>,
+) -> Result<()> {
+    let color = console::colors_enabled();
+    match format {
+        DiffFormat::Unified => match word_diff {
+            // Colour words need a terminal; plain markers work anywhere.
+            Some(mode) => {
+                let mode = if color { mode } else { WordDiff::Plain };
+                let mut out = io::stdout().lock();
+                word::write_word_diff(repo, diff, indices, attributes, mode, &mut out)?;
+            }
+            None => write_patch(
+                repo,
+                diff,
+                indices,
+                attributes,
+                color,
+                &mut io::stdout().lock(),
+            )?,
+        },
+        DiffFormat::Patch | DiffFormat::FormatPatch => {
+            write_patch(
+                repo,
+                diff,
+                indices,
+                attributes,
+                false,
+                &mut io::stdout().lock(),
+            )?;
+        }
+        DiffFormat::Stat { width } => {
+            let stats = collect_file_stats(repo, diff, indices, attributes)?;
+            let width = width.unwrap_or_else(stat::default_stat_width);
+            for line in render_stat(&stats, width, color) {
+                println!("{line}");
+            }
+        }
+        DiffFormat::NumStat => {
+            for line in stat::render_numstat(&collect_file_stats(repo, diff, indices, attributes)?)
+            {
+                println!("{line}");
+            }
+        }
+        DiffFormat::ShortStat => {
+            let stats = collect_file_stats(repo, diff, indices, attributes)?;
+            if !stats.is_empty() {
+                println!("{}", summary_line(&stats));
+            }
+        }
+        DiffFormat::DirStat(opts) => {
+            for line in
+                stat::render_dirstat(&collect_file_stats(repo, diff, indices, attributes)?, opts)
+            {
+                println!("{line}");
+            }
+        }
+        DiffFormat::NameOnly => {
+            print_diff_name_only(diff, indices);
+        }
+        DiffFormat::NameStatus => {
+            print_diff_name_status(diff, indices)?;
+        }
+        DiffFormat::Quiet => {}
+    }
+
+    io::stdout().flush()?;
+    Ok(())
+}
+
+// Build a tree from the working directory for diff operations
+pub(crate) fn build_working_tree_from_status<'a>(
+    repo: &'a Repository,
+    repo_root: &std::path::Path,
+) -> Result<Tree<'a>> {
+    repo.set_workdir(repo_root, false)
+        .context("failed to set workdir")?;
+    build_index(repo).context("failed to build index")?;
+    let mut index = repo.index().context("failed to get index")?;
+    let tree_id = index.write_tree()?;
+    repo.find_tree(tree_id)
+        .context("failed to find written tree")
+}
+
+fn print_diff_name_only(diff: &git2::Diff, indices: &[usize]) {
+    for delta in indices.iter().filter_map(|&idx| diff.get_delta(idx)) {
+        let path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .and_then(|p| p.to_str())
+            .unwrap_or("unknown");
+        println!("{path}");
+    }
+}
+
+/// Print `<status>\t<path>` per selected delta, as in `git diff --name-status`.
+pub(crate) fn print_diff_name_status(diff: &git2::Diff, indices: &[usize]) -> Result<()> {
+    let similarities = Similarities::of(diff)?;
+    for &idx in indices {
+        if let Some(delta) = diff.get_delta(idx) {
+            println!("{}", name_status_line(&delta, &similarities));
+        }
+    }
+    Ok(())
+}
+
+/// Every delta of `diff`, for callers that show the whole diff.
+pub(crate) fn all_deltas(diff: &git2::Diff) -> Vec<usize> {
+    (0..diff.deltas().len()).collect()
+}
+
+/// One `--name-status` line; renames and copies carry their similarity score and
+/// both paths (`R087\told\tnew`).
+pub(crate) fn name_status_line(delta: &git2::DiffDelta, similarities: &Similarities) -> String {
+    let old_path = delta.old_file().path().and_then(|p| p.to_str());
+    let new_path = delta.new_file().path().and_then(|p| p.to_str());
+    let path = new_path.or(old_path).unwrap_or("unknown");
+    let status = status_letter(delta);
+    if is_rename_or_copy(delta) {
+        let score = similarities.get(delta).unwrap_or(100);
+        let old_path = old_path.unwrap_or("unknown");
+        format!("{status}{score:03}\t{old_path}\t{path}")
+    } else {
+        format!("{status}\t{path}")
+    }
+}
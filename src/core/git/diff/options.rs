@@ -0,0 +1,187 @@
+use anyhow::{Result, bail};
+use git2::{Delta, Diff, DiffDelta, Repository};
+
+use super::{attributes::Gitattributes, rename::RenameDetection};
+
+/// How whitespace differences are treated when comparing lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    #[default]
+    Exact,
+    /// `-b`: changes in the amount of whitespace are ignored.
+    IgnoreChange,
+    /// `-w`: all whitespace is ignored.
+    IgnoreAll,
+}
+
+/// `--word-diff` rendering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDiff {
+    /// Changed words in colour only.
+    Color,
+    /// Changed words wrapped in `[-removed-]` and `{+added+}`.
+    Plain,
+}
+
+/// Knobs shared by every `diff` output format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffSettings {
+    pub renames: RenameDetection,
+    pub whitespace: Whitespace,
+    pub ignore_blank_lines: bool,
+    /// Lines of context around each hunk (`-U`), git's 3 by default.
+    pub context_lines: Option<u32>,
+    pub word_diff: Option<WordDiff>,
+    pub filter: Option<DiffFilter>,
+}
+
+impl DiffSettings {
+    /// Apply the line-comparison settings to libgit2's diff options.
+    pub fn apply(&self, opts: &mut git2::DiffOptions) {
+        match self.whitespace {
+            Whitespace::Exact => {}
+            Whitespace::IgnoreChange => {
+                opts.ignore_whitespace_change(true);
+            }
+            Whitespace::IgnoreAll => {
+                opts.ignore_whitespace(true);
+            }
+        }
+        if self.ignore_blank_lines {
+            opts.ignore_blank_lines(true);
+        }
+        if let Some(lines) = self.context_lines {
+            opts.context_lines(lines);
+        }
+    }
+
+    const fn ignores_some_changes(&self) -> bool {
+        !matches!(self.whitespace, Whitespace::Exact) || self.ignore_blank_lines
+    }
+}
+
+/// `--diff-filter`: uppercase status letters select, lowercase ones exclude.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffFilter {
+    include: u32,
+    exclude: u32,
+}
+
+impl DiffFilter {
+    const LETTERS: &'static str = "ACDMRTUXB";
+
+    /// Parse a filter such as `AM` or `d`.
+    ///
+    /// # Errors
+    /// Returns an error for letters git does not know.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut filter = Self::default();
+        for ch in spec.chars() {
+            let Some(bit) = Self::LETTERS.find(ch.to_ascii_uppercase()) else {
+                bail!("unknown change class '{ch}' in --diff-filter={spec}");
+            };
+            if ch.is_ascii_uppercase() {
+                filter.include |= 1 << bit;
+            } else {
+                filter.exclude |= 1 << bit;
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether a change with the given status letter is shown.
+    #[must_use]
+    pub fn matches(self, status: char) -> bool {
+        let bit = Self::LETTERS.find(status).map_or(0, |b| 1 << b);
+        (self.include == 0 || self.include & bit != 0) && self.exclude & bit == 0
+    }
+}
+
+/// Git's one-letter status for a delta, as shown by `--name-status`.
+pub fn status_letter(delta: &DiffDelta<'_>) -> char {
+    match delta.status() {
+        Delta::Added => 'A',
+        Delta::Deleted => 'D',
+        Delta::Modified => 'M',
+        Delta::Renamed => 'R',
+        Delta::Copied => 'C',
+        Delta::Conflicted => 'U',
+        Delta::Ignored => 'I',
+        Delta::Untracked | Delta::Unreadable => '?',
+        Delta::Typechange => 'T',
+        Delta::Unmodified => ' ',
+    }
+}
+
+/// Indices of the deltas to show, after `--diff-filter`.
+///
+/// With `content` set, files whose changes were all ignored (`-w`, `-b`,
+/// `--ignore-blank-lines`) are dropped too, as git does for patch and stat output.
+///
+/// # Errors
+/// Returns an error if a patch cannot be generated.
+pub fn select_deltas(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    settings: &DiffSettings,
+    attributes: &Gitattributes,
+    content: bool,
+) -> Result<Vec<usize>> {
+    let mut selected = Vec::with_capacity(diff.deltas().len());
+    for (idx, delta) in diff.deltas().enumerate() {
+        if settings
+            .filter
+            .is_some_and(|filter| !filter.matches(status_letter(&delta)))
+        {
+            continue;
+        }
+        if content
+            && settings.ignores_some_changes()
+            && only_ignored_changes(repo, diff, idx, &delta, attributes)?
+        {
+            continue;
+        }
+        selected.push(idx);
+    }
+    Ok(selected)
+}
+
+fn only_ignored_changes(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    idx: usize,
+    delta: &DiffDelta<'_>,
+    attributes: &Gitattributes,
+) -> Result<bool> {
+    if delta.status() != Delta::Modified || delta.old_file().mode() != delta.new_file().mode() {
+        return Ok(false);
+    }
+    let Some(patch) = attributes.file_patch(repo, diff, idx)? else {
+        return Ok(false);
+    };
+    Ok(!patch.shows_changes()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_filter_includes_and_excludes() {
+        let only_am = DiffFilter::parse("AM").unwrap();
+        assert!(only_am.matches('A'));
+        assert!(only_am.matches('M'));
+        assert!(!only_am.matches('D'));
+
+        let no_deletes = DiffFilter::parse("d").unwrap();
+        assert!(no_deletes.matches('A'));
+        assert!(!no_deletes.matches('D'));
+
+        let mixed = DiffFilter::parse("Rm").unwrap();
+        assert!(mixed.matches('R'));
+        assert!(!mixed.matches('M'));
+        assert!(!mixed.matches('A'));
+
+        assert!(DiffFilter::parse("Z").is_err());
+    }
+}
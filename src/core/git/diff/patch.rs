@@ -0,0 +1,189 @@
+use std::io::{self, Write};
+
+use anyhow::{Context, Result};
+use console::Style;
+use git2::{Delta, Diff, DiffLineType, Oid, Repository, Signature};
+use time::{OffsetDateTime, UtcOffset, format_description::well_known::Rfc2822};
+
+use super::{
+    attributes::Gitattributes,
+    rename::{Similarities, is_rename_or_copy, pretty_rename},
+    stat::{FileStat, render_stat},
+};
+
+/// Width git uses for the diffstat in `format-patch` mails.
+const MAIL_STAT_WIDTH: usize = 72;
+
+/// Who and what a `--format-patch` mail describes.
+pub struct MailHeader<'a> {
+    /// Snapshot on the new side, or zero for the working tree.
+    pub id: Oid,
+    pub author: Signature<'a>,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Write the selected deltas of `diff` in git's patch format, as `git diff` prints it.
+///
+/// The output is byte-for-byte what `git apply` expects once colour is off: binary
+/// deltas only carry a literal/delta payload when the diff was built with
+/// `show_binary`, and `attributes` must be the default so no file is converted or
+/// suppressed.
+///
+/// # Errors
+/// Returns an error if the patch cannot be generated or written.
+pub fn write_patch(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    indices: &[usize],
+    attributes: &Gitattributes,
+    color: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let meta = Style::new().bold().force_styling(color);
+    let frag = Style::new().cyan().force_styling(color);
+    let old = Style::new().red().force_styling(color);
+    let new = Style::new().green().force_styling(color);
+
+    for &idx in indices {
+        let Some(mut patch) = attributes.file_patch(repo, diff, idx)? else {
+            continue;
+        };
+        patch.print(&mut |origin, content| match origin {
+            DiffLineType::FileHeader => write_line(out, color.then_some(&meta), "", content),
+            DiffLineType::HunkHeader => write_line(out, color.then_some(&frag), "", content),
+            DiffLineType::Addition => write_line(out, color.then_some(&new), "+", content),
+            DiffLineType::Deletion => write_line(out, color.then_some(&old), "-", content),
+            DiffLineType::Context => write_line(out, None, " ", content),
+            // End-of-file newline markers and binary payloads go out verbatim.
+            _ => out.write_all(content),
+        })?;
+    }
+    Ok(())
+}
+
+/// Write `diff` as a single `git format-patch` mail: mbox header, diffstat with the
+/// create/delete/rename summary, then the patch.
+///
+/// # Errors
+/// Returns an error if the patch cannot be generated or written.
+pub fn write_format_patch(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    indices: &[usize],
+    stats: &[FileStat],
+    header: &MailHeader<'_>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let author = &header.author;
+    writeln!(out, "From {} Mon Sep 17 00:00:00 2001", header.id)?;
+    writeln!(
+        out,
+        "From: {} <{}>",
+        author.name().unwrap_or("unknown"),
+        author.email().unwrap_or("unknown")
+    )?;
+    writeln!(out, "Date: {}", mail_date(author.when())?)?;
+    writeln!(out, "Subject: [PATCH] {}", header.subject)?;
+    writeln!(out)?;
+    let body = header.body.trim();
+    if !body.is_empty() {
+        writeln!(out, "{body}")?;
+    }
+    writeln!(out, "---")?;
+    for line in render_stat(stats, MAIL_STAT_WIDTH, false) {
+        writeln!(out, "{line}")?;
+    }
+    for line in summary_lines(diff, indices)? {
+        writeln!(out, "{line}")?;
+    }
+    writeln!(out)?;
+    write_patch(repo, diff, indices, &Gitattributes::default(), false, out)?;
+    writeln!(out, "-- ")?;
+    writeln!(out, "git-autosnap {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Git's `--summary` lines: created, deleted, renamed and copied files and mode changes.
+fn summary_lines(diff: &Diff<'_>, indices: &[usize]) -> Result<Vec<String>> {
+    let similarities = Similarities::of(diff)?;
+    let mut lines = Vec::new();
+    for &idx in indices {
+        let Some(delta) = diff.get_delta(idx) else {
+            continue;
+        };
+        let old_path = delta
+            .old_file()
+            .path()
+            .and_then(|p| p.to_str())
+            .unwrap_or("");
+        let new_path = delta
+            .new_file()
+            .path()
+            .and_then(|p| p.to_str())
+            .unwrap_or("");
+        let old_mode = u32::from(delta.old_file().mode());
+        let new_mode = u32::from(delta.new_file().mode());
+        match delta.status() {
+            Delta::Added => lines.push(format!(" create mode {new_mode:06o} {new_path}")),
+            Delta::Deleted => lines.push(format!(" delete mode {old_mode:06o} {old_path}")),
+            _ if is_rename_or_copy(&delta) => {
+                let kind = if delta.status() == Delta::Copied {
+                    "copy"
+                } else {
+                    "rename"
+                };
+                let score = similarities.get(&delta).unwrap_or(100);
+                lines.push(format!(
+                    " {kind} {} ({score}%)",
+                    pretty_rename(old_path, new_path)
+                ));
+                if old_mode != new_mode {
+                    lines.push(format!(" mode change {old_mode:06o} => {new_mode:06o}"));
+                }
+            }
+            _ if old_mode != new_mode => lines.push(format!(
+                " mode change {old_mode:06o} => {new_mode:06o} {new_path}"
+            )),
+            _ => {}
+        }
+    }
+    Ok(lines)
+}
+
+// Without a style the bytes go out untouched; with one, each line is styled on its
+// own so resets never swallow the newline.
+pub fn write_line(
+    out: &mut dyn Write,
+    style: Option<&Style>,
+    prefix: &str,
+    content: &[u8],
+) -> io::Result<()> {
+    let Some(style) = style else {
+        out.write_all(prefix.as_bytes())?;
+        return out.write_all(content);
+    };
+    let text = String::from_utf8_lossy(content);
+    for line in text.split_inclusive('\n') {
+        let (body, newline) = line
+            .strip_suffix('\n')
+            .map_or((line, ""), |body| (body, "\n"));
+        write!(
+            out,
+            "{}{newline}",
+            style.apply_to(format!("{prefix}{body}"))
+        )?;
+    }
+    Ok(())
+}
+
+fn mail_date(when: git2::Time) -> Result<String> {
+    let offset = UtcOffset::from_whole_seconds(when.offset_minutes() * 60)
+        .context("invalid commit time offset")?;
+    OffsetDateTime::from_unix_timestamp(when.seconds())
+        .context("invalid commit time")?
+        .to_offset(offset)
+        .format(&Rfc2822)
+        .context("failed to format commit date")
+}
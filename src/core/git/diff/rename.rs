@@ -0,0 +1,230 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{Context, Result, bail};
+use git2::{Delta, Diff, DiffDelta, DiffFindOptions, DiffFormat};
+
+/// Rename/copy detection thresholds (percent similarity), `None` to disable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameDetection {
+    pub renames: Option<u16>,
+    pub copies: Option<u16>,
+}
+
+impl Default for RenameDetection {
+    /// Renames at git's default 50% similarity; copies off.
+    fn default() -> Self {
+        Self {
+            renames: Some(50),
+            copies: None,
+        }
+    }
+}
+
+impl RenameDetection {
+    /// No rename or copy detection.
+    #[must_use]
+    pub const fn disabled() -> Self {
+        Self {
+            renames: None,
+            copies: None,
+        }
+    }
+}
+
+/// Parse a git similarity score as accepted by `-M`/`-C`.
+///
+/// Like git, `90%` is a percentage while bare digits are a decimal fraction, so `9`,
+/// `90` and `0.9` all mean 90%.
+///
+/// # Errors
+/// Returns an error if the score is malformed or above 100%.
+pub fn parse_similarity(score: &str) -> Result<u16> {
+    let percent = score.strip_suffix('%').map_or_else(
+        || {
+            let digits = score.strip_prefix("0.").unwrap_or(score);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            // Only the first two fractional digits matter at percent resolution.
+            let padded = format!("{digits:0<2}");
+            padded[..2].parse::<u16>().ok()
+        },
+        |pct| pct.parse::<u16>().ok(),
+    );
+    match percent {
+        Some(p) if p <= 100 => Ok(p),
+        _ => bail!("invalid similarity score: {score}"),
+    }
+}
+
+/// Run libgit2's rename/copy detection over `diff` in place.
+///
+/// # Errors
+/// Returns an error if similarity detection fails.
+pub fn detect_renames(diff: &mut Diff<'_>, detection: RenameDetection) -> Result<()> {
+    if detection.renames.is_none() && detection.copies.is_none() {
+        return Ok(());
+    }
+    let mut opts = DiffFindOptions::new();
+    if let Some(threshold) = detection.renames {
+        opts.renames(true).rename_threshold(threshold);
+    }
+    if let Some(threshold) = detection.copies {
+        // Like git's -C, copies also imply renames.
+        opts.renames(true).copies(true).copy_threshold(threshold);
+        if detection.renames.is_none() {
+            opts.rename_threshold(threshold);
+        }
+    }
+    diff.find_similar(Some(&mut opts))
+        .context("failed to detect renames")
+}
+
+/// The similarity scores `find_similar` gave a diff's renames and copies.
+#[derive(Debug, Default)]
+pub struct Similarities(HashMap<(PathBuf, PathBuf), u16>);
+
+impl Similarities {
+    /// Read every score of `diff` in one pass.
+    ///
+    /// libgit2 keeps the score on the delta without exposing it, but prints it in the
+    /// raw format's status (`R087`).
+    ///
+    /// # Errors
+    /// Returns an error if the diff cannot be printed.
+    pub fn of(diff: &Diff<'_>) -> Result<Self> {
+        let mut scores = HashMap::new();
+        diff.print(DiffFormat::Raw, |delta, _, line| {
+            if is_rename_or_copy(&delta)
+                && let Some(score) = std::str::from_utf8(line.content())
+                    .ok()
+                    .and_then(|raw| raw.split('\t').next()?.split(' ').next_back())
+                    .and_then(|status| status.get(1..)?.parse().ok())
+                && let (Some(old), Some(new)) = (delta.old_file().path(), delta.new_file().path())
+            {
+                scores.insert((old.to_path_buf(), new.to_path_buf()), score);
+            }
+            true
+        })
+        .context("failed to read similarity scores")?;
+        Ok(Self(scores))
+    }
+
+    /// Similarity percentage of a renamed or copied delta.
+    #[must_use]
+    pub fn get(&self, delta: &DiffDelta<'_>) -> Option<u16> {
+        let old = delta.old_file().path()?.to_path_buf();
+        let new = delta.new_file().path()?.to_path_buf();
+        self.0.get(&(old, new)).copied()
+    }
+}
+
+pub fn is_rename_or_copy(delta: &DiffDelta<'_>) -> bool {
+    matches!(delta.status(), Delta::Renamed | Delta::Copied)
+}
+
+/// Git's compact rename form: `dir/{old => new}/file`, or `old => new` when the paths
+/// share no leading or trailing directories. A port of git's `pprint_rename`.
+pub fn pretty_rename(old: &str, new: &str) -> String {
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+    // Like git, read one past the end as NUL so both scans can start there.
+    let at = |s: &[u8], i: usize| s.get(i).copied().unwrap_or(0);
+
+    // Common prefix, ending in a slash.
+    let mut prefix = 0;
+    let mut i = 0;
+    while i < a.len() && i < b.len() && a[i] == b[i] {
+        if a[i] == b'/' {
+            prefix = i + 1;
+        }
+        i += 1;
+    }
+
+    // Common suffix, starting with a slash. It may share the prefix's trailing slash.
+    let floor = prefix.saturating_sub(1);
+    let mut suffix = 0;
+    let (mut ia, mut ib) = (a.len(), b.len());
+    while ia >= floor && ib >= floor && at(a, ia) == at(b, ib) {
+        if at(a, ia) == b'/' {
+            suffix = a.len() - ia;
+        }
+        if ia == 0 || ib == 0 {
+            break;
+        }
+        ia -= 1;
+        ib -= 1;
+    }
+
+    if prefix + suffix == 0 {
+        return format!("{old} => {new}");
+    }
+    let old_mid = a.len().saturating_sub(prefix + suffix);
+    let new_mid = b.len().saturating_sub(prefix + suffix);
+    format!(
+        "{}{{{} => {}}}{}",
+        &old[..prefix],
+        &old[prefix..prefix + old_mid],
+        &new[prefix..prefix + new_mid],
+        &old[a.len() - suffix..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_scores_follow_git() {
+        assert_eq!(parse_similarity("90%").unwrap(), 90);
+        assert_eq!(parse_similarity("9").unwrap(), 90);
+        assert_eq!(parse_similarity("90").unwrap(), 90);
+        assert_eq!(parse_similarity("05").unwrap(), 5);
+        assert_eq!(parse_similarity("0.75").unwrap(), 75);
+        assert_eq!(parse_similarity("100%").unwrap(), 100);
+        assert!(parse_similarity("101%").is_err());
+        assert!(parse_similarity("abc").is_err());
+    }
+
+    #[test]
+    fn similarities_match_the_patch_header() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(dir.path()).unwrap();
+        let tree = |name: &str, content: &str| {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert(name, blob, 0o100_644).unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        let lines = (1..=20)
+            .map(|n| format!("line {n}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        let old = tree("old.txt", &lines);
+        let new = tree("new.txt", &lines.replace("line 7\n", "changed\n"));
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&old), Some(&new), None)
+            .unwrap();
+        detect_renames(&mut diff, RenameDetection::default()).unwrap();
+
+        let delta = diff.get_delta(0).unwrap();
+        assert_eq!(delta.status(), Delta::Renamed);
+        let score = Similarities::of(&diff).unwrap().get(&delta).unwrap();
+        let mut patch = git2::Patch::from_diff(&diff, 0).unwrap().unwrap();
+        let header = format!("similarity index {score}%");
+        assert!(score < 100);
+        assert!(patch.to_buf().unwrap().as_str().unwrap().contains(&header));
+    }
+
+    #[test]
+    fn renames_are_compacted_like_git() {
+        assert_eq!(pretty_rename("a.txt", "b.txt"), "a.txt => b.txt");
+        assert_eq!(
+            pretty_rename("src/old/mod.rs", "src/new/mod.rs"),
+            "src/{old => new}/mod.rs"
+        );
+        assert_eq!(pretty_rename("src/a.rs", "src/b.rs"), "src/{a.rs => b.rs}");
+        assert_eq!(pretty_rename("a/x.rs", "b/x.rs"), "{a => b}/x.rs");
+        assert_eq!(pretty_rename("x.rs", "dir/x.rs"), "x.rs => dir/x.rs");
+        assert_eq!(pretty_rename("a/x.rs", "a/y/x.rs"), "a/{ => y}/x.rs");
+    }
+}
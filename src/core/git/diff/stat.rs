@@ -0,0 +1,484 @@
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+use console::{Style, Term, measure_text_width};
+use git2::{Diff, Patch, Repository};
+
+use super::{
+    attributes::Gitattributes,
+    rename::{is_rename_or_copy, pretty_rename},
+};
+
+/// Per-file change counts, the input to every stat renderer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub path: String,
+    /// Source path when the file was renamed or copied.
+    pub old_path: Option<String>,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Old and new sizes in bytes when either side is binary.
+    pub binary: Option<(u64, u64)>,
+}
+
+impl FileStat {
+    const fn changes(&self) -> usize {
+        self.insertions + self.deletions
+    }
+
+    /// Name shown by `--stat`/`--numstat`, in git's `dir/{old => new}` form for renames.
+    pub fn display_name(&self) -> String {
+        self.old_path
+            .as_deref()
+            .map_or_else(|| self.path.clone(), |old| pretty_rename(old, &self.path))
+    }
+}
+
+/// How `--dirstat` weighs and accumulates changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirstatOptions {
+    /// Count each changed file once instead of by changed lines.
+    pub by_file: bool,
+    /// Also count a reported subdirectory's changes in its parents.
+    pub cumulative: bool,
+    /// Directories below this share of all changes (in permille) are not shown.
+    pub limit_permille: u64,
+}
+
+impl Default for DirstatOptions {
+    fn default() -> Self {
+        Self {
+            by_file: false,
+            cumulative: false,
+            limit_permille: 30,
+        }
+    }
+}
+
+impl DirstatOptions {
+    /// Parse git's `--dirstat=` parameter list, e.g. `files,10,cumulative`.
+    ///
+    /// # Errors
+    /// Returns an error for unknown parameters.
+    pub fn parse(params: &str) -> Result<Self> {
+        let mut opts = Self::default();
+        for param in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match param {
+                "changes" | "lines" => opts.by_file = false,
+                "files" => opts.by_file = true,
+                "cumulative" => opts.cumulative = true,
+                "noncumulative" => opts.cumulative = false,
+                limit => {
+                    opts.limit_permille = parse_permille(limit)
+                        .with_context(|| format!("unknown --dirstat parameter: {param}"))?;
+                }
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Collect insertion/deletion counts (or binary sizes) for the given deltas of `diff`.
+///
+/// # Errors
+/// Returns an error if a patch cannot be generated.
+pub fn collect_file_stats(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    indices: &[usize],
+    attributes: &Gitattributes,
+) -> Result<Vec<FileStat>> {
+    let blob_size = |id: git2::Oid| {
+        if id.is_zero() {
+            0
+        } else {
+            repo.find_blob(id).map_or(0, |b| b.size() as u64)
+        }
+    };
+
+    let mut stats = Vec::with_capacity(indices.len());
+    for &idx in indices {
+        // Generating the patch loads both sides, which also settles the binary flag.
+        let patch = Patch::from_diff(diff, idx).context("failed to generate patch")?;
+        let Some(delta) = patch
+            .as_ref()
+            .map(Patch::delta)
+            .or_else(|| diff.get_delta(idx))
+        else {
+            continue;
+        };
+        let file_path = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map_or_else(
+                || "unknown".to_string(),
+                |p| p.to_string_lossy().into_owned(),
+            );
+        let old_path = is_rename_or_copy(&delta)
+            .then(|| delta.old_file().path())
+            .flatten()
+            .map(|p| p.to_string_lossy().into_owned());
+
+        // Counts come from the stored content, but `-diff` still makes a file binary.
+        if delta.flags().is_binary() || attributes.is_binary(&file_path) {
+            stats.push(FileStat {
+                path: file_path,
+                old_path,
+                insertions: 0,
+                deletions: 0,
+                binary: Some((
+                    blob_size(delta.old_file().id()),
+                    blob_size(delta.new_file().id()),
+                )),
+            });
+        } else {
+            let (_, insertions, deletions) = match &patch {
+                Some(patch) => patch.line_stats()?,
+                None => (0, 0, 0),
+            };
+            stats.push(FileStat {
+                path: file_path,
+                old_path,
+                insertions,
+                deletions,
+                binary: None,
+            });
+        }
+    }
+    Ok(stats)
+}
+
+/// Git's closing line, e.g. ` 2 files changed, 3 insertions(+), 1 deletion(-)`.
+pub fn summary_line(stats: &[FileStat]) -> String {
+    let files = stats.len();
+    let insertions: usize = stats.iter().map(|s| s.insertions).sum();
+    let deletions: usize = stats.iter().map(|s| s.deletions).sum();
+
+    let mut line = format!(" {files} file{} changed", if files == 1 { "" } else { "s" });
+    // Like git, "0 insertions" / "0 deletions" only appear when both are zero.
+    if insertions > 0 || deletions == 0 {
+        let _ = write!(
+            line,
+            ", {insertions} insertion{}(+)",
+            if insertions == 1 { "" } else { "s" }
+        );
+    }
+    if deletions > 0 || insertions == 0 {
+        let _ = write!(
+            line,
+            ", {deletions} deletion{}(-)",
+            if deletions == 1 { "" } else { "s" }
+        );
+    }
+    line
+}
+
+/// Default `--stat` width: the terminal width, or 80 when not writing to a terminal.
+pub fn default_stat_width() -> usize {
+    let term = Term::stdout();
+    if term.is_term() {
+        usize::from(term.size().1)
+    } else {
+        80
+    }
+}
+
+/// Render git's `--stat` layout: ` path | N +++--` per file plus the summary line.
+pub fn render_stat(stats: &[FileStat], width: usize, color: bool) -> Vec<String> {
+    if stats.is_empty() {
+        return Vec::new();
+    }
+
+    let added_style = Style::new().green().force_styling(color);
+    let removed_style = Style::new().red().force_styling(color);
+
+    let max_change = stats.iter().map(FileStat::changes).max().unwrap_or(0);
+    let names: Vec<String> = stats.iter().map(FileStat::display_name).collect();
+    let max_name = names
+        .iter()
+        .map(|n| measure_text_width(n))
+        .max()
+        .unwrap_or(0);
+    let bin_labels: Vec<Option<String>> = stats
+        .iter()
+        .map(|s| s.binary.map(|(old, new)| format!(" {old} -> {new} bytes")))
+        .collect();
+    let mut number_width = decimal_width(max_change);
+    let bin_width = bin_labels
+        .iter()
+        .flatten()
+        .map(String::len)
+        .max()
+        .map(|len| {
+            number_width = number_width.max(3);
+            len + 3
+        });
+
+    // Same budget split as git: at least 6 columns of graph, the rest for names.
+    let width = width.max(16 + 6 + number_width);
+    let mut graph_width = match bin_width {
+        Some(bin) if max_change + 4 <= bin => bin - 4,
+        _ => max_change,
+    };
+    let mut name_width = max_name;
+    if name_width + number_width + 6 + graph_width > width {
+        let graph_budget = (width * 3 / 8).saturating_sub(number_width + 6);
+        if graph_width > graph_budget {
+            graph_width = graph_budget.max(6);
+        }
+        let name_budget = width.saturating_sub(number_width + 6 + graph_width);
+        if name_width > name_budget {
+            name_width = name_budget;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let mut lines = Vec::with_capacity(stats.len() + 1);
+    for ((stat, bin_label), full_name) in stats.iter().zip(&bin_labels).zip(&names) {
+        let name = truncate_name(full_name, name_width);
+        let padding = name_width.saturating_sub(measure_text_width(&name));
+        let mut line = format!(" {name}{} |", " ".repeat(padding));
+        if let Some(label) = bin_label {
+            let _ = write!(line, " {:>number_width$}{label}", "Bin");
+        } else {
+            let (mut added, mut removed) = (stat.insertions, stat.deletions);
+            if graph_width <= max_change {
+                let mut total = scale_linear(added + removed, graph_width, max_change);
+                if total < 2 && added > 0 && removed > 0 {
+                    total = 2;
+                }
+                if added < removed {
+                    added = scale_linear(added, graph_width, max_change);
+                    removed = total - added;
+                } else {
+                    removed = scale_linear(removed, graph_width, max_change);
+                    added = total - removed;
+                }
+            }
+            let _ = write!(line, " {:>number_width$}", stat.changes());
+            if stat.changes() > 0 {
+                line.push(' ');
+            }
+            line.push_str(&added_style.apply_to("+".repeat(added)).to_string());
+            line.push_str(&removed_style.apply_to("-".repeat(removed)).to_string());
+        }
+        lines.push(line);
+    }
+    lines.push(summary_line(stats));
+    lines
+}
+
+/// Render `--numstat`: `added<TAB>deleted<TAB>path`, with `-` for binary files.
+pub fn render_numstat(stats: &[FileStat]) -> Vec<String> {
+    stats
+        .iter()
+        .map(|s| {
+            if s.binary.is_some() {
+                format!("-\t-\t{}", s.display_name())
+            } else {
+                format!("{}\t{}\t{}", s.insertions, s.deletions, s.display_name())
+            }
+        })
+        .collect()
+}
+
+/// Render `--dirstat`: the share of changes per directory, following git's rules for
+/// which directories are reported.
+pub fn render_dirstat(stats: &[FileStat], opts: DirstatOptions) -> Vec<String> {
+    let mut files: Vec<(&str, u64)> = stats
+        .iter()
+        .map(|s| {
+            let damage = if opts.by_file {
+                1
+            } else if let Some((old, new)) = s.binary {
+                // Binary files have no lines; weigh them by how much their size moved.
+                old.abs_diff(new).max(1)
+            } else {
+                s.changes() as u64
+            };
+            (s.path.as_str(), damage)
+        })
+        .filter(|(_, damage)| *damage > 0)
+        .collect();
+    files.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let total: u64 = files.iter().map(|(_, d)| d).sum();
+    let mut lines = Vec::new();
+    if total == 0 {
+        return lines;
+    }
+    let mut cursor = 0;
+    gather_dirstat(
+        &files,
+        &mut cursor,
+        "",
+        total,
+        opts.limit_permille,
+        opts.cumulative,
+        &mut lines,
+    );
+    lines
+}
+
+// Port of git's `gather_dirstat`: returns the changes under `base` that were not
+// already reported by a subdirectory (unless cumulative).
+fn gather_dirstat(
+    files: &[(&str, u64)],
+    cursor: &mut usize,
+    base: &str,
+    total: u64,
+    min_permille: u64,
+    cumulative: bool,
+    out: &mut Vec<String>,
+) -> u64 {
+    let mut sum = 0;
+    let mut sources = 0;
+    while let Some(&(name, damage)) = files.get(*cursor) {
+        if !name.starts_with(base) {
+            break;
+        }
+        if let Some(slash) = name[base.len()..].find('/') {
+            let sub = &name[..=base.len() + slash];
+            sum += gather_dirstat(files, cursor, sub, total, min_permille, cumulative, out);
+            sources += 1;
+        } else {
+            sum += damage;
+            *cursor += 1;
+            sources += 2;
+        }
+    }
+
+    // Neither the top level nor a directory whose changes all come from a single
+    // subdirectory is reported.
+    if !base.is_empty() && sources != 1 && sum > 0 {
+        let permille = sum * 1000 / total;
+        if permille >= min_permille {
+            out.push(format!("{:>4}.{}% {base}", permille / 10, permille % 10));
+            if !cumulative {
+                return 0;
+            }
+        }
+    }
+    sum
+}
+
+// A percentage such as `3` or `2.5`, kept in permille like git to get identical rounding.
+fn parse_permille(percent: &str) -> Option<u64> {
+    let (whole, fraction) = percent.split_once('.').unwrap_or((percent, ""));
+    let whole: u64 = whole.parse().ok()?;
+    let tenths = match fraction.chars().next() {
+        None => 0,
+        Some(c) => u64::from(c.to_digit(10)?),
+    };
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    whole.checked_mul(10)?.checked_add(tenths)
+}
+
+const fn scale_linear(it: usize, width: usize, max_change: usize) -> usize {
+    if it == 0 || max_change == 0 {
+        return 0;
+    }
+    1 + it * width.saturating_sub(1) / max_change
+}
+
+const fn decimal_width(mut n: usize) -> usize {
+    let mut width = 1;
+    while n >= 10 {
+        n /= 10;
+        width += 1;
+    }
+    width
+}
+
+// Keep the tail of long paths, preferring to cut at a directory boundary.
+fn truncate_name(path: &str, width: usize) -> String {
+    if measure_text_width(path) <= width {
+        return path.to_string();
+    }
+    let keep = width.saturating_sub(3);
+    let chars: Vec<char> = path.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(keep)..].iter().collect();
+    let tail = tail.find('/').map_or(tail.as_str(), |i| &tail[i..]);
+    format!("...{tail}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(path: &str, insertions: usize, deletions: usize) -> FileStat {
+        FileStat {
+            path: path.to_string(),
+            old_path: None,
+            insertions,
+            deletions,
+            binary: None,
+        }
+    }
+
+    #[test]
+    fn summary_uses_git_pluralization() {
+        assert_eq!(
+            summary_line(&[stat("a", 1, 0)]),
+            " 1 file changed, 1 insertion(+)"
+        );
+        assert_eq!(
+            summary_line(&[stat("a", 2, 1), stat("b", 0, 1)]),
+            " 2 files changed, 2 insertions(+), 2 deletions(-)"
+        );
+        assert_eq!(
+            summary_line(&[stat("a", 0, 0)]),
+            " 1 file changed, 0 insertions(+), 0 deletions(-)"
+        );
+    }
+
+    #[test]
+    fn stat_scales_graph_to_width() {
+        let lines = render_stat(&[stat("src/main.rs", 300, 100), stat("b", 1, 0)], 40, false);
+        assert_eq!(lines[0], " src/main.rs | 400 +++++++++++++++-----");
+        assert_eq!(lines[1], " b           |   1 +");
+        assert!(lines[..2].iter().all(|l| l.len() <= 40));
+    }
+
+    #[test]
+    fn stat_shows_binary_sizes() {
+        let mut bin = stat("logo.png", 0, 0);
+        bin.binary = Some((10, 25));
+        let lines = render_stat(&[bin, stat("a.txt", 2, 1)], 80, false);
+        assert_eq!(lines[0], " logo.png | Bin 10 -> 25 bytes");
+        assert_eq!(lines[1], " a.txt    |   3 ++-");
+    }
+
+    #[test]
+    fn long_names_are_truncated_at_a_slash() {
+        assert_eq!(
+            truncate_name("some/deeply/nested/file.rs", 16),
+            ".../file.rs"
+        );
+    }
+
+    #[test]
+    fn dirstat_matches_git_reporting_rules() {
+        let stats = [
+            stat("src/a.rs", 50, 0),
+            stat("src/core/b.rs", 30, 0),
+            stat("src/core/c.rs", 10, 0),
+            stat("README.md", 10, 0),
+        ];
+        assert_eq!(
+            render_dirstat(&stats, DirstatOptions::default()),
+            vec!["  40.0% src/core/", "  50.0% src/"]
+        );
+        let cumulative = DirstatOptions::parse("cumulative").unwrap();
+        assert_eq!(
+            render_dirstat(&stats, cumulative),
+            vec!["  40.0% src/core/", "  90.0% src/"]
+        );
+        assert_eq!(DirstatOptions::parse("2.5").unwrap().limit_permille, 25);
+        assert!(DirstatOptions::parse("bogus").is_err());
+    }
+}
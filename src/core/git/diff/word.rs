@@ -0,0 +1,300 @@
+use std::io::{self, Write};
+
+use anyhow::{Context, Result};
+use console::Style;
+use git2::{Diff, DiffLineType, Repository};
+
+use super::{attributes::Gitattributes, options::WordDiff, patch::write_line};
+
+/// Token pairs beyond which the word-level LCS is skipped and the whole block is shown
+/// as replaced; keeps pathological hunks from going quadratic.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Write the selected deltas like `git diff --word-diff`: headers as usual, then each
+/// run of removed/added lines rendered as one line-wrapped text with the changed words
+/// marked.
+///
+/// # Errors
+/// Returns an error if a patch cannot be generated or written.
+pub fn write_word_diff(
+    repo: &Repository,
+    diff: &Diff<'_>,
+    indices: &[usize],
+    attributes: &Gitattributes,
+    mode: WordDiff,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let color = mode == WordDiff::Color;
+    let meta = Style::new().bold().force_styling(true);
+    let frag = Style::new().cyan().force_styling(true);
+    let markers = Markers::new(mode);
+
+    for &idx in indices {
+        let Some(mut patch) = attributes.file_patch(repo, diff, idx)? else {
+            continue;
+        };
+        let mut block = Block::default();
+        patch.print(&mut |origin, content| match origin {
+            DiffLineType::Deletion => {
+                block.minus.push_str(&String::from_utf8_lossy(content));
+                Ok(())
+            }
+            DiffLineType::Addition => {
+                block.plus.push_str(&String::from_utf8_lossy(content));
+                Ok(())
+            }
+            DiffLineType::FileHeader => write_line(out, color.then_some(&meta), "", content),
+            DiffLineType::HunkHeader => block
+                .flush(&markers, out)
+                .and_then(|()| write_line(out, color.then_some(&frag), "", content)),
+            DiffLineType::Context => block
+                .flush(&markers, out)
+                .and_then(|()| out.write_all(content)),
+            // Git drops the end-of-file newline markers in word diffs.
+            DiffLineType::ContextEOFNL | DiffLineType::AddEOFNL | DiffLineType::DeleteEOFNL => {
+                Ok(())
+            }
+            DiffLineType::Binary => out.write_all(content),
+        })?;
+        block
+            .flush(&markers, out)
+            .context("failed to write word diff")?;
+    }
+    Ok(())
+}
+
+/// Opening and closing text around removed and added words.
+struct Markers {
+    removed: (String, String),
+    added: (String, String),
+}
+
+impl Markers {
+    fn new(mode: WordDiff) -> Self {
+        match mode {
+            WordDiff::Plain => Self {
+                removed: ("[-".into(), "-]".into()),
+                added: ("{+".into(), "+}".into()),
+            },
+            WordDiff::Color => Self {
+                removed: ("\x1b[31m".into(), "\x1b[m".into()),
+                added: ("\x1b[32m".into(), "\x1b[m".into()),
+            },
+        }
+    }
+}
+
+/// Consecutive removed and added lines awaiting a word-level comparison.
+#[derive(Default)]
+struct Block {
+    minus: String,
+    plus: String,
+}
+
+impl Block {
+    fn flush(&mut self, markers: &Markers, out: &mut dyn Write) -> io::Result<()> {
+        if self.minus.is_empty() && self.plus.is_empty() {
+            return Ok(());
+        }
+        let rendered = render_words(&self.minus, &self.plus, markers);
+        self.minus.clear();
+        self.plus.clear();
+        out.write_all(rendered.as_bytes())
+    }
+}
+
+/// Render one block the way git's `diff_words_show` does: unchanged text comes from
+/// the new side, changed word runs are wrapped in markers line by line.
+fn render_words(minus: &str, plus: &str, markers: &Markers) -> String {
+    let mut out = String::new();
+    // Only removals: every removed line is shown on its own.
+    if plus.is_empty() {
+        write_marked(&mut out, minus, &markers.removed);
+        return out;
+    }
+
+    let old = words(minus);
+    let new = words(plus);
+    let old_words: Vec<&str> = old.iter().map(|&(b, e)| &minus[b..e]).collect();
+    let new_words: Vec<&str> = new.iter().map(|&(b, e)| &plus[b..e]).collect();
+    let mut plus_pos = 0;
+    for change in changed_runs(&old_words, &new_words) {
+        // Like git, a pure removal sits right after the preceding word.
+        let plus_begin = if change.new.is_empty() {
+            change
+                .new
+                .start
+                .checked_sub(1)
+                .map_or(0, |prev| new[prev].1)
+        } else {
+            new[change.new.start].0
+        };
+        write_marked(
+            &mut out,
+            &plus[plus_pos..plus_begin],
+            &(String::new(), String::new()),
+        );
+        if !change.old.is_empty() {
+            let begin = old[change.old.start].0;
+            let end = old[change.old.end - 1].1;
+            write_marked(&mut out, &minus[begin..end], &markers.removed);
+        }
+        if change.new.is_empty() {
+            plus_pos = plus_begin;
+        } else {
+            let end = new[change.new.end - 1].1;
+            write_marked(&mut out, &plus[plus_begin..end], &markers.added);
+            plus_pos = end;
+        }
+    }
+    write_marked(&mut out, &plus[plus_pos..], &(String::new(), String::new()));
+    out
+}
+
+// Wrap every non-empty line segment of `text` in the markers, keeping the newlines.
+fn write_marked(out: &mut String, text: &str, (open, close): &(String, String)) {
+    for (i, segment) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        if !segment.is_empty() {
+            out.push_str(open);
+            out.push_str(segment);
+            out.push_str(close);
+        }
+    }
+}
+
+/// Byte ranges of the whitespace-separated words in `text`.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, ch) in text.char_indices() {
+        match (ch.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len()));
+    }
+    words
+}
+
+/// A run of words replaced between the two sides (either range may be empty).
+#[derive(Debug, PartialEq, Eq)]
+struct Change {
+    old: std::ops::Range<usize>,
+    new: std::ops::Range<usize>,
+}
+
+// Longest-common-subsequence alignment of the two word lists, reported as the runs in
+// between matched words.
+fn changed_runs(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let shift = |change: Change| Change {
+        old: change.old.start + prefix..change.old.end + prefix,
+        new: change.new.start + prefix..change.new.end + prefix,
+    };
+
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return Vec::new();
+    }
+    if old_mid.is_empty() || new_mid.is_empty() || old_mid.len() * new_mid.len() > MAX_LCS_CELLS {
+        return vec![shift(Change {
+            old: 0..old_mid.len(),
+            new: 0..new_mid.len(),
+        })];
+    }
+
+    // lcs[i][j]: length of the LCS of old_mid[i..] and new_mid[j..].
+    let width = new_mid.len() + 1;
+    let mut lcs = vec![0u32; (old_mid.len() + 1) * width];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut run = (0, 0);
+    while i < old_mid.len() && j < new_mid.len() {
+        if old_mid[i] == new_mid[j] {
+            if (i, j) != run {
+                changes.push(shift(Change {
+                    old: run.0..i,
+                    new: run.1..j,
+                }));
+            }
+            i += 1;
+            j += 1;
+            run = (i, j);
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    if run != (old_mid.len(), new_mid.len()) {
+        changes.push(shift(Change {
+            old: run.0..old_mid.len(),
+            new: run.1..new_mid.len(),
+        }));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(minus: &str, plus: &str) -> String {
+        render_words(minus, plus, &Markers::new(WordDiff::Plain))
+    }
+
+    #[test]
+    fn words_are_marked_like_git() {
+        assert_eq!(
+            plain(
+                "the quick brown fox\njumps over\nremoved line here\n",
+                "the slow  brown cat\njumps over it\n"
+            ),
+            "the [-quick-]{+slow+}  brown [-fox-]{+cat+}\njumps over [-removed line here-]{+it+}\n"
+        );
+    }
+
+    #[test]
+    fn pure_additions_and_removals_keep_their_lines() {
+        assert_eq!(plain("", "added line\n"), "{+added line+}\n");
+        assert_eq!(plain("a b\nc\n", ""), "[-a b-]\n[-c-]\n");
+    }
+
+    #[test]
+    fn removals_attach_to_the_previous_word() {
+        assert_eq!(plain("a del b\n", "a b\n"), "a[-del-] b\n");
+        assert_eq!(plain("a del b\n", "del b\n"), "[-a-]del b\n");
+        assert_eq!(plain("a del b\n", "a del\n"), "a del[-b-]\n");
+    }
+
+    #[test]
+    fn whitespace_only_changes_show_the_new_spacing() {
+        assert_eq!(plain("x y\n\n\nz\n", "x  q y\nz\n"), "x  {+q+} y\nz\n");
+    }
+}
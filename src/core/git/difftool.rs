@@ -0,0 +1,179 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{Config, Delta, Repository};
+
+use super::{
+    diff::{RenameDetection, detect_renames},
+    extract::extract_paths_to_path,
+    repo::autosnap_dir,
+    revision::resolve_commit,
+    shell::report_extract_issues,
+};
+
+/// Options for [`difftool`].
+#[derive(Debug, Clone, Copy)]
+pub struct DifftoolOptions<'a> {
+    pub commit1: &'a str,
+    pub commit2: &'a str,
+    /// Run the tool once on two directories instead of once per file.
+    pub dir_diff: bool,
+    /// Tool name overriding `autosnap.difftool`/`diff.tool`.
+    pub tool: Option<&'a str>,
+    pub renames: RenameDetection,
+    pub paths: &'a [String],
+}
+
+/// Open the changes between two snapshots in an external diff tool.
+///
+/// Only changed files are extracted, into `left/` and `right/` under a temporary
+/// directory. The tool runs like `git difftool`: `difftool.<tool>.cmd` with `$LOCAL`,
+/// `$REMOTE`, `$MERGED` and `$BASE` set, or a built-in command line for common tools.
+///
+/// # Errors
+/// Returns an error if no tool is configured, the snapshots cannot be read, or the
+/// tool cannot be started.
+pub fn difftool(repo_root: &Path, opts: DifftoolOptions<'_>) -> Result<()> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+
+    let config = Repository::discover(repo_root)
+        .and_then(|main| main.config())
+        .context("failed to open git config")?;
+    let tool = match opts.tool {
+        Some(tool) => tool.to_string(),
+        None => configured_tool(&config)?,
+    };
+    let command = tool_command(&config, &tool);
+
+    let old_tree = resolve_commit(&repo, opts.commit1)?.tree()?;
+    let new_tree = resolve_commit(&repo, opts.commit2)?.tree()?;
+    let mut diff_opts = git2::DiffOptions::new();
+    for path in opts.paths {
+        diff_opts.pathspec(path);
+    }
+    let mut diff =
+        repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut diff_opts))?;
+    detect_renames(&mut diff, opts.renames)?;
+
+    // (old path, new path) per changed file; `None` where the file does not exist.
+    let changes: Vec<(Option<PathBuf>, Option<PathBuf>)> = diff
+        .deltas()
+        .map(|delta| {
+            let old = (delta.status() != Delta::Added)
+                .then(|| delta.old_file().path().map(Path::to_path_buf))
+                .flatten();
+            let new = (delta.status() != Delta::Deleted)
+                .then(|| delta.new_file().path().map(Path::to_path_buf))
+                .flatten();
+            (old, new)
+        })
+        .collect();
+    if changes.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("git-autosnap-difftool.")
+        .tempdir()
+        .context("failed to create temporary directory")?;
+    let left = temp_dir.path().join("left");
+    let right = temp_dir.path().join("right");
+    let old_paths: HashSet<PathBuf> = changes.iter().filter_map(|(o, _)| o.clone()).collect();
+    let new_paths: HashSet<PathBuf> = changes.iter().filter_map(|(_, n)| n.clone()).collect();
+    for (dir, tree, paths) in [
+        (&left, &old_tree, &old_paths),
+        (&right, &new_tree, &new_paths),
+    ] {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        report_extract_issues(&extract_paths_to_path(&repo, tree, dir, paths)?);
+    }
+
+    if opts.dir_diff {
+        return run_tool(&command, &left, &right, Path::new(""), repo_root);
+    }
+    let null = Path::new("/dev/null");
+    for (old, new) in &changes {
+        let local = old
+            .as_ref()
+            .map_or_else(|| null.to_path_buf(), |p| left.join(p));
+        let remote = new
+            .as_ref()
+            .map_or_else(|| null.to_path_buf(), |p| right.join(p));
+        let merged = new.as_ref().or(old.as_ref()).map_or(null, PathBuf::as_path);
+        run_tool(&command, &local, &remote, merged, repo_root)?;
+    }
+    Ok(())
+}
+
+fn configured_tool(config: &Config) -> Result<String> {
+    ["autosnap.difftool", "diff.tool"]
+        .iter()
+        .find_map(|key| config.get_string(key).ok())
+        .filter(|tool| !tool.trim().is_empty())
+        .context("no diff tool configured; set autosnap.difftool or diff.tool, or pass --tool")
+}
+
+// `difftool.<tool>.cmd` wins; otherwise a built-in command line, with
+// `difftool.<tool>.path` replacing the executable.
+fn tool_command(config: &Config, tool: &str) -> String {
+    if let Ok(cmd) = config.get_string(&format!("difftool.{tool}.cmd")) {
+        return cmd;
+    }
+    let (program, args) = match tool {
+        "vimdiff" => ("vim", "-R -f -d"),
+        "gvimdiff" => ("gvim", "-R -f -d"),
+        "nvimdiff" => ("nvim", "-R -d"),
+        "vscode" => ("code", "--wait --diff"),
+        "difftastic" => ("difft", ""),
+        "bc" | "bc3" | "bc4" => ("bcompare", ""),
+        "kdiff3" => ("kdiff3", "--L1 \"$MERGED (A)\" --L2 \"$MERGED (B)\""),
+        other => (other, ""),
+    };
+    let program = config
+        .get_string(&format!("difftool.{tool}.path"))
+        .unwrap_or_else(|_| program.to_string());
+    let args = if args.is_empty() {
+        String::new()
+    } else {
+        format!(" {args}")
+    };
+    format!(
+        "'{}'{args} \"$LOCAL\" \"$REMOTE\"",
+        program.replace('\'', r"'\''")
+    )
+}
+
+fn run_tool(
+    command: &str,
+    local: &Path,
+    remote: &Path,
+    merged: &Path,
+    repo_root: &Path,
+) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("LOCAL", local)
+        .env("REMOTE", remote)
+        .env("MERGED", merged)
+        .env("BASE", merged)
+        .current_dir(repo_root)
+        .status()
+        .with_context(|| format!("failed to run difftool: {command}"))?;
+    // Like git difftool, a tool reporting differences is not an error.
+    if status.code().is_none() {
+        bail!("difftool was terminated by a signal: {command}");
+    }
+    Ok(())
+}
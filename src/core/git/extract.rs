@@ -0,0 +1,299 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fmt, fs,
+    hash::BuildHasher,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Result;
+use git2::{ObjectType, Oid, Repository, Tree, TreeEntry, TreeWalkMode, TreeWalkResult};
+
+const MODE_TYPE_MASK: i32 = 0o170_000;
+const MODE_REGULAR: i32 = 0o100_000;
+const MODE_SYMLINK: i32 = 0o120_000;
+const MODE_GITLINK: i32 = 0o160_000;
+
+/// Why a tree entry was not materialized as-is during extraction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Symlink whose target would resolve outside the extraction root.
+    SymlinkEscapesRoot { target: PathBuf },
+    /// Submodule (gitlink) entry; an empty directory is created in its place.
+    Submodule { commit: Oid },
+    /// Entry name that cannot be safely mapped to a path (e.g. `..`).
+    InvalidPath,
+    /// Entry with a file mode extraction does not know how to recreate.
+    UnsupportedMode(i32),
+    /// Filesystem or object database failure.
+    Failed(String),
+}
+
+/// A tree entry that was skipped or only partially extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// Path relative to the extraction root.
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match &self.reason {
+            SkipReason::SymlinkEscapesRoot { target } => write!(
+                f,
+                "{path}: symlink to {} escapes the snapshot root",
+                target.display()
+            ),
+            SkipReason::Submodule { commit } => {
+                write!(f, "{path}: submodule at {commit} (left empty)")
+            }
+            SkipReason::InvalidPath => write!(f, "{path}: invalid path in tree"),
+            SkipReason::UnsupportedMode(mode) => {
+                write!(f, "{path}: unsupported file mode {mode:o}")
+            }
+            SkipReason::Failed(msg) => write!(f, "{path}: {msg}"),
+        }
+    }
+}
+
+/// Outcome of extracting a tree to the filesystem.
+#[derive(Debug, Default, Clone)]
+pub struct ExtractReport {
+    pub files: usize,
+    pub symlinks: usize,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl ExtractReport {
+    /// Submodules are expected to be absent; anything else is a real gap.
+    #[must_use]
+    pub fn problems(&self) -> Vec<&SkippedEntry> {
+        self.skipped
+            .iter()
+            .filter(|s| !matches!(s.reason, SkipReason::Submodule { .. }))
+            .collect()
+    }
+}
+
+/// Extract a git tree to a filesystem path, recreating symlinks and file modes.
+///
+/// Entries that cannot be recreated faithfully are collected in the returned report
+/// instead of aborting the extraction.
+///
+/// # Errors
+/// Returns an error only if the tree walk itself fails.
+pub fn extract_tree_to_path(
+    repo: &Repository,
+    tree: &Tree,
+    base_path: &Path,
+) -> Result<ExtractReport> {
+    extract_filtered(repo, tree, base_path, None)
+}
+
+/// Extract only the listed files of a tree, creating just the directories they need.
+///
+/// # Errors
+/// Returns an error only if the tree walk itself fails.
+pub fn extract_paths_to_path<S: BuildHasher>(
+    repo: &Repository,
+    tree: &Tree,
+    base_path: &Path,
+    paths: &HashSet<PathBuf, S>,
+) -> Result<ExtractReport> {
+    extract_filtered(repo, tree, base_path, Some(&|path| paths.contains(path)))
+}
+
+fn extract_filtered(
+    repo: &Repository,
+    tree: &Tree,
+    base_path: &Path,
+    only: Option<&dyn Fn(&Path) -> bool>,
+) -> Result<ExtractReport> {
+    let mut report = ExtractReport::default();
+    let links = symlink_paths(tree)?;
+
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let Some(entry_name) = entry.name().filter(|n| is_safe_name(n)) else {
+            report.skipped.push(SkippedEntry {
+                path: Path::new(root).join(String::from_utf8_lossy(entry.name_bytes()).as_ref()),
+                reason: SkipReason::InvalidPath,
+            });
+            return TreeWalkResult::Skip;
+        };
+
+        let entry_path = Path::new(root).join(entry_name);
+        let full_path = base_path.join(&entry_path);
+
+        let is_tree = entry.kind() == Some(ObjectType::Tree);
+        if !is_tree && only.is_some_and(|wanted| !wanted(&entry_path)) {
+            return TreeWalkResult::Ok;
+        }
+
+        let outcome = match entry.kind() {
+            // Filtered extraction creates directories only as files need them.
+            Some(ObjectType::Tree) if only.is_some() => Ok(()),
+            Some(ObjectType::Tree) => fs::create_dir_all(&full_path)
+                .map_err(|e| SkipReason::Failed(format!("failed to create directory: {e}"))),
+            Some(ObjectType::Blob) => {
+                write_blob_entry(repo, entry, &entry_path, &full_path, &links, &mut report)
+            }
+            Some(ObjectType::Commit) if entry.filemode() == MODE_GITLINK => {
+                match fs::create_dir_all(&full_path) {
+                    Ok(()) => Err(SkipReason::Submodule { commit: entry.id() }),
+                    Err(e) => Err(SkipReason::Failed(format!(
+                        "failed to create directory: {e}"
+                    ))),
+                }
+            }
+            _ => Err(SkipReason::UnsupportedMode(entry.filemode())),
+        };
+
+        if let Err(reason) = outcome {
+            report.skipped.push(SkippedEntry {
+                path: entry_path,
+                reason,
+            });
+        }
+
+        TreeWalkResult::Ok
+    })?;
+
+    Ok(report)
+}
+
+fn write_blob_entry(
+    repo: &Repository,
+    entry: &TreeEntry<'_>,
+    entry_path: &Path,
+    full_path: &Path,
+    links: &HashSet<PathBuf>,
+    report: &mut ExtractReport,
+) -> std::result::Result<(), SkipReason> {
+    let object = entry
+        .to_object(repo)
+        .map_err(|e| SkipReason::Failed(format!("failed to read object: {e}")))?;
+    let blob = object
+        .as_blob()
+        .ok_or_else(|| SkipReason::Failed("object is not a blob".to_string()))?;
+
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| SkipReason::Failed(format!("failed to create parent directory: {e}")))?;
+    }
+
+    let mode = entry.filemode();
+    match mode & MODE_TYPE_MASK {
+        MODE_SYMLINK => {
+            let target = PathBuf::from(OsStr::from_bytes(blob.content()));
+            if !symlink_stays_inside(entry_path, &target, links) {
+                return Err(SkipReason::SymlinkEscapesRoot { target });
+            }
+            std::os::unix::fs::symlink(&target, full_path)
+                .map_err(|e| SkipReason::Failed(format!("failed to create symlink: {e}")))?;
+            report.symlinks += 1;
+        }
+        MODE_REGULAR => {
+            fs::write(full_path, blob.content())
+                .map_err(|e| SkipReason::Failed(format!("failed to write file: {e}")))?;
+            // Git records 0644 or 0755 (legacy trees may carry other permission bits).
+            let permissions = fs::Permissions::from_mode(mode.cast_unsigned() & 0o777);
+            fs::set_permissions(full_path, permissions)
+                .map_err(|e| SkipReason::Failed(format!("failed to set permissions: {e}")))?;
+            report.files += 1;
+        }
+        _ => return Err(SkipReason::UnsupportedMode(mode)),
+    }
+
+    Ok(())
+}
+
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+// Paths of the symlinks in `tree`, relative to its root.
+fn symlink_paths(tree: &Tree) -> Result<HashSet<PathBuf>> {
+    let mut links = HashSet::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.filemode() & MODE_TYPE_MASK == MODE_SYMLINK
+            && let Some(name) = entry.name()
+        {
+            links.insert(Path::new(root).join(name));
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(links)
+}
+
+// Lexically resolve `target` relative to the symlink's directory and make sure the
+// result never climbs above the extraction root. Lexical resolution is only sound
+// through real directories, so a target that walks through another of the tree's
+// `links` (`a -> b/../x` with `b -> ..`) is refused outright.
+fn symlink_stays_inside(link_path: &Path, target: &Path, links: &HashSet<PathBuf>) -> bool {
+    if target.is_absolute() {
+        return false;
+    }
+
+    let mut depth: Vec<&OsStr> = link_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+
+    for component in target.components() {
+        if depth.last().is_some() && links.contains(&depth.iter().collect::<PathBuf>()) {
+            return false;
+        }
+        match component {
+            Component::Normal(part) => depth.push(part),
+            Component::ParentDir => {
+                if depth.pop().is_none() {
+                    return false;
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symlink_escape_detection() {
+        let inside = |link: &str, target: &str| {
+            symlink_stays_inside(Path::new(link), Path::new(target), &HashSet::new())
+        };
+        assert!(inside("a/link", "b"));
+        assert!(inside("a/link", "../b"));
+        assert!(inside("a/b/link", "./../../c"));
+        assert!(!inside("link", "../outside"));
+        assert!(!inside("a/link", "../../x"));
+        assert!(!inside("a/link", "/etc/passwd"));
+    }
+
+    #[test]
+    fn symlink_through_another_link_is_refused() {
+        let links = HashSet::from([PathBuf::from("sub/b"), PathBuf::from("sub/a")]);
+        let inside = |link: &str, target: &str| {
+            symlink_stays_inside(Path::new(link), Path::new(target), &links)
+        };
+        assert!(inside("sub/b", ".."));
+        assert!(!inside("sub/a", "b/../x"));
+        assert!(!inside("sub/a", "b/y"));
+        // Naming another link as the final component is fine: it is checked itself.
+        assert!(inside("sub/a", "b"));
+        assert!(inside("top", "sub/c"));
+    }
+}
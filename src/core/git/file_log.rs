@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use git2::{Delta, Repository, Tree};
+
+use super::{
+    diff::{RenameDetection, Similarities, detect_renames, is_rename_or_copy, name_status_line},
+    repo::autosnap_dir,
+    revision::{short_summary, snapshot_chain},
+};
+
+/// Print the snapshots that changed `path`, newest first, following renames and
+/// copies back through the chain like `git log --follow --name-status`.
+///
+/// # Errors
+/// Returns an error if the autosnap repository cannot be read.
+pub fn file_log(
+    repo_root: &Path,
+    path: &str,
+    renames: RenameDetection,
+    limit: Option<usize>,
+) -> Result<()> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+
+    let chain = snapshot_chain(&repo)?;
+    let mut current = path.trim_end_matches('/').to_string();
+    let mut shown = 0;
+
+    for (pos, oid) in chain.iter().enumerate().rev() {
+        if limit.is_some_and(|max| shown >= max) {
+            break;
+        }
+        let commit = repo.find_commit(*oid)?;
+        let tree = commit.tree()?;
+        let parent_tree = match pos.checked_sub(1) {
+            Some(prev) => Some(repo.find_commit(chain[prev])?.tree()?),
+            None => None,
+        };
+
+        // Cheap check first: only run a full diff with rename detection when the
+        // followed path actually differs between the two snapshots.
+        if entry_id(parent_tree.as_ref(), &current) == entry_id(Some(&tree), &current) {
+            continue;
+        }
+
+        let mut diff = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .context("failed to diff snapshot against its parent")?;
+        detect_renames(&mut diff, renames)?;
+
+        let found = diff.deltas().find(|delta| {
+            let file = if delta.status() == Delta::Deleted {
+                delta.old_file()
+            } else {
+                delta.new_file()
+            };
+            file.path().and_then(|p| p.to_str()) == Some(current.as_str())
+        });
+        let Some(delta) = found else {
+            continue;
+        };
+
+        let (short_id, subject) = short_summary(&commit);
+        if shown > 0 {
+            println!();
+        }
+        println!("{short_id} {subject}");
+        println!("{}", name_status_line(&delta, &Similarities::of(&diff)?));
+        shown += 1;
+
+        if is_rename_or_copy(&delta)
+            && let Some(old) = delta.old_file().path().and_then(|p| p.to_str())
+        {
+            current = old.to_string();
+        }
+    }
+
+    if shown == 0 {
+        println!("No snapshots touch {path}");
+    }
+    Ok(())
+}
+
+fn entry_id(tree: Option<&Tree<'_>>, path: &str) -> Option<git2::Oid> {
+    tree?.get_path(Path::new(path)).ok().map(|entry| entry.id())
+}
@@ -0,0 +1,601 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{ObjectType, Oid, PackBuilderStage, Repository, Sort};
+
+/// How unreachable snapshot objects are removed after a history rewrite.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GcBackend {
+    /// Repack reachable objects with libgit2 and delete everything else.
+    #[default]
+    Native,
+    /// Shell out to `git reflog expire` and `git gc --prune`.
+    Git,
+}
+
+impl FromStr for GcBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "native" => Ok(Self::Native),
+            "git" => Ok(Self::Git),
+            other => bail!("unknown gc backend `{other}` (expected native or git)"),
+        }
+    }
+}
+
+impl fmt::Display for GcBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Native => "native",
+            Self::Git => "git",
+        })
+    }
+}
+
+/// A step of a garbage collection, reported as it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcProgress {
+    /// Objects found reachable from the refs so far.
+    Counting(usize),
+    /// Reachable objects added to the new pack.
+    Packing { done: usize, total: usize },
+    /// Objects considered for delta compression.
+    Compressing { done: usize, total: usize },
+    /// Superseded packs and loose objects removed.
+    Pruning { done: usize, total: usize },
+}
+
+/// What a garbage collection did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Objects left in the store.
+    pub objects: usize,
+    /// Unreachable objects removed from the store.
+    pub pruned: usize,
+}
+
+/// Drop reflogs and every object neither HEAD nor a ref reaches, leaving the store
+/// in one pack.
+///
+/// Loose objects written less than `prune_grace` seconds ago are kept even when
+/// unreachable, as they may belong to a write still in progress; so are objects in
+/// packs marked with a `.keep` file. The native backend skips the repack when the
+/// store is already a single pack with nothing to prune.
+///
+/// # Errors
+/// Returns an error if the store cannot be read or rewritten, or `git` fails.
+pub fn collect_garbage(
+    repo: &Repository,
+    backend: GcBackend,
+    prune_grace: i64,
+    progress: &mut dyn FnMut(GcProgress),
+) -> Result<GcStats> {
+    match backend {
+        GcBackend::Native => native_gc(repo, prune_grace, progress),
+        GcBackend::Git => {
+            let before = stored_objects(repo)?.len();
+            git_gc(repo.path(), prune_grace)?;
+            let objects = stored_objects(repo)?.len();
+            Ok(GcStats {
+                objects,
+                pruned: before.saturating_sub(objects),
+            })
+        }
+    }
+}
+
+fn native_gc(
+    repo: &Repository,
+    prune_grace: i64,
+    progress: &mut dyn FnMut(GcProgress),
+) -> Result<GcStats> {
+    expire_reflogs(repo)?;
+    let reachable = reachable_objects(repo, progress)?;
+    let reachable_set: HashSet<Oid> = reachable.iter().copied().collect();
+    let stored = stored_objects(repo)?;
+
+    let objects_dir = repo.path().join("objects");
+    let pack_dir = objects_dir.join("pack");
+    let packs = pack_files(&pack_dir)?;
+    let mut spared = kept_pack_objects(&pack_dir)?;
+    // Fresh loose objects nothing reaches yet stay where they are.
+    let cutoff = SystemTime::now()
+        .checked_sub(Duration::from_secs(prune_grace.max(0).cast_unsigned()))
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut loose = Vec::new();
+    for (id, path) in loose_objects(&objects_dir)? {
+        if !reachable_set.contains(&id) && modified_after(&path, cutoff) {
+            spared.insert(id);
+        } else {
+            loose.push(path);
+        }
+    }
+    let prunable = stored
+        .iter()
+        .any(|id| !reachable_set.contains(id) && !spared.contains(id));
+    if !prunable && loose.is_empty() && packs.len() <= 1 {
+        return Ok(GcStats {
+            objects: stored.len(),
+            pruned: 0,
+        });
+    }
+
+    let new_pack = if reachable.is_empty() {
+        None
+    } else {
+        Some(write_pack(repo, &reachable, &pack_dir, progress)?)
+    };
+
+    // The new pack holds everything reachable: the old packs and loose objects go.
+    let stale: Vec<PathBuf> = packs
+        .iter()
+        .filter(|pack| new_pack.as_deref() != pack.file_stem().and_then(|stem| stem.to_str()))
+        .flat_map(|pack| {
+            ["pack", "idx", "rev", "bitmap", "mtimes"].map(|ext| pack.with_extension(ext))
+        })
+        .filter(|path| path.exists())
+        .chain(loose)
+        .collect();
+    let total = stale.len();
+    for (done, path) in stale.iter().enumerate() {
+        remove_file(path)?;
+        progress(GcProgress::Pruning {
+            done: done + 1,
+            total,
+        });
+    }
+    remove_empty_fanout_dirs(&objects_dir)?;
+    // Indexes `git` keeps over packs and commits; stale once packs are replaced.
+    let info_dir = objects_dir.join("info");
+    remove_file(&info_dir.join("packs"))?;
+    remove_file(&info_dir.join("commit-graph"))?;
+    remove_file(&pack_dir.join("multi-pack-index"))?;
+    let graphs = info_dir.join("commit-graphs");
+    if graphs.exists() {
+        fs::remove_dir_all(&graphs)
+            .with_context(|| format!("failed to remove {}", graphs.display()))?;
+    }
+
+    repo.odb()
+        .and_then(|odb| odb.refresh())
+        .context("failed to refresh object database")?;
+    let objects = stored_objects(repo)?.len();
+    Ok(GcStats {
+        objects,
+        pruned: stored.len().saturating_sub(objects),
+    })
+}
+
+fn expire_reflogs(repo: &Repository) -> Result<()> {
+    let mut names = vec!["HEAD".to_string()];
+    for reference in repo.references().context("failed to list refs")? {
+        let reference = reference.context("failed to read ref")?;
+        if let Some(name) = reference.name() {
+            names.push(name.to_string());
+        }
+    }
+    for name in names {
+        repo.reflog_delete(&name)
+            .with_context(|| format!("failed to expire reflog of {name}"))?;
+    }
+    Ok(())
+}
+
+/// Every object reachable from HEAD or a ref: snapshot chains, notes, and the staged
+/// trees refs point at directly.
+fn reachable_objects(repo: &Repository, progress: &mut dyn FnMut(GcProgress)) -> Result<Vec<Oid>> {
+    let mut seen = HashSet::new();
+    // In pack order: commits, then their trees and blobs.
+    let mut order = Vec::new();
+    let mut trees = Vec::new();
+    let mut walk = repo.revwalk().context("failed to start revwalk")?;
+    walk.set_sorting(Sort::TOPOLOGICAL)
+        .context("failed to sort revwalk")?;
+
+    // A detached HEAD is the only thing holding the chain it points at.
+    let mut roots: Vec<Oid> = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .into_iter()
+        .collect();
+    for reference in repo.references().context("failed to list refs")? {
+        let reference = reference.context("failed to read ref")?;
+        if let Some(target) = reference.resolve().ok().and_then(|r| r.target()) {
+            roots.push(target);
+        }
+    }
+    for mut target in roots {
+        loop {
+            let object = repo
+                .find_object(target, None)
+                .with_context(|| format!("failed to read {target}"))?;
+            match object.kind() {
+                Some(ObjectType::Commit) => walk.push(target)?,
+                Some(ObjectType::Tree) => trees.push(target),
+                Some(ObjectType::Tag) => {
+                    if seen.insert(target) {
+                        order.push(target);
+                    }
+                    target = object
+                        .as_tag()
+                        .map(git2::Tag::target_id)
+                        .context("failed to read tag")?;
+                    continue;
+                }
+                _ => {
+                    if seen.insert(target) {
+                        order.push(target);
+                    }
+                }
+            }
+            break;
+        }
+    }
+
+    for commit in walk {
+        let commit = commit.context("failed to walk snapshot history")?;
+        if seen.insert(commit) {
+            order.push(commit);
+            trees.push(repo.find_commit(commit)?.tree_id());
+        }
+        if order.len() % 1024 == 0 {
+            progress(GcProgress::Counting(order.len()));
+        }
+    }
+    while let Some(tree_id) = trees.pop() {
+        if !seen.insert(tree_id) {
+            continue;
+        }
+        order.push(tree_id);
+        let tree = repo
+            .find_tree(tree_id)
+            .with_context(|| format!("failed to read tree {tree_id}"))?;
+        for entry in &tree {
+            match entry.kind() {
+                Some(ObjectType::Tree) => trees.push(entry.id()),
+                Some(ObjectType::Blob) if seen.insert(entry.id()) => order.push(entry.id()),
+                // Submodule commits live in other repositories.
+                _ => {}
+            }
+        }
+        if order.len() % 1024 == 0 {
+            progress(GcProgress::Counting(order.len()));
+        }
+    }
+    progress(GcProgress::Counting(order.len()));
+    Ok(order)
+}
+
+/// Ids of every object in the store, loose or packed.
+fn stored_objects(repo: &Repository) -> Result<HashSet<Oid>> {
+    let odb = repo.odb().context("failed to open object database")?;
+    let mut ids = HashSet::new();
+    odb.foreach(|id| {
+        ids.insert(*id);
+        true
+    })
+    .context("failed to list stored objects")?;
+    Ok(ids)
+}
+
+/// Write `objects` into a new pack in `pack_dir`; returns its file stem.
+fn write_pack(
+    repo: &Repository,
+    objects: &[Oid],
+    pack_dir: &Path,
+    progress: &mut dyn FnMut(GcProgress),
+) -> Result<String> {
+    let mut builder = repo.packbuilder().context("failed to start pack")?;
+    builder.set_threads(0);
+    let total = objects.len();
+    for (done, id) in objects.iter().enumerate() {
+        builder
+            .insert_object(*id, None)
+            .with_context(|| format!("failed to pack {id}"))?;
+        if (done + 1) % 1024 == 0 || done + 1 == total {
+            progress(GcProgress::Packing {
+                done: done + 1,
+                total,
+            });
+        }
+    }
+    builder
+        .set_progress_callback(|stage, done, total| {
+            // Adding objects was reported above, with a known total.
+            if stage == PackBuilderStage::Deltafication {
+                progress(GcProgress::Compressing {
+                    done: done as usize,
+                    total: total as usize,
+                });
+            }
+            true
+        })
+        .context("failed to watch pack progress")?;
+    builder
+        .write(pack_dir, 0)
+        .with_context(|| format!("failed to write pack to {}", pack_dir.display()))?;
+    builder
+        .name()
+        .map(|name| format!("pack-{name}"))
+        .context("written pack has no name")
+}
+
+/// `.pack` files in `pack_dir` not protected by a `.keep` file.
+fn pack_files(pack_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut packs = Vec::new();
+    let entries = match fs::read_dir(pack_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(packs),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", pack_dir.display()));
+        }
+    };
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read {}", pack_dir.display()))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "pack")
+            && !path.with_extension("keep").exists()
+        {
+            packs.push(path);
+        }
+    }
+    Ok(packs)
+}
+
+/// Objects listed by the indexes of packs protected by a `.keep` file.
+fn kept_pack_objects(pack_dir: &Path) -> Result<HashSet<Oid>> {
+    let mut ids = HashSet::new();
+    let Ok(entries) = fs::read_dir(pack_dir) else {
+        return Ok(ids);
+    };
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read {}", pack_dir.display()))?
+            .path();
+        if path.extension().is_some_and(|ext| ext == "keep") {
+            ids.extend(pack_index_ids(&path.with_extension("idx"))?);
+        }
+    }
+    Ok(ids)
+}
+
+/// Object ids listed in a version 2 pack index.
+fn pack_index_ids(idx: &Path) -> Result<Vec<Oid>> {
+    const HEADER: [u8; 8] = [0xff, b't', b'O', b'c', 0, 0, 0, 2];
+    const FANOUT_END: usize = HEADER.len() + 256 * 4;
+
+    let data = fs::read(idx).with_context(|| format!("failed to read {}", idx.display()))?;
+    if data.len() < FANOUT_END || data[..HEADER.len()] != HEADER {
+        bail!("unsupported pack index {}", idx.display());
+    }
+    let mut count = [0; 4];
+    count.copy_from_slice(&data[FANOUT_END - 4..FANOUT_END]);
+    let count = u32::from_be_bytes(count) as usize;
+    data.get(FANOUT_END..FANOUT_END + count * 20)
+        .with_context(|| format!("truncated pack index {}", idx.display()))?
+        .chunks_exact(20)
+        .map(|id| Oid::from_bytes(id).context("invalid object id in pack index"))
+        .collect()
+}
+
+fn modified_after(path: &Path, cutoff: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| modified > cutoff)
+}
+
+/// Loose objects, with their files: `objects/xx/<38 hex digits>`.
+fn loose_objects(objects_dir: &Path) -> Result<Vec<(Oid, PathBuf)>> {
+    let mut loose = Vec::new();
+    for dir in fanout_dirs(objects_dir)? {
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let path = entry
+                .with_context(|| format!("failed to read {}", dir.display()))?
+                .path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .filter(|name| name.len() == 38 && is_hex(name))
+                .zip(dir.file_name().and_then(|name| name.to_str()))
+                .and_then(|(name, fanout)| Oid::from_str(&format!("{fanout}{name}")).ok());
+            if let Some(id) = id {
+                loose.push((id, path));
+            }
+        }
+    }
+    Ok(loose)
+}
+
+fn fanout_dirs(objects_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(objects_dir)
+        .with_context(|| format!("failed to read {}", objects_dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("failed to read {}", objects_dir.display()))?
+            .path();
+        if path.is_dir()
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.len() == 2 && is_hex(name))
+        {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+fn remove_empty_fanout_dirs(objects_dir: &Path) -> Result<()> {
+    for dir in fanout_dirs(objects_dir)? {
+        // Fails harmlessly when something was written there meanwhile.
+        let _ = fs::remove_dir(dir);
+    }
+    Ok(())
+}
+
+fn is_hex(name: &str) -> bool {
+    name.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn git_gc(gitdir: &Path, prune_grace: i64) -> Result<()> {
+    let gitdir = gitdir.to_string_lossy().to_string();
+    let prune = if prune_grace > 0 {
+        format!("--prune={prune_grace}.seconds.ago")
+    } else {
+        "--prune=now".to_string()
+    };
+
+    let status = Command::new("git")
+        .args([
+            format!("--git-dir={gitdir}").as_str(),
+            "reflog",
+            "expire",
+            "--expire=now",
+            "--all",
+        ])
+        .status()
+        .context("failed to run git reflog expire --expire=now --all")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "git reflog expire --expire=now --all exited with status {status}"
+        ));
+    }
+
+    let status = Command::new("git")
+        .args([format!("--git-dir={gitdir}").as_str(), "gc", &prune])
+        .status()
+        .with_context(|| format!("failed to run git gc {prune}"))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "git gc {prune} exited with status {status}"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &Repository, update_ref: Option<&str>, content: &str) -> Oid {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("state.txt", blob, 0o100_644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let sig = git2::Signature::now("t", "t@example.com").unwrap();
+        repo.commit(update_ref, &sig, &sig, content, &tree, &[])
+            .unwrap()
+    }
+
+    #[test]
+    fn native_gc_keeps_reachable_objects_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let kept = commit(&repo, Some("HEAD"), "kept");
+        let dropped = commit(&repo, None, "dropped");
+        // A ref straight at a tree, like staged trees.
+        let staged = repo.blob(b"staged").unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("index.txt", staged, 0o100_644).unwrap();
+        let staged_tree = builder.write().unwrap();
+        repo.reference("refs/autosnap/staged/x", staged_tree, false, "test")
+            .unwrap();
+
+        let mut steps = Vec::new();
+        let stats =
+            collect_garbage(&repo, GcBackend::Native, 0, &mut |step| steps.push(step)).unwrap();
+        // kept commit, tree, blob; staged tree and blob.
+        assert_eq!(
+            stats,
+            GcStats {
+                objects: 5,
+                pruned: 3
+            }
+        );
+        assert!(steps.contains(&GcProgress::Counting(5)));
+        assert!(repo.find_commit(kept).is_ok());
+        assert!(repo.find_blob(staged).is_ok());
+        assert!(repo.find_commit(dropped).is_err());
+        assert!(
+            loose_objects(&dir.path().join("objects"))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            pack_files(&dir.path().join("objects/pack")).unwrap().len(),
+            1
+        );
+
+        // A clean store is left alone.
+        let again = collect_garbage(&repo, GcBackend::Native, 0, &mut |_| {}).unwrap();
+        assert_eq!(again.pruned, 0);
+    }
+
+    #[test]
+    fn native_gc_spares_detached_head_kept_packs_and_fresh_objects() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let head = commit(&repo, None, "head");
+        repo.set_head_detached(head).unwrap();
+        let dropped = commit(&repo, None, "dropped");
+        // An unreachable blob in a pack marked `.keep`.
+        let pack_dir = dir.path().join("objects/pack");
+        let kept = repo.blob(b"kept").unwrap();
+        let mut builder = repo.packbuilder().unwrap();
+        builder.insert_object(kept, None).unwrap();
+        builder.write(&pack_dir, 0).unwrap();
+        let keep = pack_dir.join(format!("pack-{}.keep", builder.name().unwrap()));
+        fs::write(keep, "").unwrap();
+
+        // Everything unreachable is still fresh.
+        let stats = collect_garbage(&repo, GcBackend::Native, 3600, &mut |_| {}).unwrap();
+        assert_eq!(stats.pruned, 0);
+        assert!(repo.find_commit(dropped).is_ok());
+
+        let stats = collect_garbage(&repo, GcBackend::Native, 0, &mut |_| {}).unwrap();
+        // Only the dropped commit, tree and blob left the store.
+        assert_eq!(
+            stats,
+            GcStats {
+                objects: 4,
+                pruned: 3
+            }
+        );
+        // Reopened: the first gc looked `dropped` up, and libgit2 caches objects.
+        let repo = Repository::open_bare(dir.path()).unwrap();
+        assert!(repo.find_commit(head).is_ok());
+        assert!(repo.find_blob(kept).is_ok());
+        assert!(repo.find_commit(dropped).is_err());
+    }
+
+    #[test]
+    fn parses_backends() {
+        assert_eq!("native".parse::<GcBackend>().unwrap(), GcBackend::Native);
+        assert_eq!(" git ".parse::<GcBackend>().unwrap(), GcBackend::Git);
+        assert!("jgit".parse::<GcBackend>().is_err());
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{ObjectType, Oid, Repository, Signature, Tree, TreeWalkMode, TreeWalkResult};
+
+use super::notes::squash_notes;
+
+/// Notes ref holding per-file metadata for each snapshot commit.
+pub const METADATA_NOTES_REF: &str = "refs/notes/autosnap-metadata";
+
+const HEADER: &str = "autosnap-metadata v1";
+
+/// Filesystem metadata Git trees do not carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub mtime_secs: i64,
+    pub mtime_nanos: u32,
+    /// Full permission bits (including setuid/setgid/sticky), when recorded.
+    pub mode: Option<u32>,
+}
+
+/// Per-file metadata recorded alongside a snapshot, keyed by repo-relative path.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SnapshotMetadata {
+    pub files: BTreeMap<String, FileMetadata>,
+}
+
+impl SnapshotMetadata {
+    /// Collect metadata from `workdir` for every regular file in `tree`.
+    ///
+    /// # Errors
+    /// Returns an error if the tree walk fails.
+    pub fn collect(tree: &Tree<'_>, workdir: &Path, include_mode: bool) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() != Some(ObjectType::Blob) || entry.filemode() & 0o170_000 != 0o100_000 {
+                return TreeWalkResult::Ok;
+            }
+            let Some(name) = entry.name() else {
+                return TreeWalkResult::Ok;
+            };
+            let rel = format!("{root}{name}");
+            if rel.contains('\n') {
+                return TreeWalkResult::Ok;
+            }
+            if let Ok(meta) = fs::symlink_metadata(workdir.join(&rel))
+                && meta.is_file()
+            {
+                files.insert(
+                    rel,
+                    FileMetadata {
+                        mtime_secs: meta.mtime(),
+                        mtime_nanos: u32::try_from(meta.mtime_nsec()).unwrap_or(0),
+                        mode: include_mode.then(|| meta.mode() & 0o7777),
+                    },
+                );
+            }
+            TreeWalkResult::Ok
+        })?;
+        Ok(Self { files })
+    }
+
+    fn serialize(&self) -> String {
+        let mut out = String::from(HEADER);
+        out.push('\n');
+        for (path, meta) in &self.files {
+            let mode = meta
+                .mode
+                .map_or_else(|| "-".to_string(), |m| format!("{m:o}"));
+            let _ = writeln!(
+                out,
+                "{}.{:09} {mode} {path}",
+                meta.mtime_secs, meta.mtime_nanos
+            );
+        }
+        out
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            bail!("unrecognized snapshot metadata format");
+        }
+
+        let mut files = BTreeMap::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let mut parts = line.splitn(3, ' ');
+            let (Some(mtime), Some(mode), Some(path)) = (parts.next(), parts.next(), parts.next())
+            else {
+                bail!("malformed snapshot metadata line: {line}");
+            };
+            let (secs, nanos) = mtime.split_once('.').unwrap_or((mtime, "0"));
+            let mode = match mode {
+                "-" => None,
+                m => Some(u32::from_str_radix(m, 8).context("invalid mode in metadata")?),
+            };
+            files.insert(
+                path.to_string(),
+                FileMetadata {
+                    mtime_secs: secs.parse().context("invalid mtime in metadata")?,
+                    mtime_nanos: nanos.parse().context("invalid mtime in metadata")?,
+                    mode,
+                },
+            );
+        }
+        Ok(Self { files })
+    }
+
+    /// Reapply recorded mtimes (and modes, when recorded) to files under `base`.
+    ///
+    /// Only paths accepted by `filter` are touched; symlinks and missing files are
+    /// ignored. Returns the number of files updated.
+    pub fn apply(&self, base: &Path, mut filter: impl FnMut(&str) -> bool) -> usize {
+        let mut applied = 0;
+        for (path, meta) in &self.files {
+            if !filter(path) {
+                continue;
+            }
+            let full = base.join(path);
+            if !fs::symlink_metadata(&full).is_ok_and(|m| m.is_file()) {
+                continue;
+            }
+            if let Some(mode) = meta.mode {
+                let _ = fs::set_permissions(&full, fs::Permissions::from_mode(mode));
+            }
+            let Ok(secs) = u64::try_from(meta.mtime_secs) else {
+                continue;
+            };
+            let mtime = SystemTime::UNIX_EPOCH
+                + Duration::from_secs(secs)
+                + Duration::from_nanos(u64::from(meta.mtime_nanos));
+            if fs::File::open(&full)
+                .and_then(|f| f.set_modified(mtime))
+                .is_ok()
+            {
+                applied += 1;
+            }
+        }
+        applied
+    }
+}
+
+/// Attach `metadata` to `commit` as a note.
+///
+/// # Errors
+/// Returns an error if the note cannot be written.
+pub fn write_metadata(
+    repo: &Repository,
+    sig: &Signature<'_>,
+    commit: Oid,
+    metadata: &SnapshotMetadata,
+) -> Result<()> {
+    repo.note(
+        sig,
+        sig,
+        Some(METADATA_NOTES_REF),
+        commit,
+        &metadata.serialize(),
+        true,
+    )
+    .with_context(|| format!("failed to write metadata note for {commit}"))?;
+    Ok(())
+}
+
+/// Load the metadata recorded for `commit`, if any.
+///
+/// # Errors
+/// Returns an error if a note exists but cannot be parsed.
+pub fn read_metadata(repo: &Repository, commit: Oid) -> Result<Option<SnapshotMetadata>> {
+    let Ok(note) = repo.find_note(Some(METADATA_NOTES_REF), commit) else {
+        return Ok(None);
+    };
+    let text = note.message().context("metadata note is not valid UTF-8")?;
+    SnapshotMetadata::parse(text).map(Some)
+}
+
+/// Move the metadata note from `from` to `to` (used when history is rewritten).
+///
+/// # Errors
+/// Returns an error if the note cannot be rewritten.
+pub fn move_metadata(repo: &Repository, sig: &Signature<'_>, from: Oid, to: Oid) -> Result<()> {
+    let Some(metadata) = read_metadata(repo, from)? else {
+        return Ok(());
+    };
+    write_metadata(repo, sig, to, &metadata)?;
+    if from != to {
+        repo.note_delete(from, Some(METADATA_NOTES_REF), sig, sig)
+            .with_context(|| format!("failed to remove metadata note for {from}"))?;
+    }
+    Ok(())
+}
+
+/// Drop the metadata of every snapshot `keep` rejects and squash the notes history,
+/// so garbage collection can reclaim what compact folded. Returns how many notes
+/// were dropped.
+///
+/// # Errors
+/// Returns an error if the notes cannot be read or rewritten.
+pub fn prune_metadata(
+    repo: &Repository,
+    sig: &Signature<'_>,
+    keep: impl FnMut(Oid) -> bool,
+) -> Result<usize> {
+    squash_notes(repo, METADATA_NOTES_REF, sig, keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_round_trips() {
+        let mut files = BTreeMap::new();
+        files.insert(
+            "dir/with space.txt".to_string(),
+            FileMetadata {
+                mtime_secs: 1_700_000_000,
+                mtime_nanos: 42,
+                mode: Some(0o4755),
+            },
+        );
+        files.insert(
+            "plain".to_string(),
+            FileMetadata {
+                mtime_secs: 5,
+                mtime_nanos: 0,
+                mode: None,
+            },
+        );
+        let metadata = SnapshotMetadata { files };
+        let parsed = SnapshotMetadata::parse(&metadata.serialize()).expect("parse");
+        assert_eq!(parsed, metadata);
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        assert!(SnapshotMetadata::parse("something else\n").is_err());
+    }
+}
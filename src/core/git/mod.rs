@@ -1,15 +1,53 @@
+pub mod backup;
+pub mod bisect;
+pub mod browse;
+pub mod budget;
+pub mod cat;
+pub mod changed_since;
 pub mod compact;
 pub mod diff;
+pub mod difftool;
+pub mod extract;
+pub mod file_log;
+pub mod gc;
 pub mod index;
+pub mod metadata;
+pub mod notes;
 mod ops_lock;
+pub mod pager;
+pub mod picker;
+pub mod pin;
 pub mod repo;
+pub mod report;
 pub mod restore;
+pub mod restore_plan;
+pub mod retention;
+pub mod revision;
+pub mod rewrite_map;
 pub mod shell;
 pub mod snapshot;
+pub mod staged;
 
-pub use compact::{CompactResult, compact};
-pub use diff::{DiffFormat, diff};
+pub use bisect::{BisectVerdict, bisect_mark, bisect_reset, bisect_run, bisect_start};
+pub use browse::browse;
+pub use cat::cat;
+pub use changed_since::changed_since;
+pub use compact::{
+    CompactOptions, CompactPreview, CompactResult, PreviewEntry, RollbackResult, SnapshotFate,
+    compact, compact_preview, compact_to_budget, rollback,
+};
+pub use diff::{
+    DiffFilter, DiffFormat, DiffRange, DiffSettings, DirstatOptions, RenameDetection, Whitespace,
+    WordDiff, diff, parse_similarity,
+};
+pub use difftool::{DifftoolOptions, difftool};
+pub use file_log::file_log;
+pub use gc::{GcBackend, GcProgress, GcStats, collect_garbage};
+pub use pager::{Pager, start_pager};
 pub use repo::{autosnap_dir, init_autosnap, repo_root};
-pub use restore::restore;
+pub use report::{Report, ReportFormat, activity_report};
+pub use restore::{IndexMode, RestoreOptions, restore};
+pub use restore_plan::{PlanAction, RestorePlan};
+pub use retention::RetentionPolicy;
 pub use shell::snapshot_shell;
-pub use snapshot::snapshot_once;
+pub use snapshot::{SnapshotTrigger, snapshot_once};
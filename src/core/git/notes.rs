@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use git2::{Oid, Repository, Signature};
+
+/// The notes on `notes_ref` as (annotated object, note blob) pairs.
+///
+/// # Errors
+/// Returns an error if the notes cannot be read.
+pub fn list_notes(repo: &Repository, notes_ref: &str) -> Result<Vec<(Oid, Oid)>> {
+    let Ok(notes) = repo.notes(Some(notes_ref)) else {
+        return Ok(Vec::new());
+    };
+    let mut out = Vec::new();
+    for note in notes {
+        let (blob, annotated) = note.with_context(|| format!("failed to read {notes_ref}"))?;
+        out.push((annotated, blob));
+    }
+    Ok(out)
+}
+
+/// Point `notes_ref` at a single commit, without history, holding exactly `notes`
+/// (annotated object, note blob). Deletes the ref when `notes` is empty.
+///
+/// Every `repo.note` call adds a commit to the notes ref, and that history keeps every
+/// blob it ever held reachable; rewriting the ref this way lets gc reclaim them.
+///
+/// # Errors
+/// Returns an error if the tree, commit or ref cannot be written.
+pub fn write_notes(
+    repo: &Repository,
+    notes_ref: &str,
+    sig: &Signature<'_>,
+    notes: &[(Oid, Oid)],
+) -> Result<()> {
+    if notes.is_empty() {
+        if let Ok(mut reference) = repo.find_reference(notes_ref) {
+            reference
+                .delete()
+                .with_context(|| format!("failed to delete {notes_ref}"))?;
+        }
+        return Ok(());
+    }
+    // A flat tree of full hex ids is a valid notes tree; readers handle any fanout.
+    let mut builder = repo.treebuilder(None)?;
+    for (annotated, blob) in notes {
+        builder
+            .insert(annotated.to_string(), *blob, 0o100_644)
+            .with_context(|| format!("failed to add note for {annotated}"))?;
+    }
+    let tree = repo.find_tree(builder.write()?)?;
+    let commit = repo
+        .commit(
+            None,
+            sig,
+            sig,
+            "Notes rewritten by 'git autosnap'\n",
+            &tree,
+            &[],
+        )
+        .with_context(|| format!("failed to write {notes_ref}"))?;
+    repo.reference(notes_ref, commit, true, "autosnap: rewrite notes")
+        .with_context(|| format!("failed to update {notes_ref}"))?;
+    Ok(())
+}
+
+/// Rewrite `notes_ref` as a single commit keeping only the notes on objects `keep`
+/// accepts. Returns how many notes were dropped.
+///
+/// # Errors
+/// Returns an error if the notes cannot be read or rewritten.
+pub fn squash_notes(
+    repo: &Repository,
+    notes_ref: &str,
+    sig: &Signature<'_>,
+    mut keep: impl FnMut(Oid) -> bool,
+) -> Result<usize> {
+    let notes = list_notes(repo, notes_ref)?;
+    let total = notes.len();
+    let kept: Vec<_> = notes
+        .into_iter()
+        .filter(|&(annotated, _)| keep(annotated))
+        .collect();
+    write_notes(repo, notes_ref, sig, &kept)?;
+    Ok(total - kept.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squash_keeps_selected_notes_without_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = Signature::now("t", "t@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let [a, b] = ["a", "b"].map(|msg| repo.commit(None, &sig, &sig, msg, &tree, &[]).unwrap());
+        let notes_ref = "refs/notes/test";
+        for (id, text) in [(a, "one"), (b, "two"), (a, "three")] {
+            repo.note(&sig, &sig, Some(notes_ref), id, text, true)
+                .unwrap();
+        }
+
+        assert_eq!(
+            squash_notes(&repo, notes_ref, &sig, |id| id == a).unwrap(),
+            1
+        );
+        let head = repo
+            .find_reference(notes_ref)
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(head.parent_count(), 0);
+        let note = repo.find_note(Some(notes_ref), a).unwrap();
+        assert_eq!(note.message(), Some("three"));
+        assert!(repo.find_note(Some(notes_ref), b).is_err());
+
+        assert_eq!(squash_notes(&repo, notes_ref, &sig, |_| false).unwrap(), 1);
+        assert!(repo.find_reference(notes_ref).is_err());
+    }
+}
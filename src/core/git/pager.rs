@@ -0,0 +1,94 @@
+use std::{
+    env,
+    io::{self, Write},
+    os::fd::{AsFd, OwnedFd},
+    path::Path,
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{Context, Result};
+use console::Term;
+use git2::Repository;
+use nix::unistd::dup2_stdout;
+
+/// Standard output redirected into a pager process.
+///
+/// Dropping it restores stdout, which closes the pager's input, and waits for the user
+/// to quit the pager.
+pub struct Pager {
+    child: Child,
+    saved_stdout: OwnedFd,
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        let _ = dup2_stdout(&self.saved_stdout);
+        let _ = self.child.wait();
+    }
+}
+
+/// Send the rest of this process's stdout through the user's pager, like git does.
+///
+/// The pager is chosen from `GIT_PAGER`, `core.pager`, `PAGER`, then `less`. Nothing is
+/// started when stdout is not a terminal or the pager is `cat` or empty.
+///
+/// # Errors
+/// Returns an error if the pager cannot be started or stdout cannot be redirected.
+pub fn start_pager(repo_root: &Path) -> Result<Option<Pager>> {
+    if !Term::stdout().is_term() {
+        return Ok(None);
+    }
+    let Some(pager) = pager_command(repo_root) else {
+        return Ok(None);
+    };
+
+    // Colour is decided against the terminal, before stdout turns into a pipe.
+    console::set_colors_enabled(console::colors_enabled());
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    // Git's defaults: quit if one screen, keep colours, don't clear the screen.
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    if env::var_os("LV").is_none() {
+        command.env("LV", "-c");
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("failed to start pager: {pager}"))?;
+    let Some(input) = child.stdin.take() else {
+        return Ok(None);
+    };
+
+    io::stdout().flush().context("failed to flush stdout")?;
+    let saved_stdout = io::stdout()
+        .as_fd()
+        .try_clone_to_owned()
+        .context("failed to duplicate stdout")?;
+    dup2_stdout(&input).context("failed to redirect stdout to the pager")?;
+
+    Ok(Some(Pager {
+        child,
+        saved_stdout,
+    }))
+}
+
+fn pager_command(repo_root: &Path) -> Option<String> {
+    let configured = || {
+        Repository::discover(repo_root)
+            .ok()?
+            .config()
+            .ok()?
+            .get_string("core.pager")
+            .ok()
+    };
+    let pager = env::var("GIT_PAGER")
+        .ok()
+        .or_else(configured)
+        .or_else(|| env::var("PAGER").ok())
+        .unwrap_or_else(|| "less".to_string());
+    let pager = pager.trim();
+    (!pager.is_empty() && pager != "cat").then(|| pager.to_string())
+}
@@ -0,0 +1,267 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{DiffOptions, ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use skim::{
+    ItemPreview, PreviewContext, SkimItem, SkimItemReceiver, SkimItemSender, prelude::unbounded,
+};
+
+use super::{
+    super::{
+        diff::{
+            Gitattributes, all_deltas, build_working_tree_from_status, status_letter, write_patch,
+        },
+        revision::resolve_commit,
+    },
+    PreviewBase, TOGGLE_KEY, Toggle, open_repo, run_skim,
+};
+
+/// Pick files of snapshot `commit` interactively.
+///
+/// Files that differ from `base` come first, marked with their status letter, followed
+/// by the rest of the snapshot's tree. Tab marks several, alt-a every listed one; the
+/// preview shows a changed file's diff (ctrl-t: its content) or an unchanged file's
+/// content. Returns the chosen paths, none when aborted.
+///
+/// # Errors
+/// Returns an error if the snapshot cannot be read or skim fails.
+pub fn select_files_interactive(
+    autosnap_dir: &Path,
+    commit: &str,
+    base: PreviewBase<'_>,
+    prompt: &str,
+) -> Result<Vec<String>> {
+    let repo = open_repo(autosnap_dir)?;
+    let commit = resolve_commit(&repo, commit)?;
+    let tree = commit.tree().context("failed to read snapshot tree")?.id();
+    let base = match base {
+        PreviewBase::Parent => commit.parents().next().map(|p| p.tree_id()),
+        PreviewBase::WorkTree(repo_root) => {
+            Some(build_working_tree_from_status(&repo, repo_root)?.id())
+        }
+    };
+    let files = list_files(&repo, tree, base)?;
+    drop(commit);
+    if files.is_empty() {
+        bail!("the snapshot has no files");
+    }
+
+    let previewer = Arc::new(FilePreviewer {
+        repo: Mutex::new(repo),
+        tree,
+        base,
+        toggle: Toggle::new()?,
+    });
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for (index, file) in files.into_iter().enumerate() {
+        let item = FileItem {
+            index,
+            file,
+            previewer: Arc::clone(&previewer),
+        };
+        tx.send(Arc::new(item))
+            .context("failed to queue file for selection")?;
+    }
+    drop(tx);
+
+    let bind = vec![previewer.toggle.bind(), "alt-a:toggle-all".to_string()];
+    let header = format!("tab: mark  alt-a: mark all  {TOGGLE_KEY}: diff/content");
+    Ok(run_skim(true, prompt, &header, bind, rx)?
+        .iter()
+        .filter_map(|item| {
+            (**item)
+                .as_any()
+                .downcast_ref::<FileItem>()
+                .map(|item| item.file.path.clone())
+        })
+        .collect())
+}
+
+/// A file of the snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileEntry {
+    path: String,
+    blob: Oid,
+    /// Status letter against the base; `None` when unchanged.
+    status: Option<char>,
+}
+
+/// Every file in `tree`, those that differ from `base` first.
+fn list_files(repo: &Repository, tree: Oid, base: Option<Oid>) -> Result<Vec<FileEntry>> {
+    let tree = repo.find_tree(tree)?;
+    let base = base.map(|id| repo.find_tree(id)).transpose()?;
+    let diff = repo.diff_tree_to_tree(base.as_ref(), Some(&tree), None)?;
+    let changed: HashMap<String, char> = all_deltas(&diff)
+        .into_iter()
+        .filter_map(|idx| {
+            let delta = diff.get_delta(idx)?;
+            let path = delta.new_file().path()?.to_str()?.to_string();
+            Some((path, status_letter(&delta)))
+        })
+        .collect();
+
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(ObjectType::Blob)
+            && let Some(name) = entry.name()
+        {
+            let path = format!("{dir}{name}");
+            files.push(FileEntry {
+                status: changed.get(&path).copied(),
+                blob: entry.id(),
+                path,
+            });
+        }
+        TreeWalkResult::Ok
+    })
+    .context("failed to walk snapshot tree")?;
+    // Stable, so each group stays in tree order.
+    files.sort_by_key(|file| file.status.is_none());
+    Ok(files)
+}
+
+struct FileItem {
+    index: usize,
+    file: FileEntry,
+    previewer: Arc<FilePreviewer>,
+}
+
+impl SkimItem for FileItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Owned(format!(
+            "{}  {}",
+            self.file.status.unwrap_or(' '),
+            self.file.path
+        ))
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.file.path)
+    }
+
+    // skim keys marked items by this index.
+    fn get_index(&self) -> usize {
+        self.index
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        match self.previewer.render(&self.file) {
+            Ok(text) => ItemPreview::AnsiText(text),
+            Err(err) => ItemPreview::Text(format!("preview failed: {err:#}")),
+        }
+    }
+}
+
+/// Renders file previews for one picker.
+struct FilePreviewer {
+    repo: Mutex<Repository>,
+    /// The snapshot's tree.
+    tree: Oid,
+    /// Tree the snapshot is compared with.
+    base: Option<Oid>,
+    /// Shows a changed file's content instead of its diff.
+    toggle: Toggle,
+}
+
+impl FilePreviewer {
+    fn render(&self, file: &FileEntry) -> Result<String> {
+        let repo = self.repo.lock().unwrap_or_else(PoisonError::into_inner);
+        if file.status.is_some() && !self.toggle.is_on() {
+            render_file_diff(&repo, self.base, self.tree, &file.path)
+        } else {
+            render_content(&repo, file.blob)
+        }
+    }
+}
+
+/// The coloured patch of one file, from the base tree to the snapshot.
+fn render_file_diff(repo: &Repository, base: Option<Oid>, tree: Oid, path: &str) -> Result<String> {
+    let base = base.map(|id| repo.find_tree(id)).transpose()?;
+    let tree = repo.find_tree(tree)?;
+    let mut opts = DiffOptions::new();
+    opts.pathspec(path).disable_pathspec_match(true);
+    let diff = repo.diff_tree_to_tree(base.as_ref(), Some(&tree), Some(&mut opts))?;
+    let mut out = Vec::new();
+    write_patch(
+        repo,
+        &diff,
+        &all_deltas(&diff),
+        &Gitattributes::default(),
+        true,
+        &mut out,
+    )?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// A blob's text, or a note for binary content.
+fn render_content(repo: &Repository, blob: Oid) -> Result<String> {
+    let blob = repo.find_blob(blob)?;
+    if blob.is_binary() {
+        return Ok(format!("(binary file, {} bytes)", blob.size()));
+    }
+    Ok(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(repo: &Repository, files: &[(&str, &str)]) -> Oid {
+        let mut index = git2::Index::new().unwrap();
+        for (path, content) in files {
+            let id = repo.blob(content.as_bytes()).unwrap();
+            let entry = git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100_644,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            };
+            index.add(&entry).unwrap();
+        }
+        index.write_tree_to(repo).unwrap()
+    }
+
+    #[test]
+    fn changed_files_are_listed_first() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let base = tree(&repo, &[("a.txt", "a"), ("src/b.txt", "b"), ("z.txt", "z")]);
+        let snapshot = tree(
+            &repo,
+            &[
+                ("a.txt", "a"),
+                ("src/b.txt", "b2"),
+                ("src/c.txt", "c"),
+                ("z.txt", "z"),
+            ],
+        );
+
+        let listed: Vec<_> = list_files(&repo, snapshot, Some(base))
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.status, file.path))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (Some('M'), "src/b.txt".to_string()),
+                (Some('A'), "src/c.txt".to_string()),
+                (None, "a.txt".to_string()),
+                (None, "z.txt".to_string()),
+            ]
+        );
+    }
+}
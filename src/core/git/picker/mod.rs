@@ -0,0 +1,649 @@
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use anyhow::{Context, Result, bail};
+use console::Style;
+use git2::{Diff, Oid, Repository};
+use skim::{
+    ItemPreview, PreviewContext, Skim, SkimItem, SkimItemReceiver, SkimItemSender,
+    prelude::{SkimOptionsBuilder, unbounded},
+};
+use tempfile::NamedTempFile;
+use time::format_description::well_known::Rfc3339;
+use tracing::debug;
+
+mod files;
+
+pub(crate) use files::select_files_interactive;
+
+use super::{
+    diff::{
+        Gitattributes, RenameDetection, all_deltas, build_working_tree_from_status,
+        collect_file_stats, detect_renames, render_stat, write_patch,
+    },
+    report::{branch_of, commit_time, note_of, short_time},
+    snapshot::trigger_of,
+    staged::staged_tree,
+};
+
+/// Key that switches the preview between `--stat` and the full patch.
+const TOGGLE_KEY: &str = "ctrl-t";
+
+/// Picker line of the working tree pseudo-entry in range selection.
+const WORK_TREE_LINE: &str = "working tree  (current files, not yet snapshotted)";
+
+/// What the picker's preview compares each snapshot with.
+#[derive(Debug, Clone, Copy)]
+pub enum PreviewBase<'a> {
+    /// The snapshot's parent, like `git show --stat`.
+    Parent,
+    /// The working tree of the repository at this root: what restoring would change.
+    WorkTree(&'a Path),
+}
+
+/// Interactive commit selection using skim fuzzy finder.
+///
+/// The whole history is listed, newest first, as it is read. The preview pane shows
+/// the highlighted snapshot's metadata and `--stat` against `base`; ctrl-t toggles
+/// the full patch.
+pub(crate) fn select_commit_interactive(
+    autosnap_dir: &Path,
+    base: PreviewBase<'_>,
+) -> Result<Option<String>> {
+    let repo = open_repo(autosnap_dir)?;
+    let (work_tree, compare_work_tree) = match base {
+        PreviewBase::Parent => (None, false),
+        PreviewBase::WorkTree(repo_root) => (
+            Some(build_working_tree_from_status(&repo, repo_root)?.id()),
+            true,
+        ),
+    };
+    let previewer = Previewer::new(repo, work_tree, compare_work_tree)?;
+
+    let picked = run_picker(autosnap_dir, &previewer, false, "Select snapshot> ")?;
+    Ok(picked.first().map(|entry| entry.short.clone()))
+}
+
+/// The two ends `diff -i` compares, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PickedRange {
+    pub base: String,
+    /// `None` for the working tree.
+    pub target: Option<String>,
+}
+
+/// Pick the base and target of an interactive diff in one picker.
+///
+/// Tab marks up to two entries, including a "working tree" pseudo-entry; a single
+/// snapshot is compared with the working tree. The pair is ordered by time whatever
+/// order it was marked in, and the preview pane shows the resulting range.
+pub(crate) fn select_range_interactive(
+    autosnap_dir: &Path,
+    repo_root: &Path,
+) -> Result<Option<PickedRange>> {
+    let repo = open_repo(autosnap_dir)?;
+    let work_tree = build_working_tree_from_status(&repo, repo_root)?.id();
+    let previewer = Previewer::new(repo, Some(work_tree), false)?;
+
+    let picked = run_picker(
+        autosnap_dir,
+        &previewer,
+        true,
+        "Diff (tab marks base/target)> ",
+    )?;
+    if picked.is_empty() {
+        return Ok(None);
+    }
+    let picked: Vec<&Entry> = picked.iter().map(AsRef::as_ref).collect();
+    let (base, target) = order_range(&picked)?;
+    Ok(Some(PickedRange {
+        base: base.map_or_else(|| "HEAD".to_string(), |entry| entry.short.clone()),
+        target: target.map(|entry| entry.short.clone()),
+    }))
+}
+
+fn open_repo(autosnap_dir: &Path) -> Result<Repository> {
+    Repository::open(autosnap_dir)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap_dir.display()))
+}
+
+/// Run skim over the snapshots (after the working tree entry, when the previewer has
+/// a working tree but does not compare with it) and return the accepted entries.
+fn run_picker(
+    autosnap_dir: &Path,
+    previewer: &Arc<Previewer>,
+    multi: bool,
+    prompt: &str,
+) -> Result<Vec<Arc<Entry>>> {
+    let mut bind = vec![previewer.toggle.bind()];
+    if multi {
+        // Marking changes the range, which the preview shows.
+        bind.push("tab:toggle+down+refresh-preview".to_string());
+        bind.push("btab:toggle+up+refresh-preview".to_string());
+    }
+    let header = format!("{TOGGLE_KEY}: toggle full patch");
+
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    if previewer.work_tree.is_some() && !previewer.compare_work_tree {
+        previewer.send(
+            |index| Entry {
+                index,
+                id: None,
+                short: "working tree".to_string(),
+                line: WORK_TREE_LINE.to_string(),
+                time: i64::MAX,
+            },
+            &tx,
+        );
+    }
+    stream_snapshots(autosnap_dir.to_path_buf(), Arc::clone(previewer), tx);
+
+    Ok(run_skim(multi, prompt, &header, bind, rx)?
+        .iter()
+        .filter_map(|item| {
+            (**item)
+                .as_any()
+                .downcast_ref::<SnapshotItem>()
+                .map(|item| Arc::clone(&item.entry))
+        })
+        .collect())
+}
+
+/// Run skim with the picker layout (list above a large preview pane) and return the
+/// accepted items, none when aborted.
+fn run_skim(
+    multi: bool,
+    prompt: &str,
+    header: &str,
+    bind: Vec<String>,
+    items: SkimItemReceiver,
+) -> Result<Vec<Arc<dyn SkimItem>>> {
+    // Configure skim options
+    let options = SkimOptionsBuilder::default()
+        .height("80%".to_string())
+        .multi(multi)
+        .preview(Some(String::new()))
+        .preview_window("down:60%:wrap".to_string())
+        .bind(bind)
+        .header(Some(header.to_string()))
+        .prompt(prompt.to_string())
+        .build()
+        .context("failed to build skim options")?;
+
+    // Run skim
+    let skim_output = Skim::run_with(&options, Some(items)).context("skim UI failed")?;
+
+    if skim_output.is_abort {
+        return Ok(Vec::new());
+    }
+    Ok(skim_output.selected_items)
+}
+
+/// The preview switch on ctrl-t.
+///
+/// skim only runs shell commands on key presses, so the key appends a byte to a file
+/// that the in-process preview reads back: an odd length means on.
+struct Toggle(NamedTempFile);
+
+impl Toggle {
+    fn new() -> Result<Self> {
+        NamedTempFile::new()
+            .map(Self)
+            .context("failed to create preview toggle file")
+    }
+
+    /// The `--bind` entry flipping the switch and redrawing the preview.
+    fn bind(&self) -> String {
+        format!(
+            "{TOGGLE_KEY}:execute-silent(printf t >> {})+refresh-preview",
+            shell_quote(&self.0.path().to_string_lossy())
+        )
+    }
+
+    fn is_on(&self) -> bool {
+        fs::metadata(self.0.path()).map_or(0, |m| m.len()) % 2 == 1
+    }
+}
+
+/// Read the history on a background thread, handing each snapshot to skim as soon
+/// as it is read; the walk stops once skim has gone.
+fn stream_snapshots(autosnap_dir: PathBuf, previewer: Arc<Previewer>, tx: SkimItemSender) {
+    thread::spawn(move || {
+        let walk = || -> Result<()> {
+            let repo = open_repo(&autosnap_dir)?;
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push_head()?;
+            for oid in revwalk {
+                let oid = oid?;
+                let entry = snapshot_entry(&repo, oid)?;
+                if !previewer.send(|index| Entry { index, ..entry }, &tx) {
+                    break;
+                }
+            }
+            Ok(())
+        };
+        // Nothing can be shown once skim owns the terminal.
+        if let Err(err) = walk() {
+            debug!(error = ?err, "stopped listing snapshots");
+        }
+    });
+}
+
+/// Order picked entries by time: a single snapshot runs to the working tree, two
+/// entries run oldest to newest. A `None` base is the latest snapshot (the working
+/// tree was picked alone); a `None` target is the working tree.
+fn order_range<'a>(picked: &[&'a Entry]) -> Result<(Option<&'a Entry>, Option<&'a Entry>)> {
+    let mut picked = picked.to_vec();
+    // Entries are listed newest first, so a later index is older at equal times.
+    picked.sort_by_key(|entry| (entry.time, std::cmp::Reverse(entry.index)));
+    let snapshot = |entry: &'a Entry| entry.id.is_some().then_some(entry);
+    match picked[..] {
+        [only] => Ok((snapshot(only), None)),
+        [base, target] => Ok((Some(base), snapshot(target))),
+        _ => bail!("select one or two entries to diff (tab marks them)"),
+    }
+}
+
+/// One line of the picker: a snapshot, or the working tree when picking a range.
+#[derive(Debug)]
+struct Entry {
+    /// Position in the picker, which skim uses to tell marked items apart.
+    index: usize,
+    /// `None` for the working tree.
+    id: Option<Oid>,
+    short: String,
+    /// Time, branch, trigger, changed files and note, for display and fuzzy search.
+    line: String,
+    /// Commit time in seconds; the working tree sorts after every snapshot.
+    time: i64,
+}
+
+/// Describe snapshot `id` for the picker:
+/// `<short id>  <time>  <branch>  <trigger>  <N files>  <note>`.
+fn snapshot_entry(repo: &Repository, id: Oid) -> Result<Entry> {
+    let commit = repo.find_commit(id)?;
+    let short = repo
+        .find_object(id, None)?
+        .short_id()?
+        .as_str()
+        .map_or_else(|| format!("{id:.7}"), ToString::to_string);
+    let message = commit.message().unwrap_or("<no message>");
+
+    let parent = commit.parents().next().map(|p| p.tree()).transpose()?;
+    let changed = repo
+        .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?
+        .deltas()
+        .len();
+
+    let line = format!(
+        "{short}  {}  {}  {}  {changed} file{}  {}",
+        short_time(commit_time(commit.time())),
+        branch_of(message),
+        trigger_of(message).unwrap_or("-"),
+        if changed == 1 { "" } else { "s" },
+        note_of(message),
+    );
+    Ok(Entry {
+        index: 0,
+        id: Some(id),
+        short,
+        line: line.trim_end().to_string(),
+        time: commit.time().seconds(),
+    })
+}
+
+struct SnapshotItem {
+    entry: Arc<Entry>,
+    previewer: Arc<Previewer>,
+}
+
+impl SkimItem for SnapshotItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.entry.line)
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.entry.short)
+    }
+
+    // skim keys marked items by this index; the default of 0 would make every
+    // item the same one.
+    fn get_index(&self) -> usize {
+        self.entry.index
+    }
+
+    fn preview(&self, context: PreviewContext) -> ItemPreview {
+        match self.previewer.render(&self.entry, &context) {
+            Ok(text) => ItemPreview::AnsiText(text),
+            Err(err) => ItemPreview::Text(format!("preview failed: {err:#}")),
+        }
+    }
+}
+
+/// Renders previews for every item of one picker and keeps its entries.
+struct Previewer {
+    repo: Mutex<Repository>,
+    /// Every entry sent to skim so far, by index.
+    entries: Mutex<Vec<Arc<Entry>>>,
+    /// The working tree, for its pseudo-entry or as the comparison base.
+    work_tree: Option<Oid>,
+    /// Compare snapshots with `work_tree` instead of their parent.
+    compare_work_tree: bool,
+    /// Shows the full patch instead of `--stat`.
+    toggle: Toggle,
+}
+
+impl Previewer {
+    fn new(repo: Repository, work_tree: Option<Oid>, compare_work_tree: bool) -> Result<Arc<Self>> {
+        if repo.head().is_err() {
+            bail!("No snapshots found in .autosnap repository");
+        }
+        Ok(Arc::new(Self {
+            repo: Mutex::new(repo),
+            entries: Mutex::new(Vec::new()),
+            work_tree,
+            compare_work_tree,
+            toggle: Toggle::new()?,
+        }))
+    }
+
+    /// Register the entry built for the next index and send it to skim; false once
+    /// skim is no longer listening.
+    fn send(self: &Arc<Self>, entry: impl FnOnce(usize) -> Entry, tx: &SkimItemSender) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let entry = Arc::new(entry(entries.len()));
+        entries.push(Arc::clone(&entry));
+        drop(entries);
+        let item = SnapshotItem {
+            entry,
+            previewer: Arc::clone(self),
+        };
+        tx.send(Arc::new(item)).is_ok()
+    }
+
+    fn render(&self, entry: &Entry, context: &PreviewContext<'_>) -> Result<String> {
+        let full_patch = self.toggle.is_on();
+        let mut out = String::new();
+        if self.work_tree.is_some() && !self.compare_work_tree {
+            let _ = writeln!(out, "{}\n", self.range_line(entry, context.selections));
+        }
+        let repo = self.repo.lock().unwrap_or_else(PoisonError::into_inner);
+        let preview = match (entry.id, self.work_tree) {
+            (Some(id), work_tree) => render_preview(
+                &repo,
+                id,
+                work_tree.filter(|_| self.compare_work_tree),
+                full_patch,
+                context.width,
+            )?,
+            (None, Some(work_tree)) => {
+                render_work_tree_preview(&repo, work_tree, full_patch, context.width)?
+            }
+            (None, None) => String::new(),
+        };
+        drop(repo);
+        out.push_str(&preview);
+        Ok(out)
+    }
+
+    /// `Range: <base> .. <target>` for the marked entries, or the highlighted one.
+    fn range_line(&self, current: &Entry, selections: &[&str]) -> String {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut picked: Vec<&Entry> = selections
+            .iter()
+            .filter_map(|text| entries.iter().find(|entry| entry.line == *text))
+            .map(AsRef::as_ref)
+            .collect();
+        if picked.is_empty() {
+            picked.push(current);
+        }
+        let range = match order_range(&picked) {
+            Ok((base, target)) => format!(
+                "{} .. {}",
+                base.map_or("latest snapshot", |entry| &entry.short),
+                target.map_or("working tree", |entry| &entry.short)
+            ),
+            Err(err) => err.to_string(),
+        };
+        drop(entries);
+        Style::new()
+            .bold()
+            .apply_to(format!("Range: {range}"))
+            .to_string()
+    }
+}
+
+/// Metadata of snapshot `id`, then its `--stat` (or patch) against its parent, or
+/// from the `work_tree` tree to the snapshot when one is given.
+fn render_preview(
+    repo: &Repository,
+    id: Oid,
+    work_tree: Option<Oid>,
+    full_patch: bool,
+    width: usize,
+) -> Result<String> {
+    let commit = repo.find_commit(id)?;
+    let message = commit.message().unwrap_or("<no message>");
+    let when = commit_time(commit.time())
+        .format(&Rfc3339)
+        .context("failed to format snapshot time")?;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}",
+        Style::new().yellow().apply_to(format!("snapshot {id}"))
+    );
+    let _ = writeln!(out, "Branch: {}", branch_of(message));
+    let _ = writeln!(out, "Date:   {when}");
+    if let Some(trigger) = trigger_of(message) {
+        let _ = writeln!(out, "Trigger: {trigger}");
+    }
+    // Every snapshot records its index; only mention one that differs.
+    if staged_tree(repo, id).is_some_and(|staged| staged.id() != commit.tree_id()) {
+        out.push_str("Staged: index differed from the working tree\n");
+    }
+    out.push('\n');
+    for line in message.trim_end().lines() {
+        let _ = writeln!(out, "    {line}");
+    }
+    out.push('\n');
+
+    let snapshot = commit.tree()?;
+    let diff = if let Some(work_tree) = work_tree {
+        out.push_str("Restoring would change:\n");
+        let work_tree = repo.find_tree(work_tree)?;
+        repo.diff_tree_to_tree(Some(&work_tree), Some(&snapshot), None)?
+    } else {
+        let parent = commit.parents().next().map(|p| p.tree()).transpose()?;
+        repo.diff_tree_to_tree(parent.as_ref(), Some(&snapshot), None)?
+    };
+    write_changes(repo, diff, full_patch, width, &mut out)?;
+    Ok(out)
+}
+
+/// The working tree pseudo-entry: its changes since the `latest` snapshot.
+fn render_work_tree_preview(
+    repo: &Repository,
+    work_tree: Oid,
+    full_patch: bool,
+    width: usize,
+) -> Result<String> {
+    let mut out = format!(
+        "{}\n\nChanges since the latest snapshot:\n",
+        Style::new().yellow().apply_to("working tree")
+    );
+    let latest = repo.head()?.peel_to_tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&latest), Some(&repo.find_tree(work_tree)?), None)?;
+    write_changes(repo, diff, full_patch, width, &mut out)?;
+    Ok(out)
+}
+
+/// `--stat` of `diff`, or its coloured patch.
+fn write_changes(
+    repo: &Repository,
+    mut diff: Diff<'_>,
+    full_patch: bool,
+    width: usize,
+    out: &mut String,
+) -> Result<()> {
+    detect_renames(&mut diff, RenameDetection::default())?;
+    let indices = all_deltas(&diff);
+    let attributes = Gitattributes::default();
+    if indices.is_empty() {
+        out.push_str("(no changes)\n");
+    } else if full_patch {
+        let mut patch = Vec::new();
+        write_patch(repo, &diff, &indices, &attributes, true, &mut patch)?;
+        out.push_str(&String::from_utf8_lossy(&patch));
+    } else {
+        let stats = collect_file_stats(repo, &diff, &indices, &attributes)?;
+        for line in render_stat(&stats, width, true) {
+            let _ = writeln!(out, "{line}");
+        }
+    }
+    Ok(())
+}
+
+/// Single-quote `text` for `sh -c`.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::git::staged::record_staged_tree;
+
+    fn repo_with_two_snapshots() -> (tempfile::TempDir, Repository, Oid) {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("autosnap", "autosnap@local").unwrap();
+        let mut parent = None;
+        for (content, message) in [
+            ("one\n", "AUTOSNAP[main] 2025-06-01T10:00:00Z"),
+            ("one\ntwo\n", "AUTOSNAP[main] 2025-06-01T10:05:00Z: wip"),
+        ] {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("file.txt", blob, 0o100_644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parents: Vec<_> = parent
+                .iter()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            parent = Some(
+                repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+                    .unwrap(),
+            );
+        }
+        (dir, repo, parent.unwrap())
+    }
+
+    #[test]
+    fn preview_shows_metadata_and_stat_against_parent() {
+        let (_dir, repo, head) = repo_with_two_snapshots();
+        let text =
+            console::strip_ansi_codes(&render_preview(&repo, head, None, false, 80).unwrap())
+                .into_owned();
+        assert!(text.contains(&format!("snapshot {head}")));
+        assert!(text.contains("Branch: main"));
+        assert!(text.contains("    AUTOSNAP[main] 2025-06-01T10:05:00Z: wip"));
+        assert!(text.contains("file.txt | 1 +"), "{text}");
+        assert!(text.contains("1 file changed, 1 insertion(+)"), "{text}");
+        assert!(!text.contains("@@"));
+    }
+
+    #[test]
+    fn preview_marks_only_snapshots_whose_index_differed() {
+        let (_dir, repo, head) = repo_with_two_snapshots();
+        let first = repo.find_commit(head).unwrap().parent_id(0).unwrap();
+        // The first snapshot's index matched it; the second's still held the first.
+        record_staged_tree(&repo, first, repo.find_commit(first).unwrap().tree_id()).unwrap();
+        record_staged_tree(&repo, head, repo.find_commit(first).unwrap().tree_id()).unwrap();
+
+        let preview = |id| render_preview(&repo, id, None, false, 80).unwrap();
+        assert!(!preview(first).contains("Staged:"));
+        assert!(preview(head).contains("Staged: index differed"));
+    }
+
+    #[test]
+    fn preview_toggles_to_full_patch() {
+        let (_dir, repo, head) = repo_with_two_snapshots();
+        let text = console::strip_ansi_codes(&render_preview(&repo, head, None, true, 80).unwrap())
+            .into_owned();
+        assert!(text.contains("@@ -1 +1,2 @@"), "{text}");
+        assert!(text.contains("+two"));
+    }
+
+    #[test]
+    fn preview_against_work_tree_shows_what_restore_changes() {
+        let (_dir, repo, head) = repo_with_two_snapshots();
+        let first = repo.find_commit(head).unwrap().parent_id(0).unwrap();
+        let work_tree = repo.find_commit(head).unwrap().tree_id();
+        let text = console::strip_ansi_codes(
+            &render_preview(&repo, first, Some(work_tree), true, 80).unwrap(),
+        )
+        .into_owned();
+        assert!(text.contains("Restoring would change:"));
+        assert!(text.contains("-two"), "{text}");
+    }
+
+    fn entry(index: usize, id: Option<u8>, time: i64) -> Entry {
+        Entry {
+            index,
+            id: id.map(|b| Oid::from_bytes(&[b; 20]).unwrap()),
+            short: String::new(),
+            line: String::new(),
+            time,
+        }
+    }
+
+    #[test]
+    fn range_is_ordered_by_time() {
+        // As listed: working tree, then snapshots newest first.
+        let work_tree = entry(0, None, i64::MAX);
+        let third = entry(1, Some(3), 300);
+        let second = entry(2, Some(2), 200);
+        let first = entry(3, Some(1), 200);
+        let ids = |(base, target): (Option<&Entry>, Option<&Entry>)| {
+            (base.map(|e| e.index), target.map(|e| e.index))
+        };
+        assert_eq!(
+            ids(order_range(&[&third, &first]).unwrap()),
+            (Some(3), Some(1))
+        );
+        assert_eq!(
+            ids(order_range(&[&first, &third]).unwrap()),
+            (Some(3), Some(1))
+        );
+        // Equal times fall back to list order.
+        assert_eq!(
+            ids(order_range(&[&second, &first]).unwrap()),
+            (Some(3), Some(2))
+        );
+        assert_eq!(
+            ids(order_range(&[&work_tree, &second]).unwrap()),
+            (Some(2), None)
+        );
+        assert_eq!(ids(order_range(&[&second]).unwrap()), (Some(2), None));
+        assert_eq!(ids(order_range(&[&work_tree]).unwrap()), (None, None));
+        assert!(order_range(&[&work_tree, &third, &second]).is_err());
+    }
+
+    #[test]
+    fn entries_carry_branch_trigger_and_changed_files() {
+        let (_dir, repo, head) = repo_with_two_snapshots();
+        let entry = snapshot_entry(&repo, head).unwrap();
+        let line = entry.line.split("  ").collect::<Vec<_>>();
+        assert_eq!(line[0], entry.short);
+        assert_eq!(&line[2..], ["main", "-", "1 file", "wip"]);
+    }
+}
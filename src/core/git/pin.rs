@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+/// Ref namespace marking snapshots the user wants to keep.
+pub const PIN_REF_PREFIX: &str = "refs/autosnap/pins/";
+
+fn pin_ref_name(commit: Oid) -> String {
+    format!("{PIN_REF_PREFIX}{commit}")
+}
+
+/// Whether `commit` is pinned.
+#[must_use]
+pub fn is_pinned(repo: &Repository, commit: Oid) -> bool {
+    repo.find_reference(&pin_ref_name(commit)).is_ok()
+}
+
+/// Every pinned snapshot.
+///
+/// # Errors
+/// Returns an error if the refs cannot be listed.
+pub fn pinned_snapshots(repo: &Repository) -> Result<HashSet<Oid>> {
+    let mut pins = HashSet::new();
+    let refs = repo
+        .references_glob(&format!("{PIN_REF_PREFIX}*"))
+        .context("failed to list pinned snapshots")?;
+    for reference in refs {
+        let reference = reference.context("failed to read pin ref")?;
+        if let Some(target) = reference.target() {
+            pins.insert(target);
+        }
+    }
+    Ok(pins)
+}
+
+/// Pin `commit`, or unpin it when `pinned` is false. Returns whether anything changed.
+///
+/// # Errors
+/// Returns an error if the ref cannot be created or deleted.
+pub fn set_pinned(repo: &Repository, commit: Oid, pinned: bool) -> Result<bool> {
+    let name = pin_ref_name(commit);
+    match (repo.find_reference(&name), pinned) {
+        (Ok(_), true) | (Err(_), false) => Ok(false),
+        (Err(_), true) => {
+            repo.reference(&name, commit, false, "autosnap: pin snapshot")
+                .with_context(|| format!("failed to pin {commit}"))?;
+            Ok(true)
+        }
+        (Ok(mut reference), false) => {
+            reference
+                .delete()
+                .with_context(|| format!("failed to unpin {commit}"))?;
+            Ok(true)
+        }
+    }
+}
+
+/// Re-point the pin on `from` at `to` (history rewrites).
+///
+/// # Errors
+/// Returns an error if the refs cannot be updated.
+pub fn move_pin(repo: &Repository, from: Oid, to: Oid) -> Result<()> {
+    if from == to || !is_pinned(repo, from) {
+        return Ok(());
+    }
+    repo.reference(&pin_ref_name(to), to, true, "autosnap: rewrite pin")
+        .with_context(|| format!("failed to move pin from {from} to {to}"))?;
+    set_pinned(repo, from, false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_follow_rewrites() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("t", "t@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let old = repo.commit(None, &sig, &sig, "old", &tree, &[]).unwrap();
+        let new = repo.commit(None, &sig, &sig, "new", &tree, &[]).unwrap();
+
+        assert!(set_pinned(&repo, old, true).unwrap());
+        assert!(!set_pinned(&repo, old, true).unwrap());
+        move_pin(&repo, old, new).unwrap();
+        assert_eq!(pinned_snapshots(&repo).unwrap(), HashSet::from([new]));
+
+        assert!(set_pinned(&repo, new, false).unwrap());
+        assert!(pinned_snapshots(&repo).unwrap().is_empty());
+    }
+}
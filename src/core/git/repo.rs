@@ -25,6 +25,19 @@ pub fn autosnap_dir(repo_root: &Path) -> PathBuf {
     repo_root.join(".autosnap")
 }
 
+/// Return the directory for autosnap's working state, such as an in-progress bisect.
+///
+/// It lives under the main repository's git dir, outside the `.autosnap` store that
+/// `compact` rewrites and garbage-collects.
+///
+/// # Errors
+/// Returns an error if the repository at `repo_root` cannot be opened.
+pub fn state_dir(repo_root: &Path) -> Result<PathBuf> {
+    let repo = Repository::open(repo_root)
+        .with_context(|| format!("failed to open repository at {}", repo_root.display()))?;
+    Ok(repo.path().join("autosnap"))
+}
+
 /// Initialize the `.autosnap` bare repository if absent and add it to `.git/info/exclude`.
 ///
 /// # Errors
@@ -0,0 +1,348 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use git2::{Oid, Repository, Tree};
+use serde::{Serialize, Serializer};
+use time::{OffsetDateTime, UtcOffset, format_description::well_known::Rfc3339};
+
+use super::{
+    diff::{FileStat, Gitattributes, all_deltas, collect_file_stats},
+    repo::autosnap_dir,
+    revision::{parse_time, snapshot_chain},
+};
+
+/// Output styles for [`Report::render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Markdown,
+    Json,
+}
+
+/// Snapshot activity in a time window, grouped by the branch each snapshot was taken on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    #[serde(serialize_with = "rfc3339")]
+    pub since: OffsetDateTime,
+    pub snapshots: usize,
+    /// Distinct paths touched on any branch.
+    pub files: usize,
+    /// Most recently active branch first.
+    pub branches: Vec<BranchActivity>,
+}
+
+/// The snapshots of one branch in the window.
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchActivity {
+    pub branch: String,
+    pub snapshots: usize,
+    /// Most snapshots first, then by path.
+    pub files: Vec<FileActivity>,
+}
+
+/// One touched path.
+///
+/// Line counts add up each of the branch's own snapshots against the one before it,
+/// so snapshots taken on other branches in between are not counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileActivity {
+    pub path: String,
+    /// Snapshots in which this path changed.
+    pub snapshots: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub binary: bool,
+    #[serde(serialize_with = "rfc3339")]
+    pub first_touched: OffsetDateTime,
+    #[serde(serialize_with = "rfc3339")]
+    pub last_touched: OffsetDateTime,
+}
+
+/// Per-branch accumulator while walking the window.
+struct BranchAcc {
+    snapshots: usize,
+    /// Position in the chain of the branch's last snapshot.
+    last: usize,
+    files: BTreeMap<String, FileActivity>,
+}
+
+/// Summarise the snapshots taken since `since` (`1d`, `8h`, `2025-06-01`...).
+///
+/// # Errors
+/// Returns an error if `since` is not a time or the snapshots cannot be read.
+pub fn activity_report(repo_root: &Path, since: &str) -> Result<Report> {
+    let autosnap = autosnap_dir(repo_root);
+    if !autosnap.exists() {
+        bail!(".autosnap is missing; run `git autosnap init` first")
+    }
+    let repo = Repository::open(&autosnap)
+        .with_context(|| format!("failed to open autosnap repo at {}", autosnap.display()))?;
+    let Some(since) = parse_time(since, OffsetDateTime::now_utc()) else {
+        bail!("invalid time: {since} (use e.g. 8h, 1d, 1w or 2025-06-01)");
+    };
+
+    let chain = snapshot_chain(&repo)?;
+    let mut branches: HashMap<String, BranchAcc> = HashMap::new();
+    let mut snapshots = 0;
+    for (pos, &oid) in chain.iter().enumerate() {
+        let commit = repo.find_commit(oid)?;
+        let when = commit_time(commit.time());
+        if when < since {
+            continue;
+        }
+        snapshots += 1;
+        let touched = snapshot_stats(
+            &repo,
+            parent_tree(&repo, &chain, pos)?.as_ref(),
+            &commit.tree()?,
+        )?;
+        let branch = branch_of(commit.message().unwrap_or(""));
+        let acc = branches
+            .entry(branch.to_string())
+            .or_insert_with(|| BranchAcc {
+                snapshots: 0,
+                last: pos,
+                files: BTreeMap::new(),
+            });
+        acc.snapshots += 1;
+        acc.last = pos;
+        for stat in touched {
+            let entry = acc
+                .files
+                .entry(stat.path.clone())
+                .or_insert_with(|| FileActivity {
+                    path: stat.path,
+                    snapshots: 0,
+                    insertions: 0,
+                    deletions: 0,
+                    binary: false,
+                    first_touched: when,
+                    last_touched: when,
+                });
+            entry.snapshots += 1;
+            entry.insertions += stat.insertions;
+            entry.deletions += stat.deletions;
+            entry.binary |= stat.binary.is_some();
+            entry.last_touched = when;
+        }
+    }
+
+    let files = branches
+        .values()
+        .flat_map(|acc| acc.files.keys())
+        .collect::<HashSet<_>>()
+        .len();
+    let mut activity = branches
+        .into_iter()
+        .map(|(branch, acc)| (acc.last, branch_activity(branch, acc)))
+        .collect::<Vec<_>>();
+    activity.sort_by_key(|(last, _)| std::cmp::Reverse(*last));
+
+    Ok(Report {
+        since,
+        snapshots,
+        files,
+        branches: activity.into_iter().map(|(_, branch)| branch).collect(),
+    })
+}
+
+fn branch_activity(branch: String, acc: BranchAcc) -> BranchActivity {
+    let mut files: Vec<FileActivity> = acc.files.into_values().collect();
+    files.sort_by(|a, b| {
+        b.snapshots
+            .cmp(&a.snapshots)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    BranchActivity {
+        branch,
+        snapshots: acc.snapshots,
+        files,
+    }
+}
+
+// The tree before the snapshot at `pos`; the first snapshot starts from nothing.
+fn parent_tree<'r>(repo: &'r Repository, chain: &[Oid], pos: usize) -> Result<Option<Tree<'r>>> {
+    match pos.checked_sub(1) {
+        Some(prev) => Ok(Some(repo.find_commit(chain[prev])?.tree()?)),
+        None => Ok(None),
+    }
+}
+
+// What one snapshot changed against the snapshot before it.
+fn snapshot_stats(
+    repo: &Repository,
+    old: Option<&Tree<'_>>,
+    new: &Tree<'_>,
+) -> Result<Vec<FileStat>> {
+    let diff = repo
+        .diff_tree_to_tree(old, Some(new), None)
+        .context("failed to diff snapshot against its parent")?;
+    collect_file_stats(repo, &diff, &all_deltas(&diff), &Gitattributes::default())
+}
+
+/// The branch recorded in an `AUTOSNAP[<branch>] <time>` message.
+pub(crate) fn branch_of(message: &str) -> &str {
+    message
+        .strip_prefix("AUTOSNAP[")
+        .and_then(|rest| rest.split_once("] ").or_else(|| rest.split_once(']')))
+        .map_or("(unknown)", |(branch, _)| branch)
+}
+
+/// The note of a snapshot message: what follows the timestamp of an
+/// `AUTOSNAP[<branch>] <time>: note` subject, or a foreign subject as is.
+pub(crate) fn note_of(message: &str) -> &str {
+    let subject = message.lines().next().unwrap_or(message);
+    subject
+        .strip_prefix("AUTOSNAP[")
+        .map_or(Some(subject), |rest| {
+            rest.split_once(": ").map(|(_, note)| note)
+        })
+        .unwrap_or("")
+}
+
+// Snapshot times keep the offset they were taken with.
+pub(crate) fn commit_time(time: git2::Time) -> OffsetDateTime {
+    let offset =
+        UtcOffset::from_whole_seconds(time.offset_minutes() * 60).unwrap_or(UtcOffset::UTC);
+    OffsetDateTime::from_unix_timestamp(time.seconds())
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+        .to_offset(offset)
+}
+
+fn rfc3339<S: Serializer>(time: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let text = time.format(&Rfc3339).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&text)
+}
+
+pub(crate) fn short_time(time: OffsetDateTime) -> String {
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute()
+    )
+}
+
+fn lines(file: &FileActivity) -> String {
+    if file.binary {
+        "binary".to_string()
+    } else {
+        format!("+{} -{}", file.insertions, file.deletions)
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{count} {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+impl Report {
+    /// Render the report in the given format.
+    ///
+    /// # Errors
+    /// Returns an error if JSON serialization fails.
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text()),
+            ReportFormat::Markdown => Ok(self.render_markdown()),
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).context("failed to serialize report")
+            }
+        }
+    }
+
+    fn heading(&self) -> String {
+        format!(
+            "Activity since {}: {}, {}",
+            short_time(self.since.to_offset(local_offset())),
+            plural(self.snapshots, "snapshot"),
+            plural(self.files, "file")
+        )
+    }
+
+    fn render_text(&self) -> String {
+        let mut out = self.heading();
+        out.push('\n');
+        for branch in &self.branches {
+            let _ = write!(
+                out,
+                "\n{} ({})\n",
+                branch.branch,
+                plural(branch.snapshots, "snapshot")
+            );
+            let path_width = branch.files.iter().map(|f| f.path.len()).max().unwrap_or(0);
+            let lines_width = branch
+                .files
+                .iter()
+                .map(|f| lines(f).len())
+                .max()
+                .unwrap_or(0);
+            for file in &branch.files {
+                let _ = writeln!(
+                    out,
+                    "  {:<path_width$}  {:>3}x  {:<lines_width$}  {} .. {}",
+                    file.path,
+                    file.snapshots,
+                    lines(file),
+                    short_time(file.first_touched),
+                    short_time(file.last_touched),
+                );
+            }
+        }
+        out
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = format!("## {}\n", self.heading());
+        for branch in &self.branches {
+            let _ = write!(
+                out,
+                "\n### {} ({})\n\n\
+                 | File | Snapshots | Lines | First touched | Last touched |\n\
+                 | --- | ---: | ---: | --- | --- |\n",
+                branch.branch,
+                plural(branch.snapshots, "snapshot")
+            );
+            for file in &branch.files {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} | {} |",
+                    file.path.replace('|', "\\|"),
+                    file.snapshots,
+                    lines(file),
+                    short_time(file.first_touched),
+                    short_time(file.last_touched),
+                );
+            }
+        }
+        out
+    }
+}
+
+fn local_offset() -> UtcOffset {
+    UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_comes_from_the_snapshot_message() {
+        assert_eq!(branch_of("AUTOSNAP[main] 2025-06-01T10:00:00Z"), "main");
+        assert_eq!(
+            branch_of("AUTOSNAP[feature/x] 2025-06-01T10:00:00Z: msg"),
+            "feature/x"
+        );
+        assert_eq!(branch_of("compacted history"), "(unknown)");
+    }
+}
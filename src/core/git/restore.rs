@@ -1,24 +1,78 @@
-use std::{fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
 
 use anyhow::{Context, Result, bail};
-use git2::Repository;
+use git2::{DiffLineType, FileMode, ObjectType, Oid, Patch, Repository};
 
-use super::{repo::autosnap_dir, shell::select_commit_interactive};
+use super::{
+    metadata::read_metadata,
+    picker::{PreviewBase, select_commit_interactive, select_files_interactive},
+    repo::autosnap_dir,
+    restore_plan::{PlanAction, RestorePlan, plan_restore},
+    revision::resolve_commit,
+    staged::{staged_tree, write_tree_to_main_index},
+};
+
+/// How a restore updates the main repository index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexMode {
+    /// Leave the index exactly as it was.
+    #[default]
+    Keep,
+    /// Refresh tracked index entries for the paths the restore wrote or removed.
+    Update,
+    /// Reset the restored paths to the staged state recorded with the snapshot.
+    Staged,
+}
+
+/// Options controlling a restore.
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RestoreOptions<'a> {
+    /// Commit SHA or ref to restore from (defaults to HEAD).
+    pub commit: Option<&'a str>,
+    /// Select the commit interactively.
+    pub interactive: bool,
+    /// Restore even with uncommitted changes, overwriting local edits.
+    pub force: bool,
+    /// Only report what would change.
+    pub dry_run: bool,
+    /// Print the dry-run plan as JSON instead of text.
+    pub json: bool,
+    /// Remove files not present in the snapshot.
+    pub full: bool,
+    /// Reapply the mtimes recorded with the snapshot to restored files.
+    pub restore_mtimes: bool,
+    /// What to do with the main repository index afterwards.
+    pub index: IndexMode,
+    /// Restrict the restore to these paths (all paths when empty).
+    pub paths: &'a [String],
+}
 
 /// Restore files from a snapshot to the working tree.
 ///
+/// Returns the plan the restore followed (or, for a dry run, would follow).
+///
 /// # Errors
 /// Returns an error if opening repositories, resolving commits, or checkout operations fail.
-#[allow(clippy::too_many_lines, clippy::fn_params_excessive_bools)]
-pub fn restore(
-    repo_root: &Path,
-    commit: Option<&str>,
-    interactive: bool,
-    force: bool,
-    dry_run: bool,
-    full: bool,
-    paths: &[String],
-) -> Result<()> {
+#[allow(clippy::too_many_lines)]
+pub fn restore(repo_root: &Path, opts: &RestoreOptions<'_>) -> Result<RestorePlan> {
+    let RestoreOptions {
+        commit,
+        interactive,
+        force,
+        dry_run,
+        json,
+        full,
+        restore_mtimes,
+        index,
+        paths,
+    } = *opts;
+
     let autosnap = autosnap_dir(repo_root);
     if !autosnap.exists() {
         bail!(".autosnap is missing; run `git autosnap init` first")
@@ -60,7 +114,7 @@ pub fn restore(
 
     // If interactive mode, select commit using skim
     let commit_to_use = if interactive {
-        select_commit_interactive(&autosnap)?
+        select_commit_interactive(&autosnap, PreviewBase::WorkTree(repo_root))?
     } else {
         commit.map(String::from)
     };
@@ -75,16 +129,40 @@ pub fn restore(
 
     // Parse the commit reference
     let commit_ref = commit_to_use.as_deref().unwrap_or("HEAD");
-    let object = repo
-        .revparse_single(commit_ref)
-        .with_context(|| format!("failed to parse commit reference: {commit_ref}"))?;
-
-    let commit = object
-        .peel_to_commit()
-        .with_context(|| format!("failed to resolve {commit_ref} to a commit"))?;
+    let commit = resolve_commit(&repo, commit_ref)?;
 
     let tree = commit.tree().context("failed to get tree from commit")?;
 
+    // Check before touching any file: the snapshot tree also holds untracked files, so
+    // it is no stand-in for a missing staged state.
+    let staged = if index == IndexMode::Staged {
+        Some(staged_tree(&repo, commit.id()).with_context(|| {
+            format!(
+                "no staged state was recorded for snapshot {}; use --index update instead",
+                commit.id()
+            )
+        })?)
+    } else {
+        None
+    };
+
+    // Without PATH arguments, interactive mode goes on to pick the files
+    let picked_paths;
+    let paths = if interactive && paths.is_empty() {
+        picked_paths = select_files_interactive(
+            &autosnap,
+            &commit.id().to_string(),
+            PreviewBase::WorkTree(repo_root),
+            "Restore files> ",
+        )?;
+        if picked_paths.is_empty() {
+            bail!("no files selected; nothing restored");
+        }
+        &picked_paths[..]
+    } else {
+        paths
+    };
+
     // Get commit info for display
     let short_id = commit
         .as_object()
@@ -94,100 +172,377 @@ pub fn restore(
     let message = commit.message().unwrap_or("no message");
     let first_line = message.lines().next().unwrap_or(message);
 
+    if !json {
+        if dry_run {
+            println!("DRY RUN: Would restore from snapshot:");
+        } else {
+            println!("Restoring from snapshot:");
+        }
+        println!("  Commit: {short_id_str} {first_line}");
+        if !paths.is_empty() {
+            println!("  Paths: {}", paths.join(", "));
+        }
+        if full {
+            println!("  Mode: Full restore (will remove files not in snapshot)");
+        } else {
+            println!("  Mode: Overlay (preserves files not in snapshot)");
+        }
+        println!();
+    }
+
+    // Work out every change up front; a full restore deletes exactly what the plan lists.
+    let plan = plan_restore(&repo, repo_root, commit.id(), &tree, full, paths)?;
+
     if dry_run {
-        println!("DRY RUN: Would restore from snapshot:");
-    } else {
-        println!("Restoring from snapshot:");
+        if json {
+            println!("{}", plan.to_json()?);
+        } else {
+            plan.print();
+            println!("\nDRY RUN completed. No files were modified.");
+        }
+        return Ok(plan);
     }
-    println!("  Commit: {short_id_str} {first_line}");
-    if !paths.is_empty() {
-        println!("  Paths: {}", paths.join(", "));
+
+    println!("Processing files...");
+
+    // Paths written by the checkout, reported through the progress callback
+    let mut written = HashSet::new();
+
+    // Check out exactly the planned creations and modifications. Left to itself, a
+    // forced checkout would also delete files the overlay mode promises to keep.
+    let to_write: Vec<&str> = plan
+        .changes()
+        .filter(|f| f.action != PlanAction::Delete)
+        .map(|f| f.path.as_str())
+        .collect();
+
+    if !to_write.is_empty() {
+        let mut checkout_builder = git2::build::CheckoutBuilder::new();
+
+        if force {
+            checkout_builder.force();
+        } else {
+            checkout_builder.safe();
+        }
+
+        checkout_builder.recreate_missing(true);
+        checkout_builder.update_index(false);
+        checkout_builder.disable_pathspec_match(true);
+        for path in &to_write {
+            checkout_builder.path(path);
+        }
+
+        checkout_builder.progress(|path, _cur, _total| {
+            if let Some(p) = path {
+                println!("  {}", p.display());
+                written.insert(p.to_string_lossy().into_owned());
+            }
+        });
+
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))
+            .context("failed to restore snapshot")?;
     }
-    if full {
-        println!("  Mode: Full restore (will remove files not in snapshot)");
-    } else {
-        println!("  Mode: Overlay (preserves files not in snapshot)");
+
+    // Only files the checkout actually wrote get their recorded mtimes back;
+    // untouched files keep whatever timestamp they already have.
+    if restore_mtimes && let Some(metadata) = read_metadata(&repo, commit.id())? {
+        let applied = metadata.apply(repo_root, |path| written.contains(path));
+        if applied > 0 {
+            println!("  Restored mtimes for {applied} files");
+        }
     }
-    println!();
 
-    if !dry_run {
-        println!("Processing files...");
+    for file in plan.changes().filter(|f| f.action == PlanAction::Delete) {
+        let path = repo_root.join(&file.path);
+        fs::remove_file(&path)
+            .with_context(|| format!("failed to remove file: {}", path.display()))?;
+        remove_empty_parents(repo_root, &path);
+        println!("  Removed: {}", file.path);
     }
 
-    // Build checkout options
-    let mut checkout_builder = git2::build::CheckoutBuilder::new();
+    let index_changes = match (index, staged) {
+        (IndexMode::Update, _) => {
+            let restored: Vec<&str> = plan.changes().map(|f| f.path.as_str()).collect();
+            Some(update_main_index(repo_root, &restored)?)
+        }
+        (IndexMode::Staged, Some(staged)) => {
+            // Every path the restore brought in line with the snapshot, written or not.
+            let restored: Vec<&str> = plan
+                .files
+                .iter()
+                .filter(|f| f.action != PlanAction::Keep)
+                .map(|f| f.path.as_str())
+                .collect();
+            let before = main_index_entries(repo_root)?;
+            write_tree_to_main_index(&repo, &staged, repo_root, &restored)?;
+            Some(diff_index_entries(&before, &main_index_entries(repo_root)?))
+        }
+        _ => None,
+    };
 
-    if dry_run {
-        checkout_builder.dry_run();
-    } else if force {
-        checkout_builder.force();
-    } else {
-        checkout_builder.safe();
+    println!("\n✓ Restore completed successfully");
+    if full {
+        println!("Note: Files not in the snapshot have been removed.");
     }
+    print_index_changes(index, index_changes.as_deref());
 
-    checkout_builder.recreate_missing(true);
-    checkout_builder.update_index(false);
+    Ok(plan)
+}
 
-    if !paths.is_empty() {
-        for path in paths {
-            checkout_builder.path(path);
+// Remove directories left empty by a full restore, stopping at the repository root.
+fn remove_empty_parents(repo_root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == repo_root || fs::remove_dir(current).is_err() {
+            break;
         }
+        dir = current.parent();
     }
+}
 
-    checkout_builder.progress(|path, _cur, _total| {
-        if let Some(p) = path {
-            println!("  {}", p.display());
-        }
-    });
-
-    repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))
-        .context("failed to restore snapshot")?;
-
-    if full && !dry_run {
-        let entries = fs::read_dir(repo_root).context("failed to read repository directory")?;
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-            if file_name == ".git" || file_name == ".autosnap" {
-                continue;
-            }
-            let relative_path = path.strip_prefix(repo_root).unwrap_or(&path);
-            if tree.get_path(relative_path).is_err() {
-                if path.is_dir() {
-                    fs::remove_dir_all(&path).with_context(|| {
-                        format!("failed to remove directory: {}", path.display())
-                    })?;
-                } else {
-                    fs::remove_file(&path)
-                        .with_context(|| format!("failed to remove file: {}", path.display()))?;
-                }
-                println!("  Removed: {}", relative_path.display());
-            }
+type IndexEntries = BTreeMap<String, (Oid, u32)>;
+
+fn main_index_entries(repo_root: &Path) -> Result<IndexEntries> {
+    let main_repo = Repository::discover(repo_root).context("failed to open main repository")?;
+    let index = main_repo
+        .index()
+        .context("failed to open main repository index")?;
+    Ok(index
+        .iter()
+        .map(|e| {
+            (
+                String::from_utf8_lossy(&e.path).into_owned(),
+                (e.id, e.mode),
+            )
+        })
+        .collect())
+}
+
+/// Name-status style changes between two index states (`A`, `M`, `D` plus path).
+fn diff_index_entries(before: &IndexEntries, after: &IndexEntries) -> Vec<(char, String)> {
+    let mut changes = Vec::new();
+    for (path, entry) in before {
+        match after.get(path) {
+            None => changes.push(('D', path.clone())),
+            Some(new) if new != entry => changes.push(('M', path.clone())),
+            Some(_) => {}
         }
     }
+    for path in after.keys().filter(|p| !before.contains_key(*p)) {
+        changes.push(('A', path.clone()));
+    }
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    changes
+}
 
-    if dry_run {
-        println!("\nDRY RUN completed. No files were modified.");
+// Refresh the tracked entries among the restored paths, like `git add -u` on each.
+// Paths are taken literally, never as globs.
+fn update_main_index(repo_root: &Path, restored: &[&str]) -> Result<Vec<(char, String)>> {
+    let before = main_index_entries(repo_root)?;
+    let main_repo = Repository::discover(repo_root).context("failed to open main repository")?;
+    let mut index = main_repo
+        .index()
+        .context("failed to open main repository index")?;
+    for path in restored.iter().filter(|path| before.contains_key(**path)) {
+        let result = if repo_root.join(path).symlink_metadata().is_ok() {
+            index.add_path(Path::new(path))
+        } else {
+            index.remove_path(Path::new(path))
+        };
+        result.with_context(|| format!("failed to update index entry for {path}"))?;
+    }
+    index
+        .write()
+        .context("failed to write main repository index")?;
+
+    Ok(diff_index_entries(&before, &main_index_entries(repo_root)?))
+}
+
+fn print_index_changes(mode: IndexMode, changes: Option<&[(char, String)]>) {
+    let Some(changes) = changes else {
+        println!("Note: Main repository index was left untouched (use --index update|staged).");
+        return;
+    };
+    let how = if mode == IndexMode::Staged {
+        "reset to the snapshot's staged state"
     } else {
-        let main_repo =
-            Repository::discover(repo_root).context("failed to open main repository")?;
-        let mut index = main_repo
-            .index()
-            .context("failed to open main repository index")?;
-        index
-            .update_all(["*"], None)
-            .context("failed to update main repository index")?;
-        index
-            .write()
-            .context("failed to write main repository index")?;
-
-        println!("\n✓ Restore completed successfully");
-        if full {
-            println!("Note: Files not in the snapshot have been removed.");
+        "updated for restored paths"
+    };
+    if changes.is_empty() {
+        println!("Note: Main repository index {how}; no entries changed.");
+        return;
+    }
+    let plural = if changes.len() == 1 {
+        "entry"
+    } else {
+        "entries"
+    };
+    println!(
+        "Note: Main repository index {how}; {} {plural} changed:",
+        changes.len()
+    );
+    for (status, path) in changes {
+        println!("  {status}\t{path}");
+    }
+}
+
+/// Restore a single file of snapshot `commit` without printing anything: write its
+/// content and executable bit, or delete the working file when the snapshot lacks it.
+///
+/// # Errors
+/// Returns an error if the snapshot cannot be read or the file cannot be written.
+pub fn restore_file(repo: &Repository, repo_root: &Path, commit: Oid, path: &str) -> Result<()> {
+    let target = repo_root.join(path);
+    let Some((blob, mode)) = snapshot_blob(repo, commit, path)? else {
+        if target.exists() {
+            fs::remove_file(&target)
+                .with_context(|| format!("failed to remove file: {}", target.display()))?;
+            remove_empty_parents(repo_root, &target);
         }
-        println!("Note: Main repository index has been updated to match the restored state.");
+        return Ok(());
+    };
+    write_file(&target, repo.find_blob(blob)?.content(), Some(mode))
+}
+
+/// Restore one hunk of the difference between the working file and snapshot
+/// `commit`. Hunks are numbered as for [`working_file_sides`].
+///
+/// # Errors
+/// Returns an error if either side cannot be read, the hunk does not exist, or the
+/// file cannot be written.
+pub fn restore_hunk(
+    repo: &Repository,
+    repo_root: &Path,
+    commit: Oid,
+    path: &str,
+    hunk: usize,
+) -> Result<()> {
+    let (current, snapshot) = working_file_sides(repo, repo_root, commit, path)?;
+    let spliced = splice_hunk(&current, &snapshot, hunk)?;
+    let mode = snapshot_blob(repo, commit, path)?.map(|(_, mode)| mode);
+    if mode.is_none() && spliced.is_empty() {
+        return restore_file(repo, repo_root, commit, path);
+    }
+    write_file(&repo_root.join(path), &spliced, mode)
+}
+
+/// The working file at `path` and its content in snapshot `commit`.
+///
+/// Missing sides read as empty. [`restore_hunk`] numbers hunks as
+/// `git2::Patch::from_buffers` with default options does for these two buffers.
+///
+/// # Errors
+/// Returns an error if either side cannot be read.
+pub fn working_file_sides(
+    repo: &Repository,
+    repo_root: &Path,
+    commit: Oid,
+    path: &str,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let current = read_working_file(&repo_root.join(path))?;
+    let snapshot = match snapshot_blob(repo, commit, path)? {
+        Some((blob, _)) => repo.find_blob(blob)?.content().to_vec(),
+        None => Vec::new(),
+    };
+    Ok((current, snapshot))
+}
+
+/// Blob id and file mode of `path` in snapshot `commit`, `None` when absent.
+fn snapshot_blob(repo: &Repository, commit: Oid, path: &str) -> Result<Option<(Oid, i32)>> {
+    let tree = repo
+        .find_commit(commit)
+        .and_then(|commit| commit.tree())
+        .with_context(|| format!("failed to read snapshot {commit}"))?;
+    Ok(tree
+        .get_path(Path::new(path))
+        .ok()
+        .filter(|entry| entry.kind() == Some(ObjectType::Blob))
+        .map(|entry| (entry.id(), entry.filemode())))
+}
+
+fn read_working_file(path: &Path) -> Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
     }
+}
 
+/// Write `content` to `path`, creating parent directories. A snapshot `mode` sets
+/// the executable bit; without one the file keeps its permissions.
+fn write_file(path: &Path, content: &[u8], mode: Option<i32>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))?;
+    if let Some(mode) = mode {
+        let perms = if mode == i32::from(FileMode::BlobExecutable) {
+            0o755
+        } else {
+            0o644
+        };
+        fs::set_permissions(path, fs::Permissions::from_mode(perms))
+            .with_context(|| format!("failed to set permissions on {}", path.display()))?;
+    }
     Ok(())
 }
+
+/// Apply hunk `hunk` of the patch from `current` to `snapshot` to `current`, leaving
+/// the rest of it alone.
+pub(crate) fn splice_hunk(current: &[u8], snapshot: &[u8], hunk: usize) -> Result<Vec<u8>> {
+    let patch = Patch::from_buffers(current, None, snapshot, None, None)
+        .context("failed to diff working file against snapshot")?;
+    if hunk >= patch.num_hunks() {
+        bail!("hunk {} does not exist", hunk + 1);
+    }
+    let (header, line_count) = patch.hunk(hunk)?;
+    let old_lines = header.old_lines() as usize;
+    // An empty old range names the line the hunk goes after.
+    let skip = if old_lines == 0 {
+        header.old_start() as usize
+    } else {
+        header.old_start() as usize - 1
+    };
+
+    let lines: Vec<&[u8]> = current.split_inclusive(|&b| b == b'\n').collect();
+    let mut out: Vec<u8> = lines[..skip].concat();
+    for idx in 0..line_count {
+        let line = patch.line_in_hunk(hunk, idx)?;
+        if matches!(
+            line.origin_value(),
+            DiffLineType::Context | DiffLineType::Addition
+        ) {
+            out.extend_from_slice(line.content());
+        }
+    }
+    out.extend(lines[skip + old_lines..].concat());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splice_hunk_restores_only_that_hunk() {
+        let current = b"a\nB\nc\nd\ne\nf\ng\nh\ni\nJ\nk\n";
+        let snapshot = b"a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            splice_hunk(current, snapshot, 1).unwrap(),
+            b"a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n"
+        );
+        assert_eq!(
+            splice_hunk(current, snapshot, 0).unwrap(),
+            b"a\nb\nc\nd\ne\nf\ng\nh\ni\nJ\nk\n"
+        );
+        assert!(splice_hunk(current, snapshot, 2).is_err());
+    }
+
+    #[test]
+    fn splice_hunk_handles_insertions_and_missing_newlines() {
+        assert_eq!(splice_hunk(b"a\nb", b"a\nb\n", 0).unwrap(), b"a\nb\n");
+        assert_eq!(splice_hunk(b"", b"x\n", 0).unwrap(), b"x\n");
+        assert_eq!(splice_hunk(b"x\n", b"", 0).unwrap(), b"");
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::HashSet, path::Path};
+
+use anyhow::{Context, Result};
+use git2::{Delta, DiffOptions, Oid, Repository, Tree};
+use serde::Serialize;
+
+use super::diff::build_working_tree_from_status;
+
+/// What a restore would do to a single path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanAction {
+    /// Missing from the working copy; the snapshot version is written.
+    Create,
+    /// Differs from the snapshot; the working copy version is overwritten.
+    Modify,
+    /// Not in the snapshot; removed by a full restore.
+    Delete,
+    /// Not in the snapshot; left in place by an overlay restore.
+    Keep,
+    /// Already identical to the snapshot.
+    Unchanged,
+}
+
+impl PlanAction {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Delete => "delete",
+            Self::Keep => "keep",
+            Self::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// One path in a restore plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFile {
+    pub path: String,
+    pub action: PlanAction,
+    /// Size in the working copy now, if the file exists; not looked up for unchanged
+    /// files.
+    pub old_size: Option<u64>,
+    /// Size after the restore, if the file will exist; not looked up for unchanged
+    /// files.
+    pub new_size: Option<u64>,
+    /// The working copy has edits to this path that the latest snapshot does not hold.
+    pub unsaved: bool,
+}
+
+impl PlannedFile {
+    /// Byte difference the restore makes to this path.
+    #[must_use]
+    pub fn size_delta(&self) -> i64 {
+        let size = |s: Option<u64>| i64::try_from(s.unwrap_or(0)).unwrap_or(i64::MAX);
+        match self.action {
+            PlanAction::Create | PlanAction::Modify | PlanAction::Delete => {
+                size(self.new_size) - size(self.old_size)
+            }
+            PlanAction::Keep | PlanAction::Unchanged => 0,
+        }
+    }
+
+    const fn touches_working_copy(&self) -> bool {
+        matches!(
+            self.action,
+            PlanAction::Create | PlanAction::Modify | PlanAction::Delete
+        )
+    }
+}
+
+/// Everything a restore would change, computed before touching the working copy.
+#[derive(Debug, Clone, Serialize)]
+pub struct RestorePlan {
+    /// Snapshot being restored.
+    pub commit: String,
+    /// Whether files missing from the snapshot are deleted.
+    pub full: bool,
+    /// The working copy differs from the latest snapshot within the restored paths.
+    pub working_copy_dirty: bool,
+    pub files: Vec<PlannedFile>,
+}
+
+impl RestorePlan {
+    /// Paths the restore would create, modify or delete.
+    pub fn changes(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|f| f.touches_working_copy())
+    }
+
+    /// Whether any overwritten or deleted file holds edits newer than the latest snapshot.
+    #[must_use]
+    pub fn touches_unsaved(&self) -> bool {
+        self.changes()
+            .any(|f| f.unsaved && f.action != PlanAction::Create)
+    }
+
+    /// Net byte difference across the working copy.
+    #[must_use]
+    pub fn size_delta(&self) -> i64 {
+        self.files.iter().map(PlannedFile::size_delta).sum()
+    }
+
+    fn count(&self, action: PlanAction) -> usize {
+        self.files.iter().filter(|f| f.action == action).count()
+    }
+
+    /// Print the plan for humans.
+    pub fn print(&self) {
+        if self.working_copy_dirty {
+            let unsaved = self.files.iter().filter(|f| f.unsaved).count();
+            println!("Working copy has edits newer than the latest snapshot ({unsaved} files).");
+        } else {
+            println!("Working copy matches the latest snapshot.");
+        }
+        println!();
+
+        let changes: Vec<&PlannedFile> = self.changes().collect();
+        let width = changes.iter().map(|f| f.path.len()).max().unwrap_or(0);
+        for file in &changes {
+            let note = if file.unsaved && file.action != PlanAction::Create {
+                "  (unsaved edits)"
+            } else {
+                ""
+            };
+            println!(
+                "  {:<6}  {:<width$}  {:>+8} B{note}",
+                file.action.label(),
+                file.path,
+                file.size_delta()
+            );
+        }
+        if !changes.is_empty() {
+            println!();
+        }
+
+        println!(
+            "{} to change ({} create, {} modify, {} delete), {} unchanged; size change {:+} B",
+            changes.len(),
+            self.count(PlanAction::Create),
+            self.count(PlanAction::Modify),
+            self.count(PlanAction::Delete),
+            self.count(PlanAction::Unchanged),
+            self.size_delta()
+        );
+        let kept = self.count(PlanAction::Keep);
+        if kept > 0 {
+            println!("{kept} files not in the snapshot are kept (--full would delete them)");
+        }
+    }
+
+    /// Render the plan as JSON.
+    ///
+    /// # Errors
+    /// Returns an error if serialization fails.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("failed to serialize restore plan")
+    }
+}
+
+/// Compute what restoring `tree` onto the working copy would do.
+///
+/// The working copy is read with the same rules as a snapshot, so ignored files never
+/// appear in the plan.
+///
+/// # Errors
+/// Returns an error if the working copy cannot be read or diffing fails.
+pub fn plan_restore(
+    repo: &Repository,
+    repo_root: &Path,
+    commit: Oid,
+    tree: &Tree<'_>,
+    full: bool,
+    paths: &[String],
+) -> Result<RestorePlan> {
+    let work_tree = build_working_tree_from_status(repo, repo_root)?;
+
+    let mut opts = DiffOptions::new();
+    opts.include_unmodified(true);
+    for path in paths {
+        opts.pathspec(path);
+    }
+    let diff = repo
+        .diff_tree_to_tree(Some(tree), Some(&work_tree), Some(&mut opts))
+        .context("failed to diff snapshot against working copy")?;
+
+    let unsaved = unsaved_paths(repo, &work_tree, paths)?;
+    // Only the object header is read; the blob itself stays on disk.
+    let odb = repo.odb().context("failed to open object database")?;
+    let blob_size = |id: Oid| {
+        (!id.is_zero())
+            .then(|| odb.read_header(id).ok())
+            .flatten()
+            .map(|(size, _)| size as u64)
+    };
+
+    let mut files = Vec::new();
+    for delta in diff.deltas() {
+        let Some(path) = delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .and_then(|p| p.to_str())
+        else {
+            continue;
+        };
+        // Unchanged files make up most of a plan and change no sizes.
+        let (snapshot_size, working_size) = if delta.status() == Delta::Unmodified {
+            (None, None)
+        } else {
+            (
+                blob_size(delta.old_file().id()),
+                blob_size(delta.new_file().id()),
+            )
+        };
+        let action = match delta.status() {
+            Delta::Deleted => PlanAction::Create,
+            Delta::Added | Delta::Untracked if full => PlanAction::Delete,
+            Delta::Added | Delta::Untracked => PlanAction::Keep,
+            Delta::Unmodified => PlanAction::Unchanged,
+            _ => PlanAction::Modify,
+        };
+        files.push(PlannedFile {
+            path: path.to_string(),
+            action,
+            old_size: working_size,
+            new_size: if action == PlanAction::Delete {
+                None
+            } else {
+                snapshot_size.or(working_size)
+            },
+            unsaved: unsaved.contains(path),
+        });
+    }
+
+    Ok(RestorePlan {
+        commit: commit.to_string(),
+        full,
+        working_copy_dirty: !unsaved.is_empty(),
+        files,
+    })
+}
+
+// Paths whose working copy content is not captured by the latest snapshot.
+fn unsaved_paths(
+    repo: &Repository,
+    work_tree: &Tree<'_>,
+    paths: &[String],
+) -> Result<HashSet<String>> {
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree().context("failed to peel HEAD to tree")?),
+        Err(_) => None,
+    };
+    let mut opts = DiffOptions::new();
+    for path in paths {
+        opts.pathspec(path);
+    }
+    let diff = repo
+        .diff_tree_to_tree(head_tree.as_ref(), Some(work_tree), Some(&mut opts))
+        .context("failed to diff latest snapshot against working copy")?;
+    Ok(diff
+        .deltas()
+        .filter_map(|d| {
+            d.new_file()
+                .path()
+                .or_else(|| d.old_file().path())
+                .and_then(|p| p.to_str())
+                .map(String::from)
+        })
+        .collect())
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use git2::Oid;
+
+use super::revision::parse_ago;
+
+/// How densely snapshots of one age band are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionTier {
+    /// Snapshots up to this age (seconds) belong to the tier.
+    pub max_age: i64,
+    /// Keep the newest snapshot of each window this long (seconds); `None` keeps all.
+    pub every: Option<i64>,
+}
+
+/// Tiered (grandfather-father-son) retention for `compact`.
+///
+/// Each snapshot falls into the first tier whose age it does not exceed and is thinned
+/// to that tier's density. Snapshots older than the last tier fold into one baseline.
+/// Written as `AGE:EVERY` pairs, youngest first: `2h:all,1d:10m,1w:1h,3mo:1d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    tiers: Vec<RetentionTier>,
+}
+
+/// The snapshots a policy keeps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPlan {
+    /// Kept snapshots, oldest first.
+    pub keep: Vec<Oid>,
+    /// The kept snapshot that stands for everything older than the policy's horizon.
+    pub baseline: Option<Oid>,
+}
+
+impl RetentionPolicy {
+    /// Keep everything from the last `days` days; fold the rest (`compact --days`).
+    #[must_use]
+    pub fn days(days: u32) -> Self {
+        Self {
+            tiers: vec![RetentionTier {
+                max_age: i64::from(days) * 86_400,
+                every: None,
+            }],
+        }
+    }
+
+    /// Keep every snapshot: nothing is thinned or folded by age.
+    #[must_use]
+    pub fn keep_all() -> Self {
+        Self {
+            tiers: vec![RetentionTier {
+                max_age: i64::MAX,
+                every: None,
+            }],
+        }
+    }
+
+    /// The tiers, youngest first.
+    #[must_use]
+    pub fn tiers(&self) -> &[RetentionTier] {
+        &self.tiers
+    }
+
+    /// Decide which of `snapshots` (id and commit time, oldest first) to keep at time
+    /// `now`.
+    ///
+    /// The newest snapshot and `pinned` ones are always kept. Of those past the
+    /// horizon, the newest becomes the baseline.
+    #[must_use]
+    pub fn plan(&self, snapshots: &[(Oid, i64)], now: i64, pinned: &HashSet<Oid>) -> RetentionPlan {
+        let horizon = self.tiers.last().map_or(0, |tier| tier.max_age);
+        let baseline = snapshots
+            .iter()
+            .rposition(|&(_, time)| now - time > horizon);
+        // Tier and window of a snapshot; `None` past the horizon.
+        let slot = |time: i64| {
+            let age = now - time;
+            let tier = self.tiers.iter().position(|tier| age <= tier.max_age)?;
+            let window = self.tiers[tier].every.map(|every| time.div_euclid(every));
+            Some((tier, window))
+        };
+
+        let mut plan = RetentionPlan::default();
+        for (idx, &(id, time)) in snapshots.iter().enumerate() {
+            let keep = if Some(idx) == baseline {
+                plan.baseline = Some(id);
+                true
+            } else if idx + 1 == snapshots.len() || pinned.contains(&id) {
+                true
+            } else {
+                match slot(time) {
+                    None => false,
+                    Some((_, None)) => true,
+                    // The newest snapshot of each window stands for it.
+                    Some(here) => slot(snapshots[idx + 1].1) != Some(here),
+                }
+            };
+            if keep {
+                plan.keep.push(id);
+            }
+        }
+        plan
+    }
+}
+
+impl FromStr for RetentionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut tiers = Vec::new();
+        for tier in spec.split([',', ' ']).filter(|tier| !tier.is_empty()) {
+            let (age, every) = tier
+                .split_once(':')
+                .with_context(|| format!("retention tier `{tier}` is not AGE:EVERY"))?;
+            let max_age = parse_seconds(age)
+                .with_context(|| format!("invalid age `{age}` in retention tier `{tier}`"))?;
+            let every = if every == "all" {
+                None
+            } else {
+                Some(parse_seconds(every).with_context(|| {
+                    format!("invalid interval `{every}` in retention tier `{tier}`")
+                })?)
+            };
+            if let Some(last) = tiers.last().map(|last: &RetentionTier| last.max_age)
+                && max_age <= last
+            {
+                bail!("retention tiers must be listed by increasing age: `{tier}`");
+            }
+            tiers.push(RetentionTier { max_age, every });
+        }
+        if tiers.is_empty() {
+            bail!("retention policy has no tiers");
+        }
+        Ok(Self { tiers })
+    }
+}
+
+fn parse_seconds(spec: &str) -> Option<i64> {
+    Some(parse_ago(spec)?.whole_seconds()).filter(|&secs| secs > 0)
+}
+
+impl fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, tier) in self.tiers.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}:", format_seconds(tier.max_age))?;
+            match tier.every {
+                Some(every) => f.write_str(&format_seconds(every))?,
+                None => f.write_str("all")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_seconds(secs: i64) -> String {
+    for (unit, len) in [("w", 604_800), ("d", 86_400), ("h", 3_600), ("m", 60)] {
+        if secs % len == 0 {
+            return format!("{}{unit}", secs / len);
+        }
+    }
+    format!("{secs}s")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3_600;
+    const NOW: i64 = 1_000 * 86_400;
+
+    fn id(n: u8) -> Oid {
+        Oid::from_bytes(&[n; 20]).unwrap()
+    }
+
+    #[test]
+    fn parses_tiers_youngest_first() {
+        let policy: RetentionPolicy = "2h:all, 1d:10m,1w:1h,3mo:1d".parse().unwrap();
+        assert_eq!(
+            policy.tiers(),
+            [
+                RetentionTier {
+                    max_age: 2 * HOUR,
+                    every: None
+                },
+                RetentionTier {
+                    max_age: 24 * HOUR,
+                    every: Some(600)
+                },
+                RetentionTier {
+                    max_age: 7 * 24 * HOUR,
+                    every: Some(HOUR)
+                },
+                RetentionTier {
+                    max_age: 90 * 24 * HOUR,
+                    every: Some(24 * HOUR)
+                },
+            ]
+        );
+        assert_eq!(policy.to_string(), "2h:all,1d:10m,1w:1h,90d:1d");
+
+        assert!("".parse::<RetentionPolicy>().is_err());
+        assert!("1d".parse::<RetentionPolicy>().is_err());
+        assert!("1d:all,2h:10m".parse::<RetentionPolicy>().is_err());
+        assert!("1d:0m".parse::<RetentionPolicy>().is_err());
+        assert!("1d:often".parse::<RetentionPolicy>().is_err());
+    }
+
+    #[test]
+    fn thins_each_tier_to_its_density() {
+        let policy: RetentionPolicy = "2h:all,1d:1h,3d:1d".parse().unwrap();
+        let snapshots = [
+            (id(1), NOW - 10 * 24 * HOUR), // past the horizon
+            (id(2), NOW - 5 * 24 * HOUR),  // past the horizon: baseline
+            (id(3), NOW - 47 * HOUR),      // same day as 4
+            (id(4), NOW - 46 * HOUR),
+            (id(5), NOW - 5 * HOUR - 120), // same hour as 6
+            (id(6), NOW - 5 * HOUR - 60),
+            (id(7), NOW - 3 * HOUR),
+            (id(8), NOW - HOUR), // kept at full density
+            (id(9), NOW - 60),
+        ];
+        let plan = policy.plan(&snapshots, NOW, &HashSet::new());
+        assert_eq!(plan.baseline, Some(id(2)));
+        assert_eq!(plan.keep, [id(2), id(4), id(6), id(7), id(8), id(9)]);
+    }
+
+    #[test]
+    fn pinned_and_newest_snapshots_are_kept() {
+        let policy = RetentionPolicy::days(1);
+        let snapshots = [
+            (id(1), NOW - 9 * 24 * HOUR),
+            (id(2), NOW - 8 * 24 * HOUR),
+            (id(3), NOW - 7 * 24 * HOUR),
+        ];
+        let plan = policy.plan(&snapshots, NOW, &HashSet::from([id(1)]));
+        assert_eq!(plan.baseline, Some(id(3)));
+        assert_eq!(plan.keep, [id(1), id(3)]);
+    }
+}
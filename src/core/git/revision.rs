@@ -0,0 +1,315 @@
+use std::{fmt, path::Path};
+
+use anyhow::{Context, Result, bail};
+use git2::{Commit, Oid, Repository};
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use super::{
+    repo::autosnap_dir,
+    rewrite_map::{resolve_rewritten, rewritten},
+};
+
+/// Something about how a snapshot spec resolved that the user should hear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecNote {
+    /// `spec` names a snapshot `compact` has replaced; `using` stands for it now.
+    Rewritten { spec: String, using: String },
+    /// `spec` is a time before the first snapshot; the oldest, `using`, was taken.
+    BeforeOldest { spec: String, using: String },
+}
+
+impl fmt::Display for SpecNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rewritten { spec, using } => {
+                write!(f, "Note: {spec} was rewritten by compact; using {using}")
+            }
+            Self::BeforeOldest { spec, using } => {
+                write!(
+                    f,
+                    "Warning: no snapshot before {spec}; using the oldest, {using}"
+                )
+            }
+        }
+    }
+}
+
+/// Resolve a commit reference (SHA, ref name, or revspec) in the autosnap repository.
+///
+/// Ids of snapshots `compact` has since rewritten resolve to the snapshot standing
+/// for them now, even while a backup keeps the old commit around.
+///
+/// # Errors
+/// Returns an error if the reference cannot be parsed or does not point to a commit.
+pub fn resolve_commit<'r>(repo: &'r Repository, spec: &str) -> Result<Commit<'r>> {
+    resolve_commit_noted(repo, spec).map(|(commit, _)| commit)
+}
+
+/// [`resolve_commit`], also saying when `spec` was rewritten by `compact`.
+///
+/// # Errors
+/// Returns an error if the reference cannot be parsed or does not point to a commit.
+pub fn resolve_commit_noted<'r>(
+    repo: &'r Repository,
+    spec: &str,
+) -> Result<(Commit<'r>, Option<SpecNote>)> {
+    let replacement = match repo.revparse_single(spec) {
+        Ok(object) => {
+            let commit = object
+                .peel_to_commit()
+                .with_context(|| format!("failed to resolve {spec} to a commit"))?;
+            match rewritten(repo, commit.id()) {
+                Some(id) if !snapshot_chain(repo)?.contains(&commit.id()) => id,
+                _ => return Ok((commit, None)),
+            }
+        }
+        // A snapshot id noted before `compact` rewrote it and gc dropped it.
+        Err(err) => match resolve_rewritten(repo, spec)? {
+            Some(id) => id,
+            None => {
+                return Err(err)
+                    .with_context(|| format!("failed to parse commit reference: {spec}"));
+            }
+        },
+    };
+    let commit = repo
+        .find_commit(replacement)
+        .with_context(|| format!("failed to read snapshot {replacement}"))?;
+    let note = SpecNote::Rewritten {
+        spec: spec.to_string(),
+        using: short_summary(&commit).0,
+    };
+    Ok((commit, Some(note)))
+}
+
+/// What a command should tell the user about resolving `spec` as a commit (with
+/// `since`, as [`resolve_since`] does) in the autosnap repository of `repo_root`.
+///
+/// Resolution errors are left to the command itself to report.
+#[must_use]
+pub fn spec_note(repo_root: &Path, spec: &str, since: bool) -> Option<SpecNote> {
+    let repo = Repository::open(autosnap_dir(repo_root)).ok()?;
+    let resolved = if since {
+        resolve_since_noted(&repo, spec)
+    } else {
+        resolve_commit_noted(&repo, spec)
+    };
+    resolved.ok()?.1
+}
+
+/// Resolve a revision in the main repository (e.g. `HEAD~2`, `origin/main`) to a commit
+/// readable through the autosnap repository.
+///
+/// The main repo's object store is attached to `repo` as an in-memory alternate, so
+/// later diffs can read objects from both stores; nothing is written to disk.
+///
+/// # Errors
+/// Returns an error if the main repository cannot be opened or `spec` does not name a
+/// commit there.
+pub fn resolve_main_commit<'r>(
+    repo: &'r Repository,
+    repo_root: &Path,
+    spec: &str,
+) -> Result<Commit<'r>> {
+    let main_repo = Repository::discover(repo_root).context("failed to open main repository")?;
+    let id = main_repo
+        .revparse_single(spec)
+        .with_context(|| format!("failed to find main repository revision: {spec}"))?
+        .peel_to_commit()
+        .with_context(|| format!("main repository revision {spec} is not a commit"))?
+        .id();
+
+    let objects = main_repo.commondir().join("objects");
+    repo.odb()
+        .context("failed to open object database")?
+        .add_disk_alternate(&objects.to_string_lossy())
+        .with_context(|| format!("failed to read objects from {}", objects.display()))?;
+    repo.find_commit(id)
+        .with_context(|| format!("failed to read main repository commit {id}"))
+}
+
+/// List the snapshot chain reachable from HEAD, oldest first.
+///
+/// Returns an empty list when HEAD is unborn (no snapshots yet).
+///
+/// # Errors
+/// Returns an error if the revision walk fails.
+pub fn snapshot_chain(repo: &Repository) -> Result<Vec<Oid>> {
+    let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+    if revwalk.push_head().is_err() {
+        return Ok(Vec::new());
+    }
+    revwalk
+        .simplify_first_parent()
+        .context("failed to configure revwalk")?;
+
+    let mut oids = Vec::new();
+    for oid in revwalk {
+        oids.push(oid.context("failed to iterate revwalk")?);
+    }
+    oids.reverse();
+
+    Ok(oids)
+}
+
+/// Short, human-friendly id and subject line for a commit.
+pub(crate) fn short_summary(commit: &Commit<'_>) -> (String, String) {
+    let short_id = commit
+        .as_object()
+        .short_id()
+        .ok()
+        .and_then(|buf| buf.as_str().map(ToString::to_string))
+        .unwrap_or_else(|| format!("{:.7}", commit.id()));
+    let message = commit.message().unwrap_or("<no message>");
+    let first_line = message.lines().next().unwrap_or(message).to_string();
+    (short_id, first_line)
+}
+
+/// Resolve `spec` as a snapshot: a point in time (see [`parse_time`]) selects the newest
+/// snapshot taken at or before it, anything else is a revision.
+///
+/// A time before the first snapshot falls back to the oldest one.
+///
+/// # Errors
+/// Returns an error if `spec` is neither, or there are no snapshots yet.
+pub fn resolve_since<'r>(repo: &'r Repository, spec: &str) -> Result<Commit<'r>> {
+    resolve_since_noted(repo, spec).map(|(commit, _)| commit)
+}
+
+/// [`resolve_since`], also saying when it fell back to the oldest snapshot or `spec`
+/// was rewritten by `compact`.
+///
+/// # Errors
+/// Returns an error if `spec` is neither, or there are no snapshots yet.
+pub fn resolve_since_noted<'r>(
+    repo: &'r Repository,
+    spec: &str,
+) -> Result<(Commit<'r>, Option<SpecNote>)> {
+    let Some(time) = parse_time(spec, OffsetDateTime::now_utc()) else {
+        return resolve_commit_noted(repo, spec);
+    };
+    let chain = snapshot_chain(repo)?;
+    let Some(&oldest) = chain.first() else {
+        bail!("no snapshots yet");
+    };
+    let cutoff = time.unix_timestamp();
+    let mut found = None;
+    for &oid in &chain {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("failed to read snapshot {oid}"))?;
+        if commit.time().seconds() > cutoff {
+            break;
+        }
+        found = Some(commit);
+    }
+    if let Some(commit) = found {
+        return Ok((commit, None));
+    }
+    let commit = repo
+        .find_commit(oldest)
+        .with_context(|| format!("failed to read snapshot {oldest}"))?;
+    let note = SpecNote::BeforeOldest {
+        spec: spec.to_string(),
+        using: short_summary(&commit).0,
+    };
+    Ok((commit, Some(note)))
+}
+
+/// Parse a point in time relative to `now`: a duration ago (`90s`, `30m`, `2h`, `1d`,
+/// `1w`, `3mo`, also spelled out as `2 hours ago`) or a local date, `YYYY-MM-DD` with
+/// an optional `HH:MM[:SS]`.
+#[must_use]
+pub fn parse_time(spec: &str, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    let spec = spec.trim();
+    parse_ago(spec)
+        .and_then(|ago| now.checked_sub(ago))
+        .or_else(|| parse_local_date(spec))
+}
+
+/// Parse a duration such as `30m`, `2 hours` or `2 hours ago`. A month is 30 days.
+pub(crate) fn parse_ago(spec: &str) -> Option<Duration> {
+    let spec = spec.strip_suffix("ago").unwrap_or(spec).trim_end();
+    let digits = spec.find(|c: char| !c.is_ascii_digit())?;
+    let count: i64 = spec[..digits].parse().ok()?;
+    let unit = match spec[digits..].trim_start() {
+        "s" | "sec" | "secs" | "second" | "seconds" => Duration::SECOND,
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::MINUTE,
+        "h" | "hour" | "hours" => Duration::HOUR,
+        "d" | "day" | "days" => Duration::DAY,
+        "w" | "week" | "weeks" => Duration::WEEK,
+        "mo" | "month" | "months" => Duration::days(30),
+        _ => return None,
+    };
+    unit.checked_mul(i32::try_from(count).ok()?)
+}
+
+fn parse_local_date(spec: &str) -> Option<OffsetDateTime> {
+    let (date, clock) = spec
+        .split_once(['T', ' '])
+        .map_or((spec, None), |(date, clock)| (date, Some(clock.trim())));
+    let mut parts = date.split('-').map(str::parse::<i32>);
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let date = Date::from_calendar_date(
+        year,
+        Month::try_from(u8::try_from(month).ok()?).ok()?,
+        u8::try_from(day).ok()?,
+    )
+    .ok()?;
+    let clock = match clock {
+        None => Time::MIDNIGHT,
+        Some(clock) => {
+            let fields: Vec<u8> = clock
+                .split(':')
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .ok()?;
+            match fields[..] {
+                [hour, minute] => Time::from_hms(hour, minute, 0).ok()?,
+                [hour, minute, second] => Time::from_hms(hour, minute, second).ok()?,
+                _ => return None,
+            }
+        }
+    };
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    Some(PrimitiveDateTime::new(date, clock).assume_offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_ago() {
+        let now = OffsetDateTime::from_unix_timestamp(1_000_000).unwrap();
+        let ago = |spec| parse_time(spec, now).map(|t| (now - t).whole_seconds());
+        assert_eq!(ago("90s"), Some(90));
+        assert_eq!(ago("30m"), Some(1800));
+        assert_eq!(ago("2h"), Some(7200));
+        assert_eq!(ago("1d"), Some(86_400));
+        assert_eq!(ago("2 weeks ago"), Some(1_209_600));
+        assert_eq!(ago("3mo"), Some(7_776_000));
+        assert_eq!(ago("3 fortnights"), None);
+        assert_eq!(ago("HEAD~1"), None);
+        assert_eq!(ago("abc1234"), None);
+    }
+
+    #[test]
+    fn parses_local_dates() {
+        let now = OffsetDateTime::now_utc();
+        let date = parse_time("2025-06-01", now).unwrap();
+        assert_eq!(
+            date.date(),
+            Date::from_calendar_date(2025, Month::June, 1).unwrap()
+        );
+        assert_eq!(date.time(), Time::MIDNIGHT);
+        let clock = parse_time("2025-06-01 14:30", now).unwrap();
+        assert_eq!(clock.time(), Time::from_hms(14, 30, 0).unwrap());
+        assert!(parse_time("2025-13-01", now).is_none());
+        assert!(parse_time("2025-06-01 25:00", now).is_none());
+    }
+}
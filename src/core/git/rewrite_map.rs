@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, bail};
+use git2::{Oid, Repository, Signature};
+
+use super::{
+    notes::{list_notes, write_notes},
+    revision::snapshot_chain,
+};
+
+/// Notes ref mapping each snapshot `compact` rewrote or dropped to its successor.
+pub const COMPACT_NOTES_REF: &str = "refs/notes/autosnap-compact";
+
+/// How long (seconds) an id `compact` replaced keeps resolving to its successor.
+pub const REWRITE_TTL: i64 = 90 * 86_400;
+
+/// How many rewrites to follow before giving up on a cycle.
+const MAX_HOPS: usize = 64;
+
+/// Record that each `(old, new)` snapshot was replaced by `new`, at time `now`.
+///
+/// The whole map is rewritten as one notes commit without history. Every entry points
+/// straight at the snapshot on the current chain standing for it; entries leading off
+/// the chain, for ids back on it, or older than [`REWRITE_TTL`] are dropped. Call it
+/// once HEAD points at the rewritten chain.
+///
+/// # Errors
+/// Returns an error if the notes cannot be read or written.
+pub fn record_rewrites(
+    repo: &Repository,
+    sig: &Signature<'_>,
+    rewrites: &[(Oid, Oid)],
+    now: i64,
+) -> Result<()> {
+    let mut map = read_map(repo, now)?;
+    for &(old, new) in rewrites {
+        map.insert(old, (new, now));
+    }
+    let live: HashSet<Oid> = snapshot_chain(repo)?.into_iter().collect();
+    map.retain(|old, &mut (_, recorded)| now - recorded <= REWRITE_TTL && !live.contains(old));
+
+    let mut notes = Vec::new();
+    for (&old, &(_, recorded)) in &map {
+        let Some(target) = follow(old, |id| map.get(&id).map(|&(next, _)| next)) else {
+            continue;
+        };
+        if live.contains(&target) {
+            let blob = repo
+                .blob(format!("{target} {recorded}\n").as_bytes())
+                .with_context(|| format!("failed to record rewrite of {old}"))?;
+            notes.push((old, blob));
+        }
+    }
+    write_notes(repo, COMPACT_NOTES_REF, sig, &notes)
+}
+
+/// The snapshot standing for `old` now, following rewrites across compacts. `None`
+/// when `old` was never rewritten.
+#[must_use]
+pub fn rewritten(repo: &Repository, old: Oid) -> Option<Oid> {
+    follow(old, |id| {
+        let note = repo.find_note(Some(COMPACT_NOTES_REF), id).ok()?;
+        parse_entry(note.message()?).map(|(next, _)| next)
+    })
+}
+
+/// Resolve `spec`, a full or abbreviated id of a snapshot `compact` rewrote, to the
+/// snapshot standing for it now. `None` when `spec` is not such an id.
+///
+/// # Errors
+/// Returns an error if the notes cannot be read or the abbreviation is ambiguous.
+pub fn resolve_rewritten(repo: &Repository, spec: &str) -> Result<Option<Oid>> {
+    if !(4..=40).contains(&spec.len()) || !spec.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Ok(None);
+    }
+    let prefix = spec.to_ascii_lowercase();
+    let mut found = None;
+    for (old, _) in list_notes(repo, COMPACT_NOTES_REF)? {
+        if old.to_string().starts_with(&prefix) {
+            if found.is_some_and(|id| id != old) {
+                bail!("rewritten snapshot id {spec} is ambiguous");
+            }
+            found = Some(old);
+        }
+    }
+    Ok(found.and_then(|old| rewritten(repo, old)))
+}
+
+// Follow `next` from `old` to the end of its rewrites; `None` if there are none.
+fn follow(old: Oid, mut next: impl FnMut(Oid) -> Option<Oid>) -> Option<Oid> {
+    let mut current = old;
+    for _ in 0..MAX_HOPS {
+        match next(current) {
+            Some(id) if id != current => current = id,
+            _ => break,
+        }
+    }
+    (current != old).then_some(current)
+}
+
+// Every recorded rewrite as `old -> (new, recorded at)`; entries without a time count
+// as recorded `now`.
+fn read_map(repo: &Repository, now: i64) -> Result<HashMap<Oid, (Oid, i64)>> {
+    let mut map = HashMap::new();
+    for (old, blob) in list_notes(repo, COMPACT_NOTES_REF)? {
+        let blob = repo
+            .find_blob(blob)
+            .with_context(|| format!("failed to read rewrite of {old}"))?;
+        if let Some((new, recorded)) = std::str::from_utf8(blob.content())
+            .ok()
+            .and_then(parse_entry)
+        {
+            map.insert(old, (new, recorded.unwrap_or(now)));
+        }
+    }
+    Ok(map)
+}
+
+// `<new id> [<unix time recorded>]`
+fn parse_entry(text: &str) -> Option<(Oid, Option<i64>)> {
+    let mut parts = text.split_whitespace();
+    let new = Oid::from_str(parts.next()?).ok()?;
+    Some((new, parts.next().and_then(|time| time.parse().ok())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000 * 86_400;
+
+    #[test]
+    fn follows_rewrites_across_compacts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = Signature::now("t", "t@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let [a, b, c] =
+            ["a", "b", "c"].map(|msg| repo.commit(None, &sig, &sig, msg, &tree, &[]).unwrap());
+
+        repo.set_head_detached(b).unwrap();
+        record_rewrites(&repo, &sig, &[(a, b)], NOW).unwrap();
+        repo.set_head_detached(c).unwrap();
+        record_rewrites(&repo, &sig, &[(b, c)], NOW).unwrap();
+        assert_eq!(rewritten(&repo, a), Some(c));
+        assert_eq!(rewritten(&repo, c), None);
+
+        // One notes commit, with `a` pointing straight at `c`.
+        let notes = repo
+            .find_reference(COMPACT_NOTES_REF)
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(notes.parent_count(), 0);
+        let note = repo.find_note(Some(COMPACT_NOTES_REF), a).unwrap();
+        assert_eq!(note.message(), Some(format!("{c} {NOW}\n").as_str()));
+
+        let short = &a.to_string()[..10];
+        assert_eq!(resolve_rewritten(&repo, short).unwrap(), Some(c));
+        assert_eq!(resolve_rewritten(&repo, &c.to_string()).unwrap(), None);
+        assert_eq!(resolve_rewritten(&repo, "HEAD~1").unwrap(), None);
+
+        // Entries expire, and ones whose id is back on the chain go at once.
+        repo.set_head_detached(a).unwrap();
+        record_rewrites(&repo, &sig, &[(c, a)], NOW + REWRITE_TTL).unwrap();
+        assert_eq!(rewritten(&repo, c), Some(a));
+        assert_eq!(rewritten(&repo, b), Some(a));
+        assert_eq!(rewritten(&repo, a), None);
+        record_rewrites(&repo, &sig, &[], NOW + 2 * REWRITE_TTL + 1).unwrap();
+        assert!(repo.find_reference(COMPACT_NOTES_REF).is_err());
+    }
+}
@@ -1,23 +1,29 @@
 use std::{
-    fs,
-    path::{Path, PathBuf},
+    path::Path,
     process::{Command, Stdio},
 };
 
-use anyhow::{Context, Result, anyhow, bail};
-use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
-use skim::{
-    Skim,
-    prelude::{SkimItemReader, SkimOptionsBuilder},
-};
+use anyhow::{Context, Result, bail};
+use git2::Repository;
 
-use super::repo::autosnap_dir;
+use super::{
+    extract::{ExtractReport, SkipReason, extract_tree_to_path},
+    metadata::read_metadata,
+    picker::{PreviewBase, select_commit_interactive},
+    repo::autosnap_dir,
+    revision::resolve_commit,
+};
 
 /// Open a snapshot in a subshell for exploration.
 ///
 /// # Errors
 /// Returns an error if repository access or subshell launch fails.
-pub fn snapshot_shell(repo_root: &Path, commit: Option<&str>, interactive: bool) -> Result<()> {
+pub fn snapshot_shell(
+    repo_root: &Path,
+    commit: Option<&str>,
+    interactive: bool,
+    restore_mtimes: bool,
+) -> Result<()> {
     let autosnap = autosnap_dir(repo_root);
     if !autosnap.exists() {
         bail!(".autosnap is missing; run `git autosnap init` first")
@@ -25,7 +31,7 @@ pub fn snapshot_shell(repo_root: &Path, commit: Option<&str>, interactive: bool)
 
     // If interactive mode, select commit using skim
     let commit_to_use = if interactive {
-        select_commit_interactive(&autosnap)?
+        select_commit_interactive(&autosnap, PreviewBase::Parent)?
     } else {
         commit.map(String::from)
     };
@@ -40,18 +46,16 @@ pub fn snapshot_shell(repo_root: &Path, commit: Option<&str>, interactive: bool)
 
     // Parse the commit reference
     let commit_ref = commit_to_use.as_deref().unwrap_or("HEAD");
-    let object = repo
-        .revparse_single(commit_ref)
-        .with_context(|| format!("failed to parse commit reference: {commit_ref}"))?;
-
-    let commit = object
-        .peel_to_commit()
-        .with_context(|| format!("failed to resolve {commit_ref} to a commit"))?;
+    let commit = resolve_commit(&repo, commit_ref)?;
 
     let tree = commit.tree().context("failed to get tree from commit")?;
 
     // Extract files from the tree to the temporary directory
-    extract_tree_to_path(&repo, &tree, temp_path)?;
+    let report = extract_tree_to_path(&repo, &tree, temp_path)?;
+    report_extract_issues(&report);
+    if restore_mtimes && let Some(metadata) = read_metadata(&repo, commit.id())? {
+        metadata.apply(temp_path, |_| true);
+    }
 
     // Format commit info for display
     let short_id = commit
@@ -101,181 +105,21 @@ pub fn snapshot_shell(repo_root: &Path, commit: Option<&str>, interactive: bool)
     Ok(())
 }
 
-/// Helper function to extract a git tree to a filesystem path
-fn extract_tree_to_path(repo: &Repository, tree: &Tree, base_path: &Path) -> Result<()> {
-    let mut errors = Vec::new();
-
-    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
-        let Some(entry_name) = entry.name() else {
-            errors.push(anyhow!("Entry with missing name in path: {root}"));
-            return TreeWalkResult::Ok;
-        };
-
-        let entry_path = if root.is_empty() {
-            PathBuf::from(entry_name)
-        } else {
-            PathBuf::from(root).join(entry_name)
-        };
-
-        let full_path = base_path.join(&entry_path);
-
-        match entry.kind() {
-            Some(ObjectType::Tree) => {
-                // Create directory
-                if let Err(e) = fs::create_dir_all(&full_path) {
-                    errors.push(anyhow!(
-                        "Failed to create directory {}: {}",
-                        full_path.display(),
-                        e
-                    ));
-                }
-            }
-            Some(ObjectType::Blob) => {
-                // Extract file
-                match entry.to_object(repo) {
-                    Ok(object) => {
-                        if let Some(blob) = object.as_blob() {
-                            // Ensure parent directory exists
-                            if let Some(parent) = full_path.parent()
-                                && let Err(e) = fs::create_dir_all(parent)
-                            {
-                                errors.push(anyhow!(
-                                    "Failed to create parent directory for {}: {}",
-                                    full_path.display(),
-                                    e
-                                ));
-                                return TreeWalkResult::Ok;
-                            }
-
-                            if let Err(e) = fs::write(&full_path, blob.content()) {
-                                errors.push(anyhow!(
-                                    "Failed to write file {}: {}",
-                                    full_path.display(),
-                                    e
-                                ));
-                            }
-
-                            // Try to preserve executable permissions
-                            #[cfg(unix)]
-                            {
-                                use std::os::unix::fs::PermissionsExt;
-                                let filemode = entry.filemode();
-                                // Git stores executable as 0100755 (33261 in decimal)
-                                if filemode == 33261 {
-                                    let permissions = fs::Permissions::from_mode(0o755);
-                                    let _ = fs::set_permissions(&full_path, permissions);
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        errors.push(anyhow!(
-                            "Failed to get object for {}: {}",
-                            entry_path.display(),
-                            e
-                        ));
-                    }
-                }
-            }
-            _ => {}
+/// Print entries that extraction skipped, separating expected gaps (submodules)
+/// from real problems.
+pub(crate) fn report_extract_issues(report: &ExtractReport) {
+    let problems = report.problems();
+    if !problems.is_empty() {
+        eprintln!("Warning: Some entries could not be extracted:");
+        for entry in problems {
+            eprintln!("  - {entry}");
         }
-
-        TreeWalkResult::Ok
-    })?;
-
-    if !errors.is_empty() {
-        eprintln!("Warning: Some files could not be extracted:");
-        for err in &errors {
-            eprintln!("  - {err}");
-        }
-    }
-
-    Ok(())
-}
-
-/// Interactive commit selection using skim fuzzy finder.
-pub(crate) fn select_commit_interactive(autosnap_dir: &Path) -> Result<Option<String>> {
-    // Open the autosnap repository
-    let repo = Repository::open(autosnap_dir)
-        .with_context(|| format!("failed to open autosnap repo at {}", autosnap_dir.display()))?;
-
-    // Collect commits
-    let commits = list_commits(&repo, 100)?;
-
-    if commits.is_empty() {
-        bail!("No snapshots found in .autosnap repository");
     }
-
-    // Prepare items for skim
-    let items: Vec<String> = commits
+    for entry in report
+        .skipped
         .iter()
-        .map(|c| format!("{}\t{}", c.0, c.1))
-        .collect();
-
-    let items_str = items.join("\n");
-
-    // Configure skim options
-    let options = SkimOptionsBuilder::default()
-        .height("50%".to_string())
-        .multi(false)
-        .preview(Some(String::new()))
-        .preview_window("down:3:wrap".to_string())
-        .prompt("Select snapshot> ".to_string())
-        .build()
-        .context("failed to build skim options")?;
-
-    // Create item reader
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(std::io::Cursor::new(items_str));
-
-    // Run skim
-    let skim_output = Skim::run_with(&options, Some(items)).context("skim UI failed")?;
-
-    if skim_output.is_abort {
-        return Ok(None);
-    }
-
-    if let Some(item) = skim_output.selected_items.first() {
-        let output = item.output();
-        if let Some((sha, _)) = output.split_once('\t') {
-            return Ok(Some(sha.to_string()));
-        }
-    }
-
-    Ok(None)
-}
-
-/// List commits from the repository.
-fn list_commits(repo: &Repository, limit: usize) -> Result<Vec<(String, String)>> {
-    let mut commits = Vec::new();
-    let mut revwalk = repo.revwalk()?;
-
-    // Start from HEAD
-    revwalk.push_head()?;
-
-    // Collect commits with their short SHA and message
-    for (i, oid) in revwalk.enumerate() {
-        if i >= limit {
-            break;
-        }
-
-        let oid = oid?;
-        let commit = repo.find_commit(oid)?;
-
-        let short_id = repo.find_object(oid, None)?.short_id()?;
-        let short_id_str = short_id.as_str().map_or_else(
-            || {
-                eprintln!("Warning: Could not convert short ID for commit {oid}");
-                format!("{oid:.7}")
-            },
-            std::string::ToString::to_string,
-        );
-
-        let message = commit.message().unwrap_or("<no message>");
-        let first_line = message.lines().next().unwrap_or(message).to_string();
-
-        commits.push((short_id_str, first_line));
+        .filter(|s| matches!(s.reason, SkipReason::Submodule { .. }))
+    {
+        eprintln!("Note: {entry}");
     }
-
-    Ok(commits)
 }
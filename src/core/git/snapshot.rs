@@ -2,15 +2,71 @@ use std::path::Path;
 
 use anyhow::{Context, Result, bail};
 use git2::{Commit, Repository, Signature, Tree};
+use tracing::warn;
+
+use super::{
+    index::write_tree_with_retries,
+    metadata::{SnapshotMetadata, write_metadata},
+    ops_lock::acquire_ops_lock,
+    repo::autosnap_dir,
+    staged::{capture_index_tree, record_staged_tree, staged_tree},
+};
+use crate::config::AutosnapConfig;
+
+/// Trailer key recording what took a snapshot.
+const TRIGGER_TRAILER: &str = "Autosnap-Trigger: ";
+
+/// What caused a snapshot; recorded as an `Autosnap-Trigger:` trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotTrigger {
+    /// The watcher saw file changes.
+    Watch,
+    /// `git autosnap once`.
+    Manual,
+    /// SIGUSR1 to the watcher.
+    Signal,
+    /// The watcher's final snapshot on SIGTERM/SIGINT.
+    Shutdown,
+    /// Before the watcher re-execs for an ignore file change.
+    Reload,
+    /// Before the watcher re-execs an updated binary (SIGUSR2).
+    Update,
+}
 
-use super::{index::write_tree_with_retries, ops_lock::acquire_ops_lock, repo::autosnap_dir};
+impl SnapshotTrigger {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Watch => "watch",
+            Self::Manual => "manual",
+            Self::Signal => "signal",
+            Self::Shutdown => "shutdown",
+            Self::Reload => "reload",
+            Self::Update => "update",
+        }
+    }
+}
+
+/// The trigger recorded in a snapshot message, if any.
+pub(crate) fn trigger_of(message: &str) -> Option<&str> {
+    message
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix(TRIGGER_TRAILER))
+        .map(str::trim)
+}
 
 /// Take a single snapshot of the working tree and commit it into `.autosnap`.
-/// Returns the short hash of the created commit, or `None` if no changes were made.
+/// Returns the short hash of the created commit, or `None` if neither the working
+/// tree nor the main repo's index changed.
 ///
 /// # Errors
 /// Returns an error if repository operations fail (building index, creating commit, etc.).
-pub fn snapshot_once(repo_root: &Path, message: Option<&str>) -> Result<Option<String>> {
+pub fn snapshot_once(
+    repo_root: &Path,
+    message: Option<&str>,
+    trigger: SnapshotTrigger,
+) -> Result<Option<String>> {
     let autosnap = autosnap_dir(repo_root);
     if !autosnap.exists() {
         bail!(".autosnap is missing; run `git autosnap init` first")
@@ -32,12 +88,23 @@ pub fn snapshot_once(repo_root: &Path, message: Option<&str>) -> Result<Option<S
         .find_tree(tree_id)
         .context("failed to find written tree")?;
 
-    // Check if identical to HEAD to avoid duplicate commits
+    // Keep the main repo's staging area too, so `restore --staged` can bring it back.
+    // A failure here must not lose the snapshot, so it is only logged.
+    let staged = capture_index_tree(&repo, repo_root)
+        .inspect_err(|e| warn!(error = ?e, "failed to capture staged index"))
+        .ok();
+
+    // Check if identical to HEAD to avoid duplicate commits. An index-only change
+    // (`git add -p`) still gets a snapshot of its own.
     if let Some(prev_tree) = head_tree(&repo)?
         && prev_tree.id() == tree.id()
     {
-        // No changes; do not create a new commit
-        return Ok(None);
+        let head = repo.head().ok().and_then(|head| head.target());
+        let prev_staged = head.and_then(|id| staged_tree(&repo, id)).map(|t| t.id());
+        if staged.is_none() || staged == prev_staged {
+            // No changes; do not create a new commit
+            return Ok(None);
+        }
     }
 
     // Create author/committer signature from main repo config
@@ -46,10 +113,11 @@ pub fn snapshot_once(repo_root: &Path, message: Option<&str>) -> Result<Option<S
     // Commit message
     let branch = current_branch_name(repo_root).unwrap_or_else(|| "DETACHED".to_string());
     let ts = iso8601_now_with_offset();
-    let msg = message.map_or_else(
+    let subject = message.map_or_else(
         || format!("AUTOSNAP[{branch}] {ts}"),
         |custom_msg| format!("AUTOSNAP[{branch}] {ts}: {custom_msg}"),
     );
+    let msg = format!("{subject}\n\n{TRIGGER_TRAILER}{}\n", trigger.as_str());
 
     // Determine parents (if any)
     let parents: Vec<Commit> = match repo.head() {
@@ -72,6 +140,22 @@ pub fn snapshot_once(repo_root: &Path, message: Option<&str>) -> Result<Option<S
         .commit(Some("HEAD"), &sig, &sig, &msg, &tree, &parent_refs)
         .context("failed to create autosnap commit")?;
 
+    // Record mtimes (and optionally permissions) that the tree itself cannot hold.
+    // A failure here must not lose the snapshot, so it is only logged.
+    let cfg = AutosnapConfig::load(repo_root).unwrap_or_default();
+    if cfg.record_mtimes
+        && let Err(e) = SnapshotMetadata::collect(&tree, repo_root, cfg.record_permissions)
+            .and_then(|metadata| write_metadata(&repo, &sig, oid, &metadata))
+    {
+        warn!(error = ?e, "failed to record snapshot metadata");
+    }
+
+    if let Some(staged) = staged
+        && let Err(e) = record_staged_tree(&repo, oid, staged)
+    {
+        warn!(error = ?e, "failed to record staged index");
+    }
+
     // Return short id for script-friendliness per implementation plan
     if let Ok(short) = repo.find_object(oid, None).and_then(|o| o.short_id())
         && let Some(s) = short.as_str()
@@ -97,7 +181,7 @@ fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>> {
     }
 }
 
-fn signature_from_main(repo_root: &Path) -> Result<Signature<'static>> {
+pub(crate) fn signature_from_main(repo_root: &Path) -> Result<Signature<'static>> {
     let main_repo = Repository::discover(repo_root)?;
     let cfg = main_repo.config()?;
     let name = cfg
@@ -125,3 +209,17 @@ fn iso8601_now_with_offset() -> String {
     let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
     now.format(&Rfc3339).unwrap_or_else(|_| now.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_is_read_from_the_trailer() {
+        assert_eq!(
+            trigger_of("AUTOSNAP[main] 2025-06-01T10:00:00Z\n\nAutosnap-Trigger: watch\n"),
+            Some("watch")
+        );
+        assert_eq!(trigger_of("AUTOSNAP[main] 2025-06-01T10:00:00Z"), None);
+    }
+}
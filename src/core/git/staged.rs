@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use git2::{Index, IndexEntry, IndexTime, ObjectType, Oid, Repository, Tree};
+
+/// Ref namespace mapping a snapshot commit to the tree of the main repo's index.
+pub const STAGED_REF_PREFIX: &str = "refs/autosnap/staged/";
+
+const MODE_GITLINK: u32 = 0o160_000;
+
+fn staged_ref_name(commit: Oid) -> String {
+    format!("{STAGED_REF_PREFIX}{commit}")
+}
+
+/// Build a tree in the autosnap repo from the main repository's index.
+///
+/// Staged blobs are copied into the autosnap object store so the tree stays readable
+/// even after the main repo's index moves on. Conflicted (higher-stage) entries are
+/// skipped.
+///
+/// # Errors
+/// Returns an error if the main index cannot be read or objects cannot be copied.
+pub fn capture_index_tree(repo: &Repository, repo_root: &Path) -> Result<Oid> {
+    let main_repo = Repository::discover(repo_root).context("failed to open main repository")?;
+    let main_index = main_repo
+        .index()
+        .context("failed to open main repository index")?;
+
+    let mut staged = Index::new().context("failed to create in-memory index")?;
+    for entry in main_index.iter() {
+        let stage = (entry.flags >> 12) & 0x3;
+        if stage != 0 || entry.path.starts_with(b".autosnap/") {
+            continue;
+        }
+        if entry.mode != MODE_GITLINK {
+            copy_blob(&main_repo, repo, entry.id)?;
+        }
+        staged
+            .add(&entry)
+            .with_context(|| format!("failed to stage {}", String::from_utf8_lossy(&entry.path)))?;
+    }
+
+    staged
+        .write_tree_to(repo)
+        .context("failed to write staged tree")
+}
+
+/// Record `staged_tree` as the index state of `commit`.
+///
+/// It is recorded even when it matches the snapshot: the snapshot tree also holds
+/// untracked files, so only the index tree says which paths were tracked. Trees are
+/// shared between snapshots, so an unchanged index costs one ref.
+///
+/// # Errors
+/// Returns an error if the ref cannot be created.
+pub fn record_staged_tree(repo: &Repository, commit: Oid, staged_tree: Oid) -> Result<()> {
+    repo.reference(
+        &staged_ref_name(commit),
+        staged_tree,
+        true,
+        "autosnap: record staged index",
+    )
+    .with_context(|| format!("failed to record staged tree for {commit}"))?;
+    Ok(())
+}
+
+/// The staged tree recorded for `commit`, or `None` when none was recorded (the
+/// index could not be read, or the snapshot predates staged recording).
+#[must_use]
+pub fn staged_tree(repo: &Repository, commit: Oid) -> Option<Tree<'_>> {
+    repo.find_reference(&staged_ref_name(commit))
+        .ok()?
+        .peel_to_tree()
+        .ok()
+}
+
+/// Re-point the staged tree recorded for `from` at `to` (history rewrites).
+///
+/// # Errors
+/// Returns an error if the refs cannot be updated.
+pub fn move_staged_tree(repo: &Repository, from: Oid, to: Oid) -> Result<()> {
+    let Ok(mut reference) = repo.find_reference(&staged_ref_name(from)) else {
+        return Ok(());
+    };
+    if from != to {
+        reference
+            .rename(&staged_ref_name(to), true, "autosnap: rewrite staged ref")
+            .with_context(|| format!("failed to move staged tree from {from} to {to}"))?;
+    }
+    Ok(())
+}
+
+/// Forget the staged tree recorded for `commit`.
+///
+/// # Errors
+/// Returns an error if the ref exists but cannot be deleted.
+pub fn drop_staged_tree(repo: &Repository, commit: Oid) -> Result<()> {
+    if let Ok(mut reference) = repo.find_reference(&staged_ref_name(commit)) {
+        reference
+            .delete()
+            .with_context(|| format!("failed to delete staged tree for {commit}"))?;
+    }
+    Ok(())
+}
+
+/// Set the main repository's index entries for exactly `paths` to those of `tree`,
+/// which lives in the autosnap repo: paths `tree` lacks are unstaged. Returns the
+/// number of entries written.
+///
+/// # Errors
+/// Returns an error if objects cannot be copied or the index cannot be written.
+pub fn write_tree_to_main_index(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    repo_root: &Path,
+    paths: &[&str],
+) -> Result<usize> {
+    let main_repo = Repository::discover(repo_root).context("failed to open main repository")?;
+    let mut index = main_repo
+        .index()
+        .context("failed to open main repository index")?;
+
+    let mut written = 0;
+    for path in paths {
+        let entry = tree
+            .get_path(Path::new(path))
+            .ok()
+            .filter(|entry| entry.kind() != Some(ObjectType::Tree));
+        let Some(entry) = entry else {
+            if index.get_path(Path::new(path), 0).is_some() {
+                index
+                    .remove_path(Path::new(path))
+                    .with_context(|| format!("failed to unstage {path}"))?;
+            }
+            continue;
+        };
+        let mode = entry.filemode().cast_unsigned();
+        if mode != MODE_GITLINK {
+            copy_blob(repo, &main_repo, entry.id())?;
+        }
+        index
+            .add(&index_entry(path, entry.id(), mode))
+            .with_context(|| format!("failed to stage {path}"))?;
+        written += 1;
+    }
+
+    index
+        .write()
+        .context("failed to write main repository index")?;
+    Ok(written)
+}
+
+fn index_entry(path: &str, id: Oid, mode: u32) -> IndexEntry {
+    IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode,
+        uid: 0,
+        gid: 0,
+        file_size: 0,
+        id,
+        flags: u16::try_from(path.len()).map_or(0xfff, |len| len.min(0xfff)),
+        flags_extended: 0,
+        path: path.as_bytes().to_vec(),
+    }
+}
+
+fn copy_blob(from: &Repository, to: &Repository, id: Oid) -> Result<()> {
+    let to_odb = to.odb().context("failed to open object database")?;
+    if to_odb.exists(id) {
+        return Ok(());
+    }
+    let blob = from
+        .find_blob(id)
+        .with_context(|| format!("failed to read blob {id}"))?;
+    to_odb
+        .write(ObjectType::Blob, blob.content())
+        .with_context(|| format!("failed to copy blob {id}"))?;
+    Ok(())
+}
@@ -38,6 +38,13 @@ pub fn uninstall(repo_root: &Path) -> Result<()> {
     } else {
         println!("Nothing to remove at {}", dir.display());
     }
+    // Bisect state lives in the main git dir; a plain directory may have none.
+    if let Ok(state) = crate::core::git::repo::state_dir(repo_root)
+        && state.exists()
+    {
+        fs::remove_dir_all(&state)?;
+        println!("Removed {}", state.display());
+    }
     Ok(())
 }
 
@@ -2,11 +2,11 @@ use std::{
     collections::HashSet,
     path::{Path, PathBuf},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, AtomicU8, Ordering},
         mpsc::{Receiver, SyncSender, sync_channel},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, anyhow};
@@ -17,7 +17,10 @@ use watchexec_filterer_ignore::IgnoreFilterer;
 
 use crate::{
     config::AutosnapConfig,
-    core::{git, runtime::process},
+    core::{
+        git::{self, SnapshotTrigger},
+        runtime::process,
+    },
     logging::init::flush_logs,
 };
 
@@ -77,7 +80,7 @@ pub fn start_foreground(repo_root: &Path, cfg: &AutosnapConfig) -> Result<()> {
         .build()
         .context("failed to build tokio runtime")?;
 
-    rt.block_on(run_watcher(repo_root, cfg.debounce_ms))
+    rt.block_on(run_watcher(repo_root, cfg))
 }
 
 // Exit actions to perform after the watcher stops.
@@ -91,6 +94,9 @@ enum ExitAction {
     BinaryUpdateExec = 3,
 }
 
+/// How often the watcher checks the store against `autosnap.max-store-size`.
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 /// Shared watcher state used by handlers.
 struct WatcherState {
     repo_root: PathBuf,
@@ -100,6 +106,11 @@ struct WatcherState {
     binary_update_tx: SyncSender<bool>,
     original_binary_metadata: Option<std::fs::Metadata>,
     snapshot_in_progress: Arc<AtomicBool>,
+    max_store_size: Option<u64>,
+    gc_backend: git::GcBackend,
+    gc_prune_grace: i64,
+    // When the store size was last checked against `max_store_size`.
+    budget_checked_at: Arc<Mutex<Option<Instant>>>,
 }
 
 /// Control flow from a handler.
@@ -134,7 +145,8 @@ fn load_exit_action(exit_action: &Arc<AtomicU8>) -> ExitAction {
 
 /// Run the watcher async task. Not Send; runs on current-thread runtime.
 #[allow(clippy::future_not_send)]
-async fn run_watcher(repo_root: &Path, debounce_ms: u64) -> Result<()> {
+async fn run_watcher(repo_root: &Path, cfg: &AutosnapConfig) -> Result<()> {
+    let debounce_ms = cfg.debounce_ms;
     // Build git-aware ignore filterer and tracked ignore files
     let (filterer, tracked_ignore_files) = build_filterer_and_ignores(repo_root)
         .await
@@ -146,7 +158,7 @@ async fn run_watcher(repo_root: &Path, debounce_ms: u64) -> Result<()> {
     );
 
     // Build shared state and binary update channel
-    let (state, binary_update_rx) = build_state(repo_root, tracked_ignore_files);
+    let (state, binary_update_rx) = build_state(repo_root, tracked_ignore_files, cfg);
 
     // Build watchexec config and start
     // Clone needed: state is shared across async closures and signal handlers
@@ -170,6 +182,7 @@ async fn run_watcher(repo_root: &Path, debounce_ms: u64) -> Result<()> {
 fn build_state(
     repo_root: &Path,
     tracked_ignore_files: HashSet<PathBuf>,
+    cfg: &AutosnapConfig,
 ) -> (Arc<WatcherState>, Receiver<bool>) {
     let (binary_update_tx, binary_update_rx) = sync_channel::<bool>(1);
     let exit_action = Arc::new(AtomicU8::new(ExitAction::None as u8));
@@ -188,6 +201,10 @@ fn build_state(
         binary_update_tx,
         original_binary_metadata,
         snapshot_in_progress,
+        max_store_size: cfg.max_store_size,
+        gc_backend: cfg.gc_backend,
+        gc_prune_grace: cfg.gc_prune_grace,
+        budget_checked_at: Arc::new(Mutex::new(None)),
     });
 
     (state, binary_update_rx)
@@ -247,7 +264,12 @@ fn finalize_exit_actions(
     let action = load_exit_action(exit_action);
 
     if (action as u8) >= (ExitAction::Snapshot as u8) {
-        match git::snapshot_once(repo_root, None) {
+        let trigger = match action {
+            ExitAction::ReloadExec => SnapshotTrigger::Reload,
+            ExitAction::BinaryUpdateExec => SnapshotTrigger::Update,
+            ExitAction::None | ExitAction::Snapshot => SnapshotTrigger::Shutdown,
+        };
+        match git::snapshot_once(repo_root, None, trigger) {
             Ok(Some(hash)) => {
                 info!(
                     hash = hash,
@@ -363,7 +385,7 @@ fn handle_signals(signals: &[watchexec_signals::Signal], state: &WatcherState) -
                     let root = state.repo_root.clone();
                     let in_progress = state.snapshot_in_progress.clone();
                     tokio::task::spawn_blocking(move || {
-                        match git::snapshot_once(&root, None) {
+                        match git::snapshot_once(&root, None, SnapshotTrigger::Signal) {
                             Ok(Some(hash)) => {
                                 info!(
                                     hash = hash,
@@ -417,10 +439,17 @@ fn handle_fs_events(paths: &[(&Path, Option<&FileType>)], state: &WatcherState)
             // Clones required: moving values into spawned blocking task
             let root = state.repo_root.clone();
             let in_progress = state.snapshot_in_progress.clone();
+            let budget = state.max_store_size;
+            let gc = state.gc_backend;
+            let prune_grace = state.gc_prune_grace;
+            let checked_at = state.budget_checked_at.clone();
             tokio::task::spawn_blocking(move || {
-                match git::snapshot_once(&root, None) {
+                match git::snapshot_once(&root, None, SnapshotTrigger::Watch) {
                     Ok(Some(hash)) => {
                         info!(hash = hash, event = "snapshot_created", "snapshot created");
+                        if let Some(budget) = budget {
+                            enforce_store_budget(&root, budget, gc, prune_grace, &checked_at);
+                        }
                     }
                     Ok(None) => {
                         info!(event = "snapshot_skipped", "no changes to snapshot");
@@ -439,6 +468,50 @@ fn handle_fs_events(paths: &[(&Path, Option<&FileType>)], state: &WatcherState)
     }
 }
 
+// Fold the oldest snapshots when the store has outgrown `budget`, at most once per
+// `BUDGET_CHECK_INTERVAL`.
+fn enforce_store_budget(
+    root: &Path,
+    budget: u64,
+    gc: git::GcBackend,
+    prune_grace: i64,
+    checked_at: &Mutex<Option<Instant>>,
+) {
+    {
+        let Ok(mut last) = checked_at.lock() else {
+            return;
+        };
+        if last.is_some_and(|at| at.elapsed() < BUDGET_CHECK_INTERVAL) {
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+    match git::compact_to_budget(root, budget, gc, prune_grace) {
+        Ok(None) => {}
+        Ok(Some(result)) => {
+            info!(
+                event = "store_budget_compacted",
+                before = result.before_commits,
+                after = result.after_commits,
+                reclaimed = result.bytes_reclaimed,
+                store = result.store_bytes,
+                "compacted snapshots to fit autosnap.max-store-size"
+            );
+            if result.over_budget {
+                warn!(
+                    event = "store_over_budget",
+                    store = result.store_bytes,
+                    budget,
+                    "store still exceeds autosnap.max-store-size; only pinned and newest snapshots are left"
+                );
+            }
+        }
+        Err(e) => {
+            error!(error = ?e, event = "store_budget_failed", "store budget compact failed");
+        }
+    }
+}
+
 fn request_binary_update(state: &WatcherState) {
     // Defer final snapshot and exec to after the watcher stops and
     // choose the highest-precedence action: binary update exec.
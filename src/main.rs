@@ -2,7 +2,6 @@
 compile_error!("git-autosnap is Unix-only; build requires cfg(unix).");
 
 use anyhow::Result;
-use clap::Parser;
 use git_autosnap::{
     cli::{Cli, Commands},
     core::git,
@@ -11,7 +10,7 @@ use git_autosnap::{
 };
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_args();
 
     // Initialize tracing based on the command
     // Start command needs file logging for the watcher
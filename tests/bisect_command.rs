@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn init_repo(dir: &Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+    Command::new("git")
+        .args(["config", "user.name", "Test User"])
+        .current_dir(dir)
+        .assert()
+        .success();
+    Command::new("git")
+        .args(["config", "user.email", "test@example.com"])
+        .current_dir(dir)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn snapshot(dir: &Path, state: &str, step: usize) -> String {
+    std::fs::write(dir.join("state.txt"), state).unwrap();
+    std::fs::write(dir.join("step.txt"), step.to_string()).unwrap();
+    let output = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+#[test]
+fn test_bisect_run_finds_first_bad_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    init_repo(temp_dir.path());
+
+    let mut commits = Vec::new();
+    for step in 0..8 {
+        let state = if step < 5 { "pass" } else { "fail" };
+        commits.push(snapshot(temp_dir.path(), state, step));
+    }
+
+    git_autosnap_cmd()
+        .args(["bisect", "start", &commits[7], &commits[0]])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Bisecting:"));
+
+    git_autosnap_cmd()
+        .args(["bisect", "run", "grep", "-q", "pass", "state.txt"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!("{} is the first bad snapshot", commits[5]))
+                .and(predicate::str::contains("M\tstate.txt")),
+        );
+
+    git_autosnap_cmd()
+        .args(["bisect", "reset"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert!(!temp_dir.path().join(".git/autosnap/BISECT_LOG").exists());
+}
+
+#[test]
+fn test_bisect_manual_marks() {
+    let temp_dir = TempDir::new().unwrap();
+    init_repo(temp_dir.path());
+
+    let mut commits = Vec::new();
+    for step in 0..3 {
+        let state = if step < 1 { "pass" } else { "fail" };
+        commits.push(snapshot(temp_dir.path(), state, step));
+    }
+
+    git_autosnap_cmd()
+        .args(["bisect", "start", &commits[2], &commits[0]])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(&commits[1]));
+
+    // The only candidate is extracted for inspection, outside the snapshot store.
+    let extracted = temp_dir.path().join(".git/autosnap/bisect/state.txt");
+    assert_eq!(std::fs::read_to_string(extracted).unwrap(), "fail");
+
+    git_autosnap_cmd()
+        .args(["bisect", "bad"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{} is the first bad snapshot",
+            commits[1]
+        )));
+}
+
+#[test]
+fn test_bisect_rejects_inverted_range() {
+    let temp_dir = TempDir::new().unwrap();
+    init_repo(temp_dir.path());
+
+    let first = snapshot(temp_dir.path(), "pass", 0);
+    let second = snapshot(temp_dir.path(), "fail", 1);
+
+    git_autosnap_cmd()
+        .args(["bisect", "start", &first, &second])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not older than the first bad"));
+}
+
+#[test]
+fn test_bisect_mark_without_start() {
+    let temp_dir = TempDir::new().unwrap();
+    init_repo(temp_dir.path());
+    snapshot(temp_dir.path(), "pass", 0);
+
+    git_autosnap_cmd()
+        .args(["bisect", "good"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no bisection in progress"));
+}
@@ -0,0 +1,93 @@
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn snapshot(root: &std::path::Path) {
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+}
+
+fn setup_repo(root: &std::path::Path) {
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+    std::fs::create_dir(root.join("src")).unwrap();
+    std::fs::write(root.join("a.txt"), "first\n").unwrap();
+    std::fs::write(root.join("src/b.txt"), "b\n").unwrap();
+    snapshot(root);
+    std::fs::write(root.join("a.txt"), "second\n").unwrap();
+    snapshot(root);
+}
+
+#[test]
+fn test_cat_prints_files_from_a_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+    std::fs::write(root.join("a.txt"), "unsaved\n").unwrap();
+
+    git_autosnap_cmd()
+        .args(["cat", "HEAD", "a.txt", "src/b.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("second\nb\n");
+
+    git_autosnap_cmd()
+        .args(["cat", "HEAD~1", "./a.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("first\n");
+}
+
+#[test]
+fn test_cat_rejects_missing_paths_and_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+
+    git_autosnap_cmd()
+        .args(["cat", "HEAD", "a.txt", "missing.txt"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "missing.txt is not in snapshot HEAD",
+        ));
+
+    git_autosnap_cmd()
+        .args(["cat", "HEAD", "src"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("src is not a file"));
+
+    git_autosnap_cmd()
+        .args(["cat", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use -i to pick files"));
+}
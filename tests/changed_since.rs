@@ -0,0 +1,96 @@
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn setup_repo(root: &std::path::Path) {
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+    std::fs::write(root.join("a.txt"), "a\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b\n").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_changed_since_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+
+    git_autosnap_cmd()
+        .args(["changed-since", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("");
+
+    std::fs::write(root.join("a.txt"), "changed\n").unwrap();
+    std::fs::remove_file(root.join("b.txt")).unwrap();
+    std::fs::write(root.join("c.txt"), "c\n").unwrap();
+    git_autosnap_cmd()
+        .args(["changed-since", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("M\ta.txt\nD\tb.txt\nA\tc.txt\n");
+
+    git_autosnap_cmd()
+        .args(["changed-since", "HEAD", "c.txt"])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("A\tc.txt\n");
+}
+
+#[test]
+fn test_changed_since_time() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+    std::fs::write(root.join("a.txt"), "changed\n").unwrap();
+
+    // The latest snapshot at or before now.
+    git_autosnap_cmd()
+        .args(["changed-since", "0s"])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("M\ta.txt\n");
+
+    // Before the first snapshot: falls back to the oldest one.
+    git_autosnap_cmd()
+        .args(["changed-since", "1d"])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("M\ta.txt\n")
+        .stderr(predicate::str::contains("no snapshot before 1d"));
+
+    git_autosnap_cmd()
+        .args(["changed-since", "not-a-snapshot"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to parse commit reference"));
+}
@@ -22,6 +22,8 @@ async fn setup_repo_with_seed(
 ) -> Result<()> {
     exec_bash(container, "mkdir -p /repo && git init /repo").await?;
     exec_in(container, "/repo", "git autosnap init").await?;
+    // The seeded objects are fresh; let gc prune them as soon as they are dropped.
+    exec_in(container, "/repo", "git config autosnap.gc.prune-grace 0").await?;
     exec_in(container, "/repo", seed_script).await?;
     Ok(())
 }
@@ -163,3 +165,144 @@ make_commit 5 v5 "AUTOSNAP keep 5"
 
     Ok(())
 }
+
+#[tokio::test]
+async fn tiered_retention_thins_history_and_keeps_pins() -> Result<()> {
+    let image = GenericImage::new("git-autosnap-test", "latest")
+        .with_wait_for(WaitFor::message_on_stdout("ready"));
+    let container = image.start().await?;
+
+    let seed_script = r#"bash -lc '
+set -euo pipefail
+make_commit() {
+  minutes="$1"
+  content="$2"
+  msg="$3"
+  blob=$(printf "%s" "$content" | git --git-dir=.autosnap hash-object -w --stdin)
+  tree=$(printf "100644 blob %s\tstate.txt\n" "$blob" | git --git-dir=.autosnap mktree)
+  date="@$(( $(date +%s) - minutes * 60 )) +0000"
+  if git --git-dir=.autosnap rev-parse -q --verify HEAD >/dev/null 2>&1; then
+    parent=$(git --git-dir=.autosnap rev-parse HEAD)
+    oid=$(printf "%s\n" "$msg" | GIT_AUTHOR_NAME=Test GIT_AUTHOR_EMAIL=test@example.com GIT_COMMITTER_NAME=Test GIT_COMMITTER_EMAIL=test@example.com GIT_AUTHOR_DATE="$date" GIT_COMMITTER_DATE="$date" git --git-dir=.autosnap commit-tree "$tree" -p "$parent")
+  else
+    oid=$(printf "%s\n" "$msg" | GIT_AUTHOR_NAME=Test GIT_AUTHOR_EMAIL=test@example.com GIT_COMMITTER_NAME=Test GIT_COMMITTER_EMAIL=test@example.com GIT_AUTHOR_DATE="$date" GIT_COMMITTER_DATE="$date" git --git-dir=.autosnap commit-tree "$tree")
+  fi
+  git --git-dir=.autosnap update-ref HEAD "$oid"
+}
+make_commit 14400 v14400 "AUTOSNAP pinned 240h"
+git --git-dir=.autosnap update-ref refs/autosnap/pins/$(git --git-dir=.autosnap rev-parse HEAD) HEAD
+make_commit 12000 v12000 "AUTOSNAP old 200h"
+make_commit 1802 v1802 "AUTOSNAP thin 30h"
+make_commit 1801 v1801 "AUTOSNAP thin 30h"
+make_commit 1800 v1800 "AUTOSNAP thin 30h"
+make_commit 60 v60 "AUTOSNAP keep 1h"
+make_commit 0 v0 "AUTOSNAP keep 0h"
+git config autosnap.compact.retention "2h:all,3d:1w"
+'"#;
+    setup_repo_with_seed(&container, seed_script).await?;
+
+    exec_in(&container, "/repo", "git autosnap compact").await?;
+
+    let subjects = exec_in(
+        &container,
+        "/repo",
+        "git --git-dir=.autosnap log --reverse --format=%s",
+    )
+    .await?;
+    let subjects: Vec<&str> = subjects.lines().collect();
+    assert_eq!(subjects.len(), 5, "unexpected chain: {subjects:?}");
+    assert_eq!(
+        subjects,
+        [
+            "AUTOSNAP pinned 240h",
+            "AUTOSNAP_COMPACT_BASELINE",
+            "AUTOSNAP thin 30h",
+            "AUTOSNAP keep 1h",
+            "AUTOSNAP keep 0h",
+        ]
+    );
+
+    let pins = exec_in(
+        &container,
+        "/repo",
+        "git --git-dir=.autosnap for-each-ref --count=1 --format=%(subject) refs/autosnap/pins/",
+    )
+    .await?;
+    assert_eq!(pins.trim(), "AUTOSNAP pinned 240h");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dry_run_lists_fates_and_old_ids_resolve_after_compact() -> Result<()> {
+    let image = GenericImage::new("git-autosnap-test", "latest")
+        .with_wait_for(WaitFor::message_on_stdout("ready"));
+    let container = image.start().await?;
+
+    let seed_script = r#"bash -lc '
+set -euo pipefail
+make_commit() {
+  days="$1"
+  content="$2"
+  msg="$3"
+  blob=$(printf "%s" "$content" | git --git-dir=.autosnap hash-object -w --stdin)
+  tree=$(printf "100644 blob %s\tstate.txt\n" "$blob" | git --git-dir=.autosnap mktree)
+  date=$(date -u -d "$days days ago" "+%Y-%m-%dT12:00:00Z")
+  if git --git-dir=.autosnap rev-parse -q --verify HEAD >/dev/null 2>&1; then
+    parent=$(git --git-dir=.autosnap rev-parse HEAD)
+    oid=$(printf "%s\n" "$msg" | GIT_AUTHOR_NAME=Test GIT_AUTHOR_EMAIL=test@example.com GIT_COMMITTER_NAME=Test GIT_COMMITTER_EMAIL=test@example.com GIT_AUTHOR_DATE="$date" GIT_COMMITTER_DATE="$date" git --git-dir=.autosnap commit-tree "$tree" -p "$parent")
+  else
+    oid=$(printf "%s\n" "$msg" | GIT_AUTHOR_NAME=Test GIT_AUTHOR_EMAIL=test@example.com GIT_COMMITTER_NAME=Test GIT_COMMITTER_EMAIL=test@example.com GIT_AUTHOR_DATE="$date" GIT_COMMITTER_DATE="$date" git --git-dir=.autosnap commit-tree "$tree")
+  fi
+  git --git-dir=.autosnap update-ref HEAD "$oid"
+}
+make_commit 120 v120 "AUTOSNAP old 120"
+make_commit 80 v80 "AUTOSNAP old 80"
+make_commit 40 v40 "AUTOSNAP old 40"
+make_commit 10 v10 "AUTOSNAP keep 10"
+make_commit 2 v2 "AUTOSNAP keep 2"
+'"#;
+    setup_repo_with_seed(&container, seed_script).await?;
+
+    let preview = exec_in(
+        &container,
+        "/repo",
+        "git autosnap compact --days 30 --dry-run",
+    )
+    .await?;
+    let fates: Vec<&str> = preview
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|word| ["keep", "baseline", "fold", "thin"].contains(word))
+        .collect();
+    assert_eq!(fates, ["fold", "fold", "baseline", "keep", "keep"]);
+    let count = read_usize(&container, "git --git-dir=.autosnap rev-list --count HEAD").await?;
+    assert_eq!(count, 5, "dry run must not rewrite");
+
+    let old_ids = exec_in(
+        &container,
+        "/repo",
+        "git --git-dir=.autosnap log --format=%h",
+    )
+    .await?;
+    let old_ids: Vec<String> = old_ids.lines().map(str::to_string).collect();
+    exec_in(&container, "/repo", "git autosnap compact --days 30").await?;
+
+    // The old tip resolves to its replay; a folded snapshot to the baseline.
+    let head = exec_in(
+        &container,
+        "/repo",
+        &format!("git autosnap cat {} state.txt", old_ids[0]),
+    )
+    .await?;
+    assert_eq!(head.trim(), "v2");
+    let folded = exec_in(
+        &container,
+        "/repo",
+        &format!("git autosnap cat {} state.txt", old_ids[3]),
+    )
+    .await?;
+    assert_eq!(folded.trim(), "v40");
+
+    Ok(())
+}
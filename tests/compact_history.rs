@@ -0,0 +1,374 @@
+use std::path::Path;
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+fn init_repo(dir: &Path) {
+    git(dir, &["init", "-q"]);
+    git(dir, &["config", "user.name", "Test User"]);
+    git(dir, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+// Append a snapshot of `state.txt` dated `days` ago to the autosnap chain.
+fn seed(dir: &Path, days: i64, content: &str) -> String {
+    seed_bytes(dir, days, content, content.as_bytes())
+}
+
+fn seed_bytes(dir: &Path, days: i64, subject: &str, content: &[u8]) -> String {
+    let autosnap = dir.join(".autosnap");
+    let autosnap = autosnap.to_str().unwrap();
+    let blob = Command::new("git")
+        .args(["--git-dir", autosnap, "hash-object", "-w", "--stdin"])
+        .write_stdin(content)
+        .output()
+        .unwrap();
+    let blob = String::from_utf8_lossy(&blob.stdout).trim().to_string();
+    let tree = Command::new("git")
+        .args(["--git-dir", autosnap, "mktree"])
+        .write_stdin(format!("100644 blob {blob}\tstate.txt\n"))
+        .output()
+        .unwrap();
+    let tree = String::from_utf8_lossy(&tree.stdout).trim().to_string();
+
+    let date = format!("{} +0000", now() - days * 86_400);
+    let mut args = vec!["--git-dir", autosnap, "commit-tree", tree.as_str()];
+    let parent = Command::new("git")
+        .args(["--git-dir", autosnap, "rev-parse", "-q", "--verify", "HEAD"])
+        .output()
+        .unwrap();
+    let parent = String::from_utf8_lossy(&parent.stdout).trim().to_string();
+    if !parent.is_empty() {
+        args.extend(["-p", parent.as_str()]);
+    }
+    let commit = Command::new("git")
+        .args(&args)
+        .env("GIT_AUTHOR_NAME", "Test User")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test User")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .write_stdin(format!("AUTOSNAP {subject}\n"))
+        .output()
+        .unwrap();
+    let commit = String::from_utf8_lossy(&commit.stdout).trim().to_string();
+    git(dir, &["--git-dir", autosnap, "update-ref", "HEAD", &commit]);
+    commit
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .cast_signed()
+}
+
+// Bytes zlib cannot shrink, so each snapshot costs its full size in the store.
+fn noise(len: usize, mut state: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()[7]
+        })
+        .collect()
+}
+
+fn seed_history(dir: &Path) -> Vec<String> {
+    [
+        (120, "v120"),
+        (80, "v80"),
+        (40, "v40"),
+        (10, "v10"),
+        (2, "v2"),
+    ]
+    .into_iter()
+    .map(|(days, content)| seed(dir, days, content))
+    .collect()
+}
+
+#[test]
+fn stale_ids_resolve_to_their_replacement_while_a_backup_holds_them() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    let old = seed_history(root);
+
+    git_autosnap_cmd()
+        .args(["compact", "--days", "30"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("refs/autosnap/backup/"));
+
+    // The backup keeps the 80-day snapshot readable, but it was folded into the
+    // 40-day baseline, which is what its id stands for now.
+    git_autosnap_cmd()
+        .args(["cat", &old[1], "state.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("v40")
+        .stderr(predicate::str::contains(format!(
+            "Note: {} was rewritten by compact",
+            old[1]
+        )));
+
+    // A stale id is usable as a bisect bound on the rewritten chain.
+    git_autosnap_cmd()
+        .args(["bisect", "start", &old[4], &old[2]])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Bisecting"));
+}
+
+#[test]
+fn gc_keeps_the_chain_a_detached_head_points_at() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    git(root, &["config", "autosnap.gc.prune-grace", "0"]);
+    let old = seed_history(root);
+
+    // Detach HEAD and drop the branch, so no ref names the chain.
+    let autosnap = root.join(".autosnap");
+    let autosnap = autosnap.to_str().unwrap();
+    let branch = git(root, &["--git-dir", autosnap, "symbolic-ref", "HEAD"]);
+    git(
+        root,
+        &[
+            "--git-dir",
+            autosnap,
+            "update-ref",
+            "--no-deref",
+            "HEAD",
+            &old[4],
+        ],
+    );
+    git(root, &["--git-dir", autosnap, "update-ref", "-d", &branch]);
+
+    git_autosnap_cmd()
+        .args(["compact", "--days", "365"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    for id in &old {
+        git(root, &["--git-dir", autosnap, "cat-file", "-e", id]);
+    }
+    git_autosnap_cmd()
+        .args(["cat", "HEAD", "state.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("v2");
+}
+
+#[test]
+fn budget_drops_older_backups_but_keeps_the_one_just_made() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    git(root, &["config", "autosnap.gc.prune-grace", "0"]);
+    seed_history(root);
+
+    git_autosnap_cmd()
+        .args(["compact", "--days", "30"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // No store this small: the older backup goes, the new one stays.
+    git(root, &["config", "autosnap.max-store-size", "1"]);
+    git_autosnap_cmd()
+        .args(["compact", "--days", "5"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("refs/autosnap/backup/"))
+        .stderr(
+            predicate::str::contains("dropped 1 compact backups")
+                .and(predicate::str::contains("the backup of this compact holds")),
+        );
+
+    let autosnap = root.join(".autosnap");
+    let backups = git(
+        root,
+        &[
+            "--git-dir",
+            autosnap.to_str().unwrap(),
+            "for-each-ref",
+            "--format=%(refname)",
+            "refs/autosnap/backup/",
+        ],
+    );
+    assert_eq!(
+        backups
+            .lines()
+            .filter(|name| name.ends_with("/compacted"))
+            .count(),
+        1
+    );
+
+    // It still undoes the compact: v10 is back on top of the 40-day baseline.
+    git_autosnap_cmd()
+        .args(["compact", "--rollback"])
+        .current_dir(root)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .args(["cat", "HEAD~2", "state.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("v40");
+}
+
+#[test]
+fn rollback_restores_the_chain_and_keeps_newer_snapshots() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    let mut old = seed_history(root);
+    old.reverse();
+    let autosnap = root.join(".autosnap");
+    let autosnap = autosnap.to_str().unwrap();
+
+    git_autosnap_cmd()
+        .args(["compact", "--days", "30"])
+        .current_dir(root)
+        .assert()
+        .success();
+    assert_eq!(
+        git(
+            root,
+            &["--git-dir", autosnap, "rev-list", "--count", "HEAD"]
+        ),
+        "3"
+    );
+    std::fs::write(root.join("later.txt"), "later\n").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    git_autosnap_cmd()
+        .args(["compact", "--rollback"])
+        .current_dir(root)
+        .assert()
+        .success();
+    let after = git(root, &["--git-dir", autosnap, "log", "--format=%H"]);
+    let after: Vec<&str> = after.lines().collect();
+    assert_eq!(after.len(), 6, "the snapshot taken after compact is kept");
+    assert_eq!(after[1..], old[..]);
+    git_autosnap_cmd()
+        .args(["cat", "HEAD", "later.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("later\n");
+    assert_eq!(
+        git(
+            root,
+            &[
+                "--git-dir",
+                autosnap,
+                "for-each-ref",
+                "refs/autosnap/backup"
+            ]
+        ),
+        "",
+        "rollback consumes its backup"
+    );
+}
+
+#[test]
+fn store_budget_folds_oldest_snapshots_until_it_fits() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    git(root, &["config", "autosnap.gc.prune-grace", "0"]);
+    // Each snapshot holds 200 KiB of its own.
+    let mut head = Vec::new();
+    for n in 1..=6 {
+        head = noise(200 * 1024, n);
+        seed_bytes(root, 0, &format!("snap {n}"), &head);
+    }
+    git(root, &["config", "autosnap.max-store-size", "500k"]);
+
+    git_autosnap_cmd()
+        .arg("compact")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    let autosnap = root.join(".autosnap");
+    assert_eq!(
+        git(
+            root,
+            &[
+                "--git-dir",
+                autosnap.to_str().unwrap(),
+                "log",
+                "--format=%s"
+            ]
+        ),
+        "AUTOSNAP snap 6\nAUTOSNAP_COMPACT_BASELINE",
+        "only the two newest snapshots fit in the budget"
+    );
+    let store = dir_size(&autosnap.join("objects"));
+    assert!(
+        store <= 500 * 1024,
+        "store still over budget: {store} bytes"
+    );
+    let state = Command::new("git")
+        .args([
+            "--git-dir",
+            autosnap.to_str().unwrap(),
+            "show",
+            "HEAD:state.txt",
+        ])
+        .output()
+        .unwrap();
+    assert!(state.stdout == head, "HEAD tree must remain latest state");
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                dir_size(&entry.path())
+            } else {
+                entry.metadata().unwrap().len()
+            }
+        })
+        .sum()
+}
@@ -1,5 +1,5 @@
 use assert_cmd::Command;
-use git_autosnap::config::AutosnapConfig;
+use git_autosnap::{config::AutosnapConfig, core::git::GcBackend};
 use tempfile::tempdir;
 
 #[test]
@@ -21,8 +21,67 @@ fn config_loads_from_repo_config() {
     cmd.current_dir(root)
         .args(["config", "autosnap.compact.days", "5"]);
     cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.compact.retention", "2h:all,1d:10m"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.max-store-size", "500m"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.gc.backend", "git"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.compact.backup-grace", "2d"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.gc.prune-grace", "30m"]);
+    cmd.assert().success();
 
     let cfg = AutosnapConfig::load(root).expect("load config");
     assert_eq!(cfg.debounce_ms, 321);
     assert_eq!(cfg.compact_days, 5);
+    assert_eq!(cfg.compact_retention.as_deref(), Some("2h:all,1d:10m"));
+    assert_eq!(cfg.max_store_size, Some(500 * 1024 * 1024));
+    assert_eq!(cfg.gc_backend, GcBackend::Git);
+    assert_eq!(cfg.compact_backup_grace, 2 * 86_400);
+    assert_eq!(cfg.gc_prune_grace, 30 * 60);
+}
+
+#[test]
+fn config_rejects_unknown_gc_backend() {
+    let td = tempdir().unwrap();
+    let root = td.path();
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root).args(["init"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.gc.backend", "jgit"]);
+    cmd.assert().success();
+
+    let err = AutosnapConfig::load(root).unwrap_err();
+    assert!(format!("{err:#}").contains("unknown gc backend `jgit`"));
+}
+
+#[test]
+fn config_rejects_invalid_backup_grace() {
+    let td = tempdir().unwrap();
+    let root = td.path();
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root).args(["init"]);
+    cmd.assert().success();
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root)
+        .args(["config", "autosnap.compact.backup-grace", "a week"]);
+    cmd.assert().success();
+
+    let err = AutosnapConfig::load(root).unwrap_err();
+    assert!(format!("{err:#}").contains("invalid autosnap.compact.backup-grace `a week`"));
 }
@@ -87,7 +87,7 @@ fn test_diff_formats() {
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(predicate::str::contains("1 files changed"));
+        .stdout(predicate::str::contains("1 file changed"));
 
     // Test --name-only format
     git_autosnap_cmd()
@@ -173,3 +173,583 @@ fn test_diff_between_commits() {
         .success()
         .stdout(predicate::str::contains("file2.txt"));
 }
+
+#[test]
+fn test_diff_stat_variants() {
+    let temp_dir = TempDir::new().unwrap();
+
+    Command::new("git")
+        .arg("init")
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+    std::fs::write(temp_dir.path().join("src/lib.rs"), "a\nb\nc\n").unwrap();
+    std::fs::write(temp_dir.path().join("logo.bin"), [0u8, 1, 2, 3]).unwrap();
+
+    let output1 = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let commit1 = String::from_utf8_lossy(&output1).trim().to_string();
+
+    std::fs::write(temp_dir.path().join("src/lib.rs"), "a\nB\nc\nd\n").unwrap();
+    std::fs::write(temp_dir.path().join("logo.bin"), [0u8; 10]).unwrap();
+
+    let output2 = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let commit2 = String::from_utf8_lossy(&output2).trim().to_string();
+
+    git_autosnap_cmd()
+        .args(["diff", &commit1, &commit2, "--stat"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(" logo.bin   | Bin 4 -> 10 bytes\n")
+                .and(predicate::str::contains(" src/lib.rs |   3 ++-\n"))
+                .and(predicate::str::contains(
+                    " 2 files changed, 2 insertions(+), 1 deletion(-)",
+                )),
+        );
+
+    git_autosnap_cmd()
+        .args(["diff", &commit1, &commit2, "--stat", "--stat-width", "40"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" src/lib.rs |"));
+
+    git_autosnap_cmd()
+        .args(["diff", &commit1, &commit2, "--numstat"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout("-\t-\tlogo.bin\n2\t1\tsrc/lib.rs\n");
+
+    // --stat-width only sizes --stat; it does not turn --numstat into it.
+    git_autosnap_cmd()
+        .args([
+            "diff",
+            &commit1,
+            &commit2,
+            "--numstat",
+            "--stat-width",
+            "40",
+        ])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--stat"));
+
+    git_autosnap_cmd()
+        .args(["diff", &commit1, &commit2, "--shortstat"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(" 2 files changed, 2 insertions(+), 1 deletion(-)\n");
+
+    git_autosnap_cmd()
+        .args(["diff", &commit1, &commit2, "--dirstat=files"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout("  50.0% src/\n");
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+fn snapshot(dir: &std::path::Path, message: &str) -> String {
+    let output = git_autosnap_cmd()
+        .args(["once", message])
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+/// Commits the first snapshot's content to the main repo, then changes text, binary,
+/// mode and file set for the second snapshot. Returns both snapshots.
+fn setup_patch_repo(root: &std::path::Path) -> (String, String) {
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("a.txt"), "1\n2\n3\n4\n5\n6\n7\n8\n").unwrap();
+    std::fs::write(root.join("gone.txt"), "bye\n").unwrap();
+    std::fs::write(root.join("run.sh"), "echo hi\n").unwrap();
+    std::fs::write(root.join("blob.bin"), [0u8, 159, 146, 150, 1, 2]).unwrap();
+    let first = snapshot(root, "base");
+    git(root, &["add", "-A"]);
+    git(root, &["commit", "-qm", "base"]);
+
+    std::fs::rename(root.join("a.txt"), root.join("b.txt")).unwrap();
+    std::fs::write(root.join("b.txt"), "1\n2\n3\nfour\n5\n6\n7\n8\n").unwrap();
+    std::fs::remove_file(root.join("gone.txt")).unwrap();
+    std::fs::write(root.join("blob.bin"), [9u8, 0, 8, 0, 7, 0, 6]).unwrap();
+    std::fs::write(root.join("new.txt"), "fresh").unwrap();
+    let mut perms = std::fs::metadata(root.join("run.sh"))
+        .unwrap()
+        .permissions();
+    std::os::unix::fs::PermissionsExt::set_mode(&mut perms, 0o755);
+    std::fs::set_permissions(root.join("run.sh"), perms).unwrap();
+    let second = snapshot(root, "second");
+    (first, second)
+}
+
+fn snapshot_tree(root: &std::path::Path, commit: &str) -> String {
+    git(
+        root,
+        &[
+            "--git-dir=.autosnap",
+            "rev-parse",
+            &format!("{commit}^{{tree}}"),
+        ],
+    )
+}
+
+#[test]
+fn test_diff_patch_applies_with_git_apply() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_patch_repo(root);
+
+    let output = git_autosnap_cmd()
+        .args(["diff", &first, &second, "--patch"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let patch = String::from_utf8(output).unwrap();
+    assert!(patch.contains("diff --git a/a.txt b/b.txt\n"), "{patch}");
+    assert!(
+        patch.contains("old mode 100644\nnew mode 100755\n"),
+        "{patch}"
+    );
+    assert!(patch.contains("deleted file mode 100644\n"), "{patch}");
+    assert!(patch.contains("new file mode 100644\n"), "{patch}");
+    assert!(patch.contains("GIT binary patch\n"), "{patch}");
+    assert!(!patch.contains('\u{1b}'), "no colour when piped");
+
+    git(root, &["reset", "-q", "--hard"]);
+    git(root, &["clean", "-fdq", "-e", ".autosnap"]);
+    let patch_file = root.join(".autosnap/test.patch");
+    std::fs::write(&patch_file, &patch).unwrap();
+    git(root, &["apply", "--index", patch_file.to_str().unwrap()]);
+
+    assert_eq!(git(root, &["write-tree"]), snapshot_tree(root, &second));
+}
+
+#[test]
+fn test_diff_format_patch_applies_with_git_am() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_patch_repo(root);
+
+    let output = git_autosnap_cmd()
+        .args(["diff", &first, &second, "--format-patch"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let mail = String::from_utf8(output).unwrap();
+    assert!(mail.starts_with("From "), "{mail}");
+    assert!(mail.contains("\nSubject: [PATCH] AUTOSNAP["), "{mail}");
+    assert!(mail.contains(" rename a.txt => b.txt ("), "{mail}");
+    assert!(mail.contains(" create mode 100644 new.txt\n"), "{mail}");
+    assert!(mail.contains(" delete mode 100644 gone.txt\n"), "{mail}");
+    assert!(
+        mail.contains(" mode change 100644 => 100755 run.sh\n"),
+        "{mail}"
+    );
+
+    git(root, &["reset", "-q", "--hard"]);
+    git(root, &["clean", "-fdq", "-e", ".autosnap"]);
+    let mbox = root.join(".autosnap/test.mbox");
+    std::fs::write(&mbox, &mail).unwrap();
+    git(root, &["am", "-q", mbox.to_str().unwrap()]);
+
+    assert_eq!(
+        git(root, &["rev-parse", "HEAD^{tree}"]),
+        snapshot_tree(root, &second)
+    );
+}
+
+#[test]
+fn test_diff_against_main_revision() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("a.txt"), "one\n").unwrap();
+    std::fs::write(root.join("kept.txt"), "kept\n").unwrap();
+    git(root, &["add", "-A"]);
+    git(root, &["commit", "-qm", "main commit"]);
+
+    std::fs::write(root.join("a.txt"), "two\n").unwrap();
+    let snap = snapshot(root, "edit");
+    std::fs::write(root.join("b.txt"), "new\n").unwrap();
+
+    // Working tree against the last real commit
+    git_autosnap_cmd()
+        .args(["diff", "--against-main", "HEAD", "--name-status"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("M\ta.txt\nA\tb.txt\n");
+
+    // A snapshot against the last real commit
+    git_autosnap_cmd()
+        .args(["diff", "--against-main", "HEAD", &snap])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("diff --git a/a.txt b/a.txt\n")
+                .and(predicate::str::contains("-one\n+two\n"))
+                .and(predicate::str::contains("b.txt").not()),
+        );
+
+    git_autosnap_cmd()
+        .args(["diff", "--against-main", "no-such-branch"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "failed to find main repository revision: no-such-branch",
+        ));
+
+    // Objects read from the main store must not leak into later snapshots.
+    snapshot(root, "after");
+    git(
+        root,
+        &["--git-dir=.autosnap", "fsck", "--connectivity-only"],
+    );
+}
+
+#[test]
+fn test_diff_skips_pager_when_not_a_terminal() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git(root, &["init"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+    std::fs::write(root.join("a.txt"), "one\n").unwrap();
+    let first = snapshot(root, "first");
+    std::fs::write(root.join("a.txt"), "two\n").unwrap();
+    let second = snapshot(root, "second");
+
+    git_autosnap_cmd()
+        .args(["diff", &first, &second, "--name-only"])
+        .env("GIT_PAGER", "sed s/^/paged:/")
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("a.txt\n");
+}
+
+fn setup_tuning_repo(root: &std::path::Path) -> (String, String) {
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("spaces.txt"), "a b\nc\n").unwrap();
+    std::fs::write(root.join("words.txt"), "1\n2\nthe quick fox\n4\n5\n").unwrap();
+    std::fs::write(root.join("gone.txt"), "bye\n").unwrap();
+    let first = snapshot(root, "base");
+
+    std::fs::write(root.join("spaces.txt"), "a   b\nc\n").unwrap();
+    std::fs::write(root.join("words.txt"), "1\n2\nthe slow fox\n4\n5\n").unwrap();
+    std::fs::remove_file(root.join("gone.txt")).unwrap();
+    std::fs::write(root.join("added.txt"), "new\n").unwrap();
+    let second = snapshot(root, "second");
+    (first, second)
+}
+
+#[test]
+fn test_diff_ignore_whitespace() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_tuning_repo(root);
+
+    for flag in ["-w", "-b"] {
+        git_autosnap_cmd()
+            .args(["diff", flag, &first, &second])
+            .current_dir(root)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("words.txt"))
+            .stdout(predicate::str::contains("spaces.txt").not());
+    }
+
+    // Like git, name-status still lists whitespace-only changes.
+    git_autosnap_cmd()
+        .args(["diff", "-w", "--name-status", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("M\tspaces.txt"));
+}
+
+#[test]
+fn test_diff_context_lines() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_tuning_repo(root);
+
+    git_autosnap_cmd()
+        .args(["diff", "-U0", &first, &second, "words.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@@ -3 +3 @@\n-the quick fox\n+the slow fox\n",
+        ));
+
+    git_autosnap_cmd()
+        .args(["diff", "--unified=1", &first, &second, "words.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@@ -2,3 +2,3 @@\n 2\n-the quick fox\n+the slow fox\n 4\n",
+        ));
+}
+
+#[test]
+fn test_diff_word_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_tuning_repo(root);
+
+    git_autosnap_cmd()
+        .args(["diff", "--word-diff", &first, &second, "words.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("the [-quick-]{+slow+} fox\n"));
+
+    git_autosnap_cmd()
+        .args(["diff", "--word-diff=plain", "--patch", &first, &second])
+        .current_dir(root)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_diff_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_tuning_repo(root);
+
+    git_autosnap_cmd()
+        .args(["diff", "--name-status", "--diff-filter=A", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("A\tadded.txt\n");
+
+    git_autosnap_cmd()
+        .args(["diff", "--name-only", "--diff-filter=d", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("added.txt\nspaces.txt\nwords.txt\n");
+
+    git_autosnap_cmd()
+        .args(["diff", "--diff-filter=Z", &first, &second])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown change class 'Z'"));
+}
+
+#[test]
+fn test_diff_respects_gitattributes() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git(root, &["config", "diff.upper.textconv", "tr a-z A-Z <"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(
+        root.join(".gitattributes"),
+        "*.up diff=upper\n*.lock -diff\ngen/** linguist-generated\n",
+    )
+    .unwrap();
+    std::fs::create_dir(root.join("gen")).unwrap();
+    std::fs::write(root.join("x.up"), "hello\nworld\n").unwrap();
+    std::fs::write(root.join("same.up"), "Same\n").unwrap();
+    std::fs::write(root.join("deps.lock"), "a\n").unwrap();
+    std::fs::write(root.join("gen/out.js"), "g\n").unwrap();
+    let first = snapshot(root, "base");
+
+    std::fs::write(root.join("x.up"), "hello\nthere\n").unwrap();
+    std::fs::write(root.join("same.up"), "same\n").unwrap();
+    std::fs::write(root.join("deps.lock"), "b\n").unwrap();
+    std::fs::write(root.join("gen/out.js"), "h\n").unwrap();
+    let second = snapshot(root, "second");
+
+    let output = git_autosnap_cmd()
+        .args(["diff", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8_lossy(&output);
+    assert!(output.contains("Binary files a/deps.lock and b/deps.lock differ\n"));
+    assert!(!output.contains("+b\n"));
+    assert!(output.contains("Generated files a/gen/out.js and b/gen/out.js differ\n"));
+    assert!(output.contains("@@ -1,2 +1,2 @@\n HELLO\n-WORLD\n+THERE\n"));
+    // Identical textconv output hides the file, as in git.
+    assert!(!output.contains("same.up"));
+
+    git_autosnap_cmd()
+        .args(["diff", "--numstat", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("-\t-\tdeps.lock\n1\t1\tgen/out.js\n1\t1\tsame.up\n1\t1\tx.up\n");
+}
+
+#[test]
+fn test_diff_reads_gitattributes_from_the_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    git(root, &["init"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join(".gitattributes"), "*.lock -diff\n").unwrap();
+    std::fs::write(root.join("deps.lock"), "a\n").unwrap();
+    let first = snapshot(root, "base");
+
+    // The working tree drops the rule; the snapshot still has it.
+    std::fs::write(root.join(".gitattributes"), "").unwrap();
+    std::fs::write(root.join("deps.lock"), "b\n").unwrap();
+
+    let output = git_autosnap_cmd()
+        .args(["diff", &first])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8_lossy(&output);
+    assert!(output.contains("Binary files a/deps.lock and b/deps.lock differ\n"));
+    assert!(!output.contains("+b\n"));
+}
+
+#[test]
+fn test_diff_quiet_and_exit_code() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup_tuning_repo(root);
+
+    git_autosnap_cmd()
+        .args(["diff", "--quiet", &first, &second])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("");
+    git_autosnap_cmd()
+        .args(["diff", "--quiet", &second, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("");
+
+    // Ignored changes do not count, as in git.
+    git_autosnap_cmd()
+        .args(["diff", "--quiet", "-w", &first, &second, "spaces.txt"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    git_autosnap_cmd()
+        .args([
+            "diff",
+            "--exit-code",
+            "--name-only",
+            &first,
+            &second,
+            "words.txt",
+        ])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .stdout("words.txt\n");
+    git_autosnap_cmd()
+        .args(["diff", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success();
+}
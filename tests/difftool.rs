@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn snapshot(dir: &Path) -> String {
+    let output = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+/// Two snapshots where `same.txt` is untouched, `edit.txt` changes, `old.txt` is
+/// deleted and `new.txt` is added.
+fn setup(root: &Path) -> (String, String) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("same.txt"), "same\n").unwrap();
+    std::fs::write(root.join("edit.txt"), "before\n").unwrap();
+    std::fs::write(root.join("old.txt"), "old\n").unwrap();
+    let first = snapshot(root);
+
+    std::fs::write(root.join("edit.txt"), "after\n").unwrap();
+    std::fs::remove_file(root.join("old.txt")).unwrap();
+    std::fs::write(root.join("new.txt"), "new\n").unwrap();
+    let second = snapshot(root);
+    (first, second)
+}
+
+fn configure_tool(root: &Path, cmd: &str) {
+    Command::new("git")
+        .args(["config", "difftool.probe.cmd", cmd])
+        .current_dir(root)
+        .assert()
+        .success();
+    Command::new("git")
+        .args(["config", "autosnap.difftool", "probe"])
+        .current_dir(root)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_difftool_runs_once_per_changed_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+    configure_tool(
+        root,
+        r#"printf '%s|%s|%s\n' "$MERGED" "$(cat "$LOCAL")" "$(cat "$REMOTE")""#,
+    );
+
+    git_autosnap_cmd()
+        .args(["difftool", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("edit.txt|before|after\nnew.txt||new\nold.txt|old|\n");
+}
+
+#[test]
+fn test_difftool_dir_diff_extracts_only_changed_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+    configure_tool(
+        root,
+        r#"cd "$LOCAL" && ls && echo -- && cd "$REMOTE" && ls"#,
+    );
+
+    git_autosnap_cmd()
+        .args(["difftool", "--dir-diff", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("edit.txt\nold.txt\n--\nedit.txt\nnew.txt\n");
+}
+
+#[test]
+fn test_difftool_requires_a_tool() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+
+    git_autosnap_cmd()
+        .args(["difftool", &first, &second])
+        .current_dir(root)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no diff tool configured"));
+
+    // --tool falls back to running the named program on both files.
+    git_autosnap_cmd()
+        .args(["difftool", "--tool", "cat", &first, &second, "edit.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("before\nafter\n");
+}
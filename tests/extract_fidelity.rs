@@ -0,0 +1,90 @@
+use std::{os::unix::fs::PermissionsExt, path::Path};
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn init_repo(dir: &Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_shell_recreates_symlinks_and_modes() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("target.txt"), "payload").unwrap();
+    std::os::unix::fs::symlink("target.txt", root.join("link")).unwrap();
+    std::os::unix::fs::symlink("../../outside", root.join("evil")).unwrap();
+    std::fs::write(root.join("run.sh"), "#!/bin/sh\necho hi\n").unwrap();
+    std::fs::set_permissions(root.join("run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    git_autosnap_cmd()
+        .arg("shell")
+        .env("SHELL", "/bin/sh")
+        .write_stdin(
+            "readlink link; cat link; echo; test -x run.sh && echo EXEC; \
+             test -x target.txt || echo NOEXEC; test -L evil || echo NOEVIL\n",
+        )
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("target.txt\npayload")
+                .and(predicate::str::contains("EXEC"))
+                .and(predicate::str::contains("NOEXEC"))
+                .and(predicate::str::contains("NOEVIL")),
+        )
+        .stderr(predicate::str::contains(
+            "evil: symlink to ../../outside escapes",
+        ));
+}
+
+#[test]
+fn test_shell_refuses_symlinks_chained_out_of_the_root() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::create_dir(root.join("sub")).unwrap();
+    std::os::unix::fs::symlink("..", root.join("sub/b")).unwrap();
+    std::os::unix::fs::symlink("b/../x", root.join("sub/a")).unwrap();
+
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // Lexically `sub/b/../x` is `sub/x`, but through `b -> ..` it is one level above
+    // the extraction root.
+    git_autosnap_cmd()
+        .arg("shell")
+        .env("SHELL", "/bin/sh")
+        .write_stdin("readlink sub/b; test -L sub/a || echo NOCHAIN\n")
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("..\nNOCHAIN"))
+        .stderr(predicate::str::contains("sub/a: symlink to b/../x escapes"));
+}
@@ -0,0 +1,113 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn init_repo(dir: &Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn old_time() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+}
+
+fn set_mtime(path: &Path, time: SystemTime) {
+    std::fs::File::open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+fn mtime(path: &Path) -> SystemTime {
+    std::fs::metadata(path).unwrap().modified().unwrap()
+}
+
+#[test]
+fn test_restore_reapplies_recorded_mtime() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    let file = root.join("a.txt");
+    std::fs::write(&file, "original").unwrap();
+    set_mtime(&file, old_time());
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(&file, "modified").unwrap();
+    git_autosnap_cmd()
+        .args(["restore", "--force", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+    assert_eq!(mtime(&file), old_time());
+}
+
+#[test]
+fn test_restore_no_mtimes_keeps_fresh_timestamp() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    let file = root.join("a.txt");
+    std::fs::write(&file, "original").unwrap();
+    set_mtime(&file, old_time());
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(&file, "modified").unwrap();
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--no-mtimes", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+    assert_ne!(mtime(&file), old_time());
+}
+
+#[test]
+fn test_mtime_recording_can_be_disabled() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    Command::new("git")
+        .args(["config", "autosnap.metadata.mtimes", "false"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("a.txt"), "content").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    let repo = git2::Repository::open(root.join(".autosnap")).unwrap();
+    assert!(repo.find_reference("refs/notes/autosnap-metadata").is_err());
+}
@@ -0,0 +1,159 @@
+use std::{fmt::Write as _, path::Path};
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn init_repo(dir: &Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn snapshot(dir: &Path) -> String {
+    let output = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+fn numbered_lines(count: usize) -> String {
+    (1..=count).fold(String::new(), |mut out, i| {
+        let _ = writeln!(out, "line {i}");
+        out
+    })
+}
+
+/// `old.txt` is moved to `new.txt` with one of its ten lines edited (90% similar).
+fn setup(root: &Path) -> (String, String) {
+    init_repo(root);
+    std::fs::write(root.join("old.txt"), numbered_lines(10)).unwrap();
+    let first = snapshot(root);
+
+    std::fs::remove_file(root.join("old.txt")).unwrap();
+    let edited = numbered_lines(10).replace("line 5\n", "line five\n");
+    std::fs::write(root.join("new.txt"), edited).unwrap();
+    let second = snapshot(root);
+    (first, second)
+}
+
+#[test]
+fn test_diff_detects_renames_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+
+    git_autosnap_cmd()
+        .args(["diff", &first, &second, "--name-status"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^R0[0-9]{2}\told\.txt\tnew\.txt\n$").unwrap());
+
+    git_autosnap_cmd()
+        .args(["diff", &first, &second, "--stat"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" old.txt => new.txt | 2 +-\n"));
+
+    git_autosnap_cmd()
+        .args(["diff", &first, &second])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("rename from old.txt\nrename to new.txt\n")
+                .and(predicate::str::contains("similarity index ")),
+        );
+}
+
+#[test]
+fn test_rename_thresholds_and_opt_out() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+
+    git_autosnap_cmd()
+        .args(["diff", &first, &second, "--name-status", "--no-renames"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("A\tnew.txt\nD\told.txt\n");
+
+    // Git's attached form: the edit leaves the pair below 99% similar.
+    git_autosnap_cmd()
+        .args(["diff", &first, &second, "--name-status", "-M99%"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("A\tnew.txt\nD\told.txt\n");
+
+    git_autosnap_cmd()
+        .args([
+            "diff",
+            &first,
+            &second,
+            "--name-status",
+            "--find-renames=60%",
+        ])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("R0"));
+}
+
+#[test]
+fn test_file_log_follows_renames() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let (first, second) = setup(root);
+
+    let output = git_autosnap_cmd()
+        .args(["file-log", "new.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let log = String::from_utf8_lossy(&output);
+
+    let rename_at = log.find(&second[..7]).expect("rename snapshot listed");
+    let add_at = log.find(&first[..7]).expect("original snapshot listed");
+    assert!(rename_at < add_at, "newest first:\n{log}");
+    assert!(log.contains("\told.txt\tnew.txt\n"), "{log}");
+    assert!(log.contains("A\told.txt\n"), "{log}");
+
+    git_autosnap_cmd()
+        .args(["file-log", "new.txt", "--no-renames"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("A\tnew.txt").and(predicate::str::contains("old.txt").not()),
+        );
+
+    git_autosnap_cmd()
+        .args(["file-log", "missing.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("No snapshots touch missing.txt\n");
+}
@@ -0,0 +1,158 @@
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn git(dir: &std::path::Path, args: &[&str]) {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn snapshot(dir: &std::path::Path) {
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+/// Two snapshots on `main`, then one on `feature`.
+fn setup_repo(root: &std::path::Path) {
+    git(root, &["init", "-b", "main"]);
+    git(root, &["config", "user.name", "Test User"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git(root, &["commit", "--allow-empty", "-qm", "init"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("a.txt"), "a\nb\n").unwrap();
+    snapshot(root);
+    std::fs::write(root.join("a.txt"), "a\nc\n").unwrap();
+    std::fs::write(root.join("b.txt"), "x\n").unwrap();
+    snapshot(root);
+    git(root, &["checkout", "-qb", "feature"]);
+    std::fs::write(root.join("b.txt"), "x\ny\n").unwrap();
+    snapshot(root);
+}
+
+#[test]
+fn test_report_groups_by_branch() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+
+    let output = git_autosnap_cmd()
+        .args(["report", "--since", "1h", "--format", "json"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(report["snapshots"], 3);
+    assert_eq!(report["files"], 2);
+
+    let branches = report["branches"].as_array().unwrap();
+    assert_eq!(branches.len(), 2);
+    assert_eq!(branches[0]["branch"], "feature");
+    assert_eq!(branches[0]["snapshots"], 1);
+    assert_eq!(branches[0]["files"][0]["path"], "b.txt");
+    assert_eq!(branches[0]["files"][0]["insertions"], 1);
+
+    let main = &branches[1];
+    assert_eq!(main["branch"], "main");
+    assert_eq!(main["snapshots"], 2);
+    let a = &main["files"][0];
+    assert_eq!(a["path"], "a.txt");
+    assert_eq!(a["snapshots"], 2);
+    // Summed per snapshot: "a b" added, then "b" replaced by "c".
+    assert_eq!(a["insertions"], 3);
+    assert_eq!(a["deletions"], 1);
+    assert!(a["first_touched"].as_str().is_some());
+}
+
+#[test]
+fn test_report_skips_snapshots_of_other_branches_in_between() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+
+    // feature took the last snapshot; main's next one comes after it.
+    std::fs::write(root.join("c.txt"), "1\n2\n3\n").unwrap();
+    snapshot(root);
+    git(root, &["checkout", "-q", "main"]);
+    std::fs::write(root.join("a.txt"), "a\nc\nd\n").unwrap();
+    snapshot(root);
+
+    let output = git_autosnap_cmd()
+        .args(["report", "--format", "json"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let branches = report["branches"].as_array().unwrap();
+    let main = &branches[0];
+    assert_eq!(main["branch"], "main");
+    let paths: Vec<_> = main["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["a.txt", "b.txt"]);
+    assert_eq!(main["files"][0]["insertions"], 4);
+    assert_eq!(main["files"][0]["deletions"], 1);
+    // Only main's own "x" line, not the "y" feature added.
+    assert_eq!(main["files"][1]["insertions"], 1);
+
+    let feature = &branches[1];
+    assert_eq!(feature["branch"], "feature");
+    assert_eq!(feature["snapshots"], 2);
+    assert_eq!(feature["files"][0]["path"], "b.txt");
+    assert_eq!(feature["files"][1]["path"], "c.txt");
+    assert_eq!(feature["files"][1]["insertions"], 3);
+}
+
+#[test]
+fn test_report_text_and_markdown() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    setup_repo(root);
+
+    git_autosnap_cmd()
+        .arg("report")
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("3 snapshots, 2 files"))
+        .stdout(predicate::str::contains("main (2 snapshots)"))
+        .stdout(predicate::str::is_match(r"\n  a\.txt +2x +\+3 -1 ").unwrap());
+
+    git_autosnap_cmd()
+        .args(["report", "--format", "markdown"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("### feature (1 snapshot)"))
+        .stdout(predicate::str::contains("| `b.txt` | 1 | +1 -0 |"));
+
+    git_autosnap_cmd()
+        .args(["report", "--since", "soon"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid time: soon"));
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn init_repo(dir: &Path) {
+    Command::new("git")
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+fn snapshot(dir: &Path) -> String {
+    let output = git_autosnap_cmd()
+        .arg("once")
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+/// First snapshot holds `a.txt` and `b.txt`; the latest one changes `a.txt`, drops
+/// `b.txt` and adds `sub/c.txt`. `d.txt` exists only in the working copy.
+fn setup(root: &Path) -> String {
+    init_repo(root);
+    std::fs::write(root.join("a.txt"), "one\n").unwrap();
+    std::fs::write(root.join("b.txt"), "bee\n").unwrap();
+    let first = snapshot(root);
+
+    std::fs::write(root.join("a.txt"), "one two\n").unwrap();
+    std::fs::remove_file(root.join("b.txt")).unwrap();
+    std::fs::create_dir(root.join("sub")).unwrap();
+    std::fs::write(root.join("sub/c.txt"), "sea\n").unwrap();
+    snapshot(root);
+
+    std::fs::write(root.join("d.txt"), "unsaved\n").unwrap();
+    first
+}
+
+#[test]
+fn test_dry_run_plan_lists_actions() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let first = setup(root);
+
+    git_autosnap_cmd()
+        .args(["restore", "--dry-run", &first])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Working copy has edits newer than the latest snapshot")
+                .and(predicate::str::is_match(r"modify\s+a\.txt\s+-4 B").unwrap())
+                .and(predicate::str::is_match(r"create\s+b\.txt\s+\+4 B").unwrap())
+                .and(predicate::str::contains(
+                    "2 to change (1 create, 1 modify, 0 delete)",
+                ))
+                .and(predicate::str::contains(
+                    "2 files not in the snapshot are kept",
+                )),
+        );
+
+    // Nothing was touched.
+    assert!(!root.join("b.txt").exists());
+    assert!(root.join("d.txt").exists());
+}
+
+#[test]
+fn test_dry_run_json_full_fails_on_unsaved_edits() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let first = setup(root);
+
+    let output = git_autosnap_cmd()
+        .args(["restore", "--dry-run", "--full", "--json", &first])
+        .current_dir(root)
+        .assert()
+        .code(1)
+        .get_output()
+        .stdout
+        .clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(plan["full"], true);
+    assert_eq!(plan["working_copy_dirty"], true);
+
+    let entry = |path: &str| {
+        plan["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["path"] == path)
+            .unwrap_or_else(|| panic!("{path} missing from plan"))
+            .clone()
+    };
+    assert_eq!(entry("sub/c.txt")["action"], "delete");
+    assert_eq!(entry("sub/c.txt")["unsaved"], false);
+    assert_eq!(entry("d.txt")["action"], "delete");
+    assert_eq!(entry("d.txt")["unsaved"], true);
+    assert_eq!(entry("b.txt")["action"], "create");
+}
+
+#[test]
+fn test_full_restore_deletes_planned_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let first = setup(root);
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--full", &first])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed: sub/c.txt"));
+
+    assert!(!root.join("sub").exists());
+    assert!(!root.join("d.txt").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("b.txt")).unwrap(),
+        "bee\n"
+    );
+}
+
+#[test]
+fn test_overlay_restore_keeps_files_missing_from_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let first = setup(root);
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", &first])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read_to_string(root.join("a.txt")).unwrap(),
+        "one\n"
+    );
+    assert!(root.join("sub/c.txt").exists());
+    assert!(root.join("d.txt").exists());
+}
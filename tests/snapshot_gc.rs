@@ -33,6 +33,14 @@ async fn snapshot_commit_message_and_contents() -> Result<()> {
         .and(predicate::str::contains("AUTOSNAP["));
     assert!(re.eval(subject.trim()), "bad subject: {subject}");
 
+    let trigger = exec_in(
+        &container,
+        "/repo",
+        "git --git-dir=.autosnap log -1 --format=%(trailers:key=Autosnap-Trigger,valueonly)",
+    )
+    .await?;
+    assert_eq!(trigger.trim(), "manual");
+
     let listing = exec_in(
         &container,
         "/repo",
@@ -0,0 +1,291 @@
+use std::path::Path;
+
+use assert_cmd::{Command, cargo::cargo_bin_cmd};
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+fn git_autosnap_cmd() -> Command {
+    cargo_bin_cmd!("git-autosnap")
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+fn init_repo(dir: &Path) {
+    git(dir, &["init"]);
+    git(dir, &["config", "user.name", "Test User"]);
+    git(dir, &["config", "user.email", "test@example.com"]);
+    git_autosnap_cmd()
+        .arg("init")
+        .current_dir(dir)
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_restore_staged_brings_back_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    git(root, &["add", "a.txt"]);
+    git(root, &["commit", "-m", "initial"]);
+
+    // Stage v2, then keep editing to v3 without staging.
+    std::fs::write(root.join("a.txt"), "v2\n").unwrap();
+    git(root, &["add", "a.txt"]);
+    std::fs::write(root.join("a.txt"), "v3\n").unwrap();
+
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // A bad reset throws away both the staged and unstaged work.
+    git(root, &["reset", "--hard"]);
+    assert_eq!(git(root, &["show", ":a.txt"]), "v1\n");
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--staged", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("staged state"));
+
+    assert_eq!(git(root, &["show", ":a.txt"]), "v2\n");
+    assert_eq!(std::fs::read_to_string(root.join("a.txt")).unwrap(), "v3\n");
+}
+
+#[test]
+fn test_restore_staged_leaves_untracked_files_unstaged() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    git(root, &["add", "a.txt"]);
+    std::fs::write(root.join("notes.txt"), "scratch\n").unwrap();
+
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // The index matches the tracked files, and the snapshot still records it.
+    let repo = git2::Repository::open(root.join(".autosnap")).unwrap();
+    let staged_refs = repo
+        .references_glob("refs/autosnap/staged/*")
+        .unwrap()
+        .count();
+    assert_eq!(staged_refs, 1);
+
+    git(root, &["rm", "--cached", "-q", "a.txt"]);
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--staged", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    assert_eq!(git(root, &["ls-files"]), "a.txt\n");
+}
+
+#[test]
+fn test_restore_staged_fails_without_recorded_state() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    git(root, &["add", "a.txt"]);
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    let repo = git2::Repository::open(root.join(".autosnap")).unwrap();
+    for reference in repo.references_glob("refs/autosnap/staged/*").unwrap() {
+        reference.unwrap().delete().unwrap();
+    }
+    std::fs::write(root.join("a.txt"), "v2\n").unwrap();
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--staged", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no staged state was recorded"));
+
+    // Nothing was restored either.
+    assert_eq!(std::fs::read_to_string(root.join("a.txt")).unwrap(), "v2\n");
+}
+
+fn snapshot_then_stage_newer(root: &Path) {
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b1\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+
+    std::fs::write(root.join("a.txt"), "v2\n").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("a.txt"), "v3\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b3\n").unwrap();
+    git(root, &["add", "."]);
+}
+
+#[test]
+fn test_restore_leaves_index_untouched_by_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    snapshot_then_stage_newer(root);
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", "HEAD", "a.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("index was left untouched"));
+
+    assert_eq!(std::fs::read_to_string(root.join("a.txt")).unwrap(), "v2\n");
+    assert_eq!(git(root, &["show", ":a.txt"]), "v3\n");
+    assert_eq!(git(root, &["show", ":b.txt"]), "b3\n");
+}
+
+#[test]
+fn test_restore_index_update_only_touches_restored_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+    snapshot_then_stage_newer(root);
+
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--index", "update", "HEAD", "a.txt"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 entry changed:\n  M\ta.txt"));
+
+    assert_eq!(git(root, &["show", ":a.txt"]), "v2\n");
+    assert_eq!(git(root, &["show", ":b.txt"]), "b3\n");
+}
+
+#[test]
+fn test_restore_index_update_takes_paths_literally() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("f[ab].txt"), "v0\n").unwrap();
+    std::fs::write(root.join("fa.txt"), "staged\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-m", "initial"]);
+
+    // `fa.txt` matches `f[ab].txt` as a glob; its edit stays unstaged.
+    std::fs::write(root.join("f[ab].txt"), "v1\n").unwrap();
+    std::fs::write(root.join("fa.txt"), "edited\n").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    std::fs::write(root.join("f[ab].txt"), "v2\n").unwrap();
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--index", "update", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 entry changed:\n  M\tf[ab].txt"));
+
+    assert_eq!(git(root, &["show", ":f[ab].txt"]), "v1\n");
+    assert_eq!(git(root, &["show", ":fa.txt"]), "staged\n");
+}
+
+#[test]
+fn test_restore_staged_only_resets_restored_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    std::fs::write(root.join("b.txt"), "b1\n").unwrap();
+    git(root, &["add", "."]);
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // `keep.txt` is not in the snapshot; an overlay restore leaves it and its entry be.
+    std::fs::write(root.join("a.txt"), "v2\n").unwrap();
+    std::fs::write(root.join("keep.txt"), "new\n").unwrap();
+    git(root, &["add", "."]);
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--staged", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success();
+
+    assert_eq!(git(root, &["show", ":a.txt"]), "v1\n");
+    assert_eq!(git(root, &["show", ":keep.txt"]), "new\n");
+}
+
+#[test]
+fn test_index_only_change_gets_a_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    init_repo(root);
+
+    std::fs::write(root.join("a.txt"), "v1\n").unwrap();
+    git(root, &["add", "a.txt"]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+    std::fs::write(root.join("a.txt"), "v2\n").unwrap();
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success();
+
+    // Nothing changed at all: no snapshot.
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout("");
+
+    // Only the index changes.
+    git(root, &["add", "a.txt"]);
+    git_autosnap_cmd()
+        .arg("once")
+        .current_dir(root)
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty().not());
+
+    git(root, &["reset", "-q"]);
+    git_autosnap_cmd()
+        .args(["restore", "--force", "--staged", "HEAD"])
+        .current_dir(root)
+        .assert()
+        .success();
+    assert_eq!(git(root, &["show", ":a.txt"]), "v2\n");
+}